@@ -1,19 +1,44 @@
 #![allow(dead_code)]
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fs;
 use std::mem::size_of;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use gl::types::{GLenum, GLuint};
+use gl::types::{GLchar, GLenum, GLint, GLuint};
 use gl::{
-    COMPILE_STATUS, COMPUTE_SHADER, FALSE, FLOAT, FRAGMENT_SHADER, INFO_LOG_LENGTH, LINK_STATUS,
-    STATIC_DRAW, TRIANGLES, TRUE, UNSIGNED_INT, VERTEX_SHADER,
+    ACTIVE_UNIFORMS, ACTIVE_UNIFORM_BLOCKS, ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH,
+    ACTIVE_UNIFORM_MAX_LENGTH, COMPILE_STATUS, COMPUTE_SHADER, DYNAMIC_DRAW, FALSE, FLOAT,
+    FRAGMENT_SHADER, INFO_LOG_LENGTH, LINK_STATUS, STATIC_DRAW, TRIANGLES, TRUE, UNSIGNED_INT,
+    VERTEX_SHADER,
 };
 use glfw::{Context, CursorMode, Glfw, GlfwReceiver, PWindow, WindowEvent};
-use glm::{Mat4, Vec3, Vec4};
+use glm::{Mat3, Mat4, Vec3, Vec4};
 
 use crate::buffer::{Buffer, BufferType, VertexArray};
-use crate::{Camera, ObjectManager, Scene, TriangleIndecies, Vertex, WindowOptions};
+use crate::object::ObjectType;
+use bytemuck::Zeroable;
+
+use crate::scene::WorldTransforms;
+use crate::{
+    Camera, Film, Light, Material, ObjectHandle, ObjectManager, Ray, Scene, WindowOptions,
+};
+
+// Must match MAX_LIGHTS in fragment.glsl
+const MAX_LIGHTS: usize = 16;
+// Uniform buffer binding of the `Camera` block, every program's block is attached to it
+const CAMERA_BINDING: GLuint = 0;
+// Uniforms `send_lights` sets, a program without them can't be drawn with
+const LIGHT_UNIFORMS: [&str; 5] = [
+    "light_count",
+    "light_position",
+    "light_direction",
+    "light_color",
+    "light_params",
+];
 
 #[derive(Debug)]
 pub struct Window {
@@ -21,13 +46,38 @@ pub struct Window {
     window: PWindow,
     events: Arc<GlfwReceiver<(f64, WindowEvent)>>,
     shader: ShaderProgram,
+    // Backs the `Camera` block, see `CameraBlock`
+    camera_ubo: Buffer,
     pub object_manager: ObjectManager,
+    // GPU copies of `object_manager`'s meshes, indexed by object type id
+    object_info: Vec<ObjectInformation>,
+    // World and material versions of the scene the instance buffers were last brought up to
+    // date with, `None` before the first frame
+    drawn: Option<(u64, u64)>,
 }
 
 #[derive(Debug)]
 pub(crate) struct ObjectInformation {
     vao: VertexArray,
+    // Per instance attributes of the objects in `handles`
     inst_vbo: Buffer,
+    // Objects of this type in the order they are in the buffer
+    handles: Vec<ObjectHandle>,
+    // Instances the buffer has room for
+    capacity: usize,
+}
+
+// Per instance inputs of vertex.glsl, from location 4 on
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct InstanceData {
+    // Object to world, including the placement of every parent
+    model: [[f32; 4]; 4],
+    // Inverse transpose of the model matrix, normals stay perpendicular under non-uniform scale
+    normal: [[f32; 3]; 3],
+    // Material parameters, see `Material::preview_params`
+    albedo_metallic: [f32; 4],
+    emission_roughness: [f32; 4],
 }
 
 pub enum ShaderType {
@@ -36,9 +86,46 @@ pub enum ShaderType {
     Compute = COMPUTE_SHADER as isize,
 }
 
+// The `Camera` uniform block of the shaders, in std140 layout
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraBlock {
+    view: [[f32; 4]; 4],
+    projection: [[f32; 4]; 4],
+    // w is padding
+    position: [f32; 4],
+}
+
 struct Shader(pub GLuint);
+
+// Linked program with the locations of its active uniforms and the indices of its uniform
+// blocks, looked up once after linking
+#[derive(Debug)]
+struct ShaderProgram {
+    id: GLuint,
+    uniforms: HashMap<String, GLint>,
+    blocks: HashMap<String, GLuint>,
+    // Set by `watch`, the program is rebuilt from these files when they change
+    files: Option<ShaderFiles>,
+}
+
 #[derive(Debug)]
-struct ShaderProgram(pub GLuint);
+struct ShaderFiles {
+    vert: PathBuf,
+    frag: PathBuf,
+    loaded: Loaded,
+}
+
+// What the last reload saw of the watched files
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Loaded {
+    // Nothing was tried yet, the next check reads the files whatever their state
+    Never,
+    // Latest modification time of the two
+    At(SystemTime),
+    // Either file couldn't be read. Reported once, until the files are back
+    Missing,
+}
 
 impl Window {
     pub fn new(opts: WindowOptions, glfw: &mut Glfw) -> Window {
@@ -54,11 +141,23 @@ impl Window {
         let win = Arc::new(Mutex::new(&mut window));
         gl::load_with(|s| win.lock().unwrap().get_proc_address(s));
 
-        let shader = ShaderProgram::from_vert_frag(
+        let mut shader = ShaderProgram::from_vert_frag(
             include_str!("vertex.glsl"),
             include_str!("fragment.glsl"),
         )
         .unwrap();
+        prepare_shader(&shader).unwrap();
+
+        // The built in shaders stay in use until the files have been read on the first frame
+        if let Some(dir) = &opts.shader_dir {
+            if let Err(e) = shader.watch(dir.join("vertex.glsl"), dir.join("fragment.glsl")) {
+                eprintln!("Watching shaders that can't be read: {}", e);
+            }
+        }
+
+        let camera_ubo = Buffer::new(BufferType::Uniform).expect("Unable to create camera buffer");
+        camera_ubo.bind();
+        camera_ubo.buffer_data(bytemuck::bytes_of(&CameraBlock::zeroed()), DYNAMIC_DRAW);
 
         unsafe { gl::Enable(gl::DEPTH_TEST) };
         glfw.set_swap_interval(glfw::SwapInterval::None);
@@ -68,7 +167,10 @@ impl Window {
             window,
             events: Arc::new(events),
             shader,
+            camera_ubo,
             object_manager: ObjectManager::new(),
+            object_info: Vec::new(),
+            drawn: None,
         }
     }
 }
@@ -87,7 +189,8 @@ impl Window {
 
             match event {
                 WindowEvent::Close => self.window.set_should_close(true),
-                WindowEvent::Size(w, h) => unsafe {
+                // In pixels, which differ from the window size on high DPI displays
+                WindowEvent::FramebufferSize(w, h) => unsafe {
                     gl::Viewport(0, 0, w, h);
 
                     if scene.on_event.is_none() {
@@ -133,9 +236,24 @@ impl Window {
         let scene = &mut scenes[self.opts.scene];
 
         self.window.make_current();
+        self.upload_objects();
+
+        match self.shader.reload_if_changed(prepare_shader) {
+            Ok(true) => println!("Reloaded shaders from {}", self.shader_dir()),
+            Ok(false) => {}
+            Err(e) => eprintln!("Keeping the previous shaders: {}", e),
+        }
         self.shader.use_program();
 
+        // Follows the framebuffer so resizing the window doesn't stretch the image
+        let (width, height) = self.window.get_framebuffer_size();
+        if width > 0 && height > 0 {
+            scene.camera.set_aspect(width as f32 / height as f32);
+        }
+
         self.send_camera_info(&scene.camera);
+        self.send_lights(scene)
+            .unwrap_or_else(|e| panic!("Unable to send lights: {}", e));
 
         if scene.clear_color_dirty {
             let c = scene.clear_color;
@@ -151,33 +269,22 @@ impl Window {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
-        for obj in &scene.objects {
-            let ty = self.object_manager.from_id(obj.object_type);
-            ty.info.vao.bind();
-
-            //let mut position_mat = Mat4::identity();
-            //position_mat = glm::translate(&position_mat, &obj.position);
-
-            //let rotation_mat = obj.orientation.as_matrix();
-
-            //let mut scale_mat = Mat4::identity();
-            //scale_mat = glm::scale(&scale_mat, &obj.scale);
-
-            //self.send_matrix("obj_mat", &(position_mat * rotation_mat * scale_mat));
-
-            self.send_vec3("obj_position", &obj.position);
-            self.send_vec3("obj_scale", &obj.scale);
-            self.send_matrix("obj_rotation", &obj.orientation.as_matrix());
+        // One draw per object type with every object of that type as an instance
+        self.upload_instances(scene);
+        for (id, info) in self.object_info.iter().enumerate() {
+            if info.handles.is_empty() {
+                continue;
+            }
 
-            self.send_vec4("color", &obj.color);
+            info.vao.bind();
 
             unsafe {
-                gl::DrawElements(
+                gl::DrawElementsInstanced(
                     TRIANGLES,
-                    ty.tris.len() as i32 * 3,
+                    self.object_manager.from_id(id).tris.len() as i32 * 3,
                     UNSIGNED_INT,
-                    //0 as *const _,
                     std::ptr::null(),
+                    info.handles.len() as i32,
                 )
             };
         }
@@ -185,106 +292,286 @@ impl Window {
         self.window.swap_buffers();
     }
 
-    fn send_camera_info(&self, camera: &Camera) {
-        self.send_matrix("cam_view", &camera.view);
-        self.send_matrix("cam_projection", &camera.projection);
-        self.send_matrix("cam_orientation", &camera.orientation.as_matrix())
+    fn shader_dir(&self) -> String {
+        self.opts
+            .shader_dir
+            .as_ref()
+            .map_or_else(String::new, |dir| dir.display().to_string())
     }
 
-    fn uniform_location(&self, name: &str) -> i32 {
-        let cname = CString::new(name).unwrap();
-        unsafe { gl::GetUniformLocation(self.shader.0, cname.as_ptr() as *const i8) }
+    // Uploads any object types registered since the last frame. Must be called with this
+    // window's context current
+    fn upload_objects(&mut self) {
+        for id in self.object_info.len()..self.object_manager.len() {
+            let ty = self.object_manager.from_id(id);
+            let info = ObjectInformation::new(ty)
+                .unwrap_or_else(|| panic!("Unable to upload object '{}'", ty.name));
+
+            self.object_info.push(info);
+        }
     }
 
-    fn send_matrix(&self, name: &str, mat: &Mat4) {
-        unsafe {
-            gl::UniformMatrix4fv(
-                self.uniform_location(name),
-                1,
-                FALSE,
-                mat.as_slice().as_ptr(),
-            )
+    // Sends the instances of objects that moved or changed since the last frame. Nothing is done
+    // while the scene stays the same
+    fn upload_instances(&mut self, scene: &Scene) {
+        let world = scene.world();
+        let versions = (world.version(), scene.material_version());
+        if self.drawn == Some(versions) {
+            return;
         }
+
+        // Live objects of each type in slot order
+        let mut by_type = vec![Vec::new(); self.object_info.len()];
+        for (handle, object) in scene.iter() {
+            by_type[object.object_type].push(handle);
+        }
+
+        let instance = |handle: &ObjectHandle| {
+            let object = scene.get(*handle).unwrap();
+            let material = scene.resolve_material(object.material);
+            InstanceData::new(&world.matrices()[handle.index as usize], material)
+        };
+
+        for (info, handles) in self.object_info.iter_mut().zip(by_type) {
+            // Everything is sent again when objects were added, removed or changed type, or when
+            // a material changed
+            let same_objects = handles == info.handles;
+            let since = self
+                .drawn
+                .filter(|(_, materials)| same_objects && *materials == versions.1)
+                .map(|(version, _)| version);
+            info.upload_instances(handles, since, &world, instance);
+        }
+
+        self.drawn = Some(versions);
+    }
+
+    fn send_camera_info(&self, camera: &Camera) {
+        let block = CameraBlock {
+            view: camera.view().into(),
+            projection: camera.projection_matrix().into(),
+            position: camera.position.push(0.).into(),
+        };
+
+        self.camera_ubo.bind();
+        self.camera_ubo
+            .buffer_sub_data(0, bytemuck::bytes_of(&block));
+        self.camera_ubo.bind_base(CAMERA_BINDING);
+    }
+
+    // Packs the scene's lights into the uniform arrays declared in fragment.glsl
+    fn send_lights(&self, scene: &Scene) -> Result<(), String> {
+        let mut position = [Vec4::zeros(); MAX_LIGHTS];
+        let mut direction = [Vec4::zeros(); MAX_LIGHTS];
+        let mut color = [Vec4::zeros(); MAX_LIGHTS];
+        let mut params = [Vec4::zeros(); MAX_LIGHTS];
+
+        let lights = scene.lights().map(|(_, light)| light).take(MAX_LIGHTS);
+        let mut count = 0;
+
+        for (i, light) in lights.enumerate() {
+            count += 1;
+
+            // w holds the light type, see the defines in fragment.glsl
+            (position[i], direction[i], color[i], params[i]) = match *light {
+                Light::Point {
+                    position,
+                    color,
+                    intensity,
+                    ..
+                } => (
+                    position.push(0.),
+                    Vec4::zeros(),
+                    (color * intensity).push(0.),
+                    Vec4::zeros(),
+                ),
+                Light::Spot {
+                    position,
+                    direction,
+                    color,
+                    intensity,
+                    inner_angle,
+                    outer_angle,
+                    ..
+                } => (
+                    position.push(1.),
+                    direction.normalize().push(0.),
+                    (color * intensity).push(0.),
+                    Vec4::new(inner_angle.cos(), outer_angle.cos(), 0., 0.),
+                ),
+                Light::Directional {
+                    direction,
+                    color,
+                    intensity,
+                } => (
+                    Vec4::new(0., 0., 0., 2.),
+                    direction.normalize().push(0.),
+                    (color * intensity).push(0.),
+                    Vec4::zeros(),
+                ),
+                // Drawn as a point light at the center that only shines out of its front
+                Light::Area {
+                    position,
+                    u,
+                    v,
+                    color,
+                    intensity,
+                } => (
+                    (position + (u + v) / 2.).push(3.),
+                    u.cross(&v).normalize().push(0.),
+                    (color * intensity).push(0.),
+                    Vec4::new(0., 0., u.cross(&v).norm(), 0.),
+                ),
+            };
+        }
+
+        self.send_int("light_count", count)?;
+        self.send_vec4_array("light_position", &position)?;
+        self.send_vec4_array("light_direction", &direction)?;
+        self.send_vec4_array("light_color", &color)?;
+        self.send_vec4_array("light_params", &params)
     }
 
-    fn send_vec4(&self, name: &str, vec: &Vec4) {
-        unsafe { gl::Uniform4fv(self.uniform_location(name), 1, vec.as_ptr()) }
+    // The `send_` functions fail for names that aren't active uniforms of the shader, which
+    // includes uniforms the compiler removed because nothing reads them
+    fn send_int(&self, name: &str, value: i32) -> Result<(), String> {
+        let location = self.shader.uniform(name)?;
+        unsafe { gl::Uniform1i(location, value) };
+        Ok(())
     }
 
-    fn send_vec3(&self, name: &str, vec: &Vec3) {
-        unsafe { gl::Uniform3fv(self.uniform_location(name), 1, vec.as_ptr()) }
+    fn send_vec4_array(&self, name: &str, values: &[Vec4]) -> Result<(), String> {
+        let location = self.shader.uniform(name)?;
+        unsafe { gl::Uniform4fv(location, values.len() as i32, values.as_ptr().cast()) };
+        Ok(())
     }
 }
 
 impl ObjectInformation {
-    pub fn new(obj: (&[Vertex], &[TriangleIndecies])) -> Option<Self> {
+    pub fn new(ty: &ObjectType) -> Option<Self> {
         let vao = VertexArray::new()?;
         vao.bind();
 
+        // One buffer per attribute, locations match the inputs of vertex.glsl
+        Self::upload_attribute(0, 3, bytemuck::cast_slice(&ty.verts))?;
+        Self::upload_attribute(1, 3, bytemuck::cast_slice(&ty.normals))?;
+        Self::upload_attribute(2, 2, bytemuck::cast_slice(&ty.uvs))?;
+        Self::upload_attribute(3, 3, bytemuck::cast_slice(&ty.colors))?;
+
+        let ebo = Buffer::new(BufferType::ElementArray)?;
+        ebo.bind();
+        ebo.buffer_data(bytemuck::cast_slice(&ty.tris), STATIC_DRAW);
+
+        let inst_vbo = Buffer::new(BufferType::Array)?;
+        inst_vbo.bind();
+
+        // Locations and sizes of the fields of `InstanceData`, matrices take one location per
+        // column
+        let attributes = [
+            (4, 4),
+            (5, 4),
+            (6, 4),
+            (7, 4),
+            (8, 3),
+            (9, 3),
+            (10, 3),
+            (11, 4),
+            (12, 4),
+        ];
+        let stride = size_of::<InstanceData>() as i32;
+        let mut offset = 0;
+
+        for (location, components) in attributes {
+            unsafe {
+                gl::VertexAttribPointer(
+                    location,
+                    components,
+                    FLOAT,
+                    FALSE,
+                    stride,
+                    offset as *const _,
+                );
+                gl::EnableVertexAttribArray(location);
+                gl::VertexAttribDivisor(location, 1);
+            }
+            offset += components as usize * size_of::<f32>();
+        }
+
+        Some(Self {
+            vao,
+            inst_vbo,
+            handles: Vec::new(),
+            capacity: 0,
+        })
+    }
+
+    // Brings the instance buffer up to date with `handles`. With the world version the buffer
+    // was last sent at, only objects that changed since are sent, one call per run of them
+    fn upload_instances(
+        &mut self,
+        handles: Vec<ObjectHandle>,
+        since: Option<u64>,
+        world: &WorldTransforms,
+        instance: impl Fn(&ObjectHandle) -> InstanceData,
+    ) {
+        self.inst_vbo.bind();
+        let instances = |range: Range<usize>| -> Vec<InstanceData> {
+            handles[range].iter().map(&instance).collect()
+        };
+
+        let Some(version) = since else {
+            let data = instances(0..handles.len());
+            if data.len() > self.capacity {
+                self.inst_vbo
+                    .buffer_data(bytemuck::cast_slice(&data), DYNAMIC_DRAW);
+                self.capacity = data.len();
+            } else if !data.is_empty() {
+                self.inst_vbo
+                    .buffer_sub_data(0, bytemuck::cast_slice(&data));
+            }
+
+            self.handles = handles;
+            return;
+        };
+
+        let changed = |i: usize| world.changed_since(handles[i].index as usize, version);
+        let mut start = 0;
+        while start < handles.len() {
+            if !changed(start) {
+                start += 1;
+                continue;
+            }
+
+            let end = (start..handles.len())
+                .find(|i| !changed(*i))
+                .unwrap_or(handles.len());
+            self.inst_vbo.buffer_sub_data(
+                start * size_of::<InstanceData>(),
+                bytemuck::cast_slice(&instances(start..end)),
+            );
+            start = end;
+        }
+    }
+
+    // Tightly packed float attribute, the vertex array must be bound
+    fn upload_attribute(location: GLuint, components: i32, data: &[u8]) -> Option<()> {
         let vbo = Buffer::new(BufferType::Array)?;
         vbo.bind();
-        vbo.buffer_data(bytemuck::cast_slice(obj.0), STATIC_DRAW);
+        vbo.buffer_data(data, STATIC_DRAW);
 
         unsafe {
             gl::VertexAttribPointer(
-                0,
-                3,
+                location,
+                components,
                 FLOAT,
                 FALSE,
-                size_of::<Vertex>().try_into().unwrap(),
+                components * size_of::<f32>() as i32,
                 std::ptr::null(),
-                //0 as *const _,
             );
-            gl::EnableVertexAttribArray(0);
+            gl::EnableVertexAttribArray(location);
         }
 
-        let ebo = Buffer::new(BufferType::ElementArray)?;
-        ebo.bind();
-        ebo.buffer_data(bytemuck::cast_slice(obj.1), STATIC_DRAW);
-
-        let inst_vbo = Buffer::new(BufferType::Array)?;
-        inst_vbo.bind();
-
-        unsafe {
-            //gl::VertexAttribPointer(
-            //    1,
-            //    4,
-            //    FLOAT,
-            //    FALSE,
-            //    4 * size_of::<Vec4>().try_into().unwrap(),
-            //    0 as *const _,
-            //);
-            //gl::EnableVertexAttribArray(1);
-            //gl::VertexAttribPointer(
-            //    2,
-            //    4,
-            //    FLOAT,
-            //    FALSE,
-            //    4 * size_of::<Vec4>().try_into().unwrap(),
-            //    size_of::<Vec4>() as *const _,
-            //);
-            //gl::EnableVertexAttribArray(2);
-            //gl::VertexAttribPointer(
-            //    3,
-            //    4,
-            //    FLOAT,
-            //    FALSE,
-            //    4 * size_of::<Vec4>().try_into().unwrap(),
-            //    (2 * size_of::<Vec4>()) as *const _,
-            //);
-            //gl::EnableVertexAttribArray(3);
-            //gl::VertexAttribPointer(
-            //    4,
-            //    4,
-            //    FLOAT,
-            //    FALSE,
-            //    4 * size_of::<Vec4>().try_into().unwrap(),
-            //    (3 * size_of::<Vec4>()) as *const _,
-            //);
-            //gl::EnableVertexAttribArray(4);
-        }
-
-        Some(Self { vao, inst_vbo })
+        Some(())
     }
 }
 
@@ -309,6 +596,54 @@ impl Window {
         self.window.set_cursor_mode(mode)
     }
 
+    pub fn get_size(&self) -> (i32, i32) {
+        self.window.get_size()
+    }
+
+    // World space ray under the cursor for `WindowEvent::CursorPos` events, `None` for any other
+    // event. Pass the result to `Scene::raycast` to find what was clicked
+    pub fn cursor_ray(&self, camera: &Camera, event: &WindowEvent) -> Option<Ray> {
+        let WindowEvent::CursorPos(x, y) = event else {
+            return None;
+        };
+
+        let (width, height) = self.window.get_size();
+        Some(camera.screen_ray(*x as f32, *y as f32, width as f32, height as f32))
+    }
+
+    // Reads back the last rendered frame. Values are returned as stored in the framebuffer, so
+    // they are already display encoded
+    pub fn capture(&mut self) -> Film {
+        self.window.make_current();
+
+        let (width, height) = self.window.get_framebuffer_size();
+        let (width, height) = (width.max(0) as usize, height.max(0) as usize);
+        let mut data = vec![0_f32; width * height * 3];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadBuffer(gl::FRONT);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGB,
+                FLOAT,
+                data.as_mut_ptr().cast(),
+            );
+        }
+
+        // OpenGL rows start at the bottom of the image
+        let pixels = data
+            .chunks_exact(width.max(1) * 3)
+            .rev()
+            .flat_map(|row| row.chunks_exact(3).map(|p| Vec3::new(p[0], p[1], p[2])))
+            .collect();
+
+        Film::from_pixels(width, height, pixels).expect("Framebuffer readback size mismatch")
+    }
+
     pub fn destroy(self) {}
 }
 
@@ -396,21 +731,26 @@ impl ShaderProgram {
             return None;
         }
 
-        Some(Self(prog))
+        Some(Self {
+            id: prog,
+            uniforms: HashMap::new(),
+            blocks: HashMap::new(),
+            files: None,
+        })
     }
 
     pub fn attach_shader(&self, shader: &Shader) {
-        unsafe { gl::AttachShader(self.0, shader.0) };
+        unsafe { gl::AttachShader(self.id, shader.0) };
     }
 
     pub fn link_program(&self) {
-        unsafe { gl::LinkProgram(self.0) };
+        unsafe { gl::LinkProgram(self.id) };
     }
 
     pub fn link_success(&self) -> bool {
         let mut success = 0;
 
-        unsafe { gl::GetProgramiv(self.0, LINK_STATUS, &mut success) };
+        unsafe { gl::GetProgramiv(self.id, LINK_STATUS, &mut success) };
 
         success == i32::from(TRUE)
     }
@@ -418,14 +758,14 @@ impl ShaderProgram {
     pub fn info_log(&self) -> String {
         let mut needed_len = 0;
 
-        unsafe { gl::GetProgramiv(self.0, INFO_LOG_LENGTH, &mut needed_len) };
+        unsafe { gl::GetProgramiv(self.id, INFO_LOG_LENGTH, &mut needed_len) };
 
         let mut v: Vec<u8> = Vec::with_capacity(needed_len.try_into().unwrap());
         let mut len_written = 0_i32;
 
         unsafe {
             gl::GetProgramInfoLog(
-                self.0,
+                self.id,
                 v.capacity().try_into().unwrap(),
                 &mut len_written,
                 v.as_mut_ptr().cast(),
@@ -438,15 +778,135 @@ impl ShaderProgram {
     }
 
     pub fn use_program(&self) {
-        unsafe { gl::UseProgram(self.0) };
+        unsafe { gl::UseProgram(self.id) };
     }
 
     pub fn delete(self) {
-        unsafe { gl::DeleteProgram(self.0) };
+        unsafe { gl::DeleteProgram(self.id) };
+    }
+
+    // Rebuild the program from these files whenever either changes, see `reload_if_changed`. Fails
+    // if either can't be read right now, they are watched anyway
+    pub fn watch(&mut self, vert: PathBuf, frag: PathBuf) -> Result<(), String> {
+        let files = ShaderFiles {
+            vert,
+            frag,
+            loaded: Loaded::Never,
+        };
+        let readable = files.modified().map(|_| ());
+
+        self.files = Some(files);
+        readable
+    }
+
+    // Builds the program again if a watched file changed since the last call, and runs `prepare`
+    // on the new program. If reading, compiling, linking or `prepare` fails the error is returned
+    // and the current program is kept. A failed change isn't retried until the files change again,
+    // files that can't be read are reported when they go missing
+    pub fn reload_if_changed(
+        &mut self,
+        prepare: impl Fn(&ShaderProgram) -> Result<(), String>,
+    ) -> Result<bool, String> {
+        let Some(files) = &mut self.files else {
+            return Ok(false);
+        };
+
+        let modified = files.modified();
+        let unchanged = match (files.loaded, &modified) {
+            (Loaded::At(last), Ok(modified)) => last == *modified,
+            (Loaded::Missing, Err(_)) => true,
+            _ => false,
+        };
+        if unchanged {
+            return Ok(false);
+        }
+
+        files.loaded = match &modified {
+            Ok(modified) => Loaded::At(*modified),
+            Err(_) => Loaded::Missing,
+        };
+        modified?;
+
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        let program = Self::from_vert_frag(&read(&files.vert)?, &read(&files.frag)?)?;
+
+        if let Err(e) = prepare(&program) {
+            program.delete();
+            return Err(e);
+        }
+
+        let old = std::mem::replace(&mut self.id, program.id);
+        self.uniforms = program.uniforms;
+        self.blocks = program.blocks;
+        unsafe { gl::DeleteProgram(old) };
+
+        Ok(true)
+    }
+
+    // Location of an active uniform. Arrays are found by their name without an index
+    pub fn uniform(&self, name: &str) -> Result<GLint, String> {
+        self.uniforms
+            .get(name)
+            .copied()
+            .ok_or_else(|| format!("No active uniform '{}'", name))
+    }
+
+    // Attaches a uniform block to a uniform buffer binding point
+    pub fn bind_block(&self, name: &str, binding: GLuint) -> Result<(), String> {
+        let index = self
+            .blocks
+            .get(name)
+            .ok_or_else(|| format!("No active uniform block '{}'", name))?;
+
+        unsafe { gl::UniformBlockBinding(self.id, *index, binding) };
+        Ok(())
+    }
+
+    // Fills in `uniforms` and `blocks`, the program must be linked
+    fn reflect(&mut self) {
+        let count = self.parameter(ACTIVE_UNIFORMS);
+        let max_len = self.parameter(ACTIVE_UNIFORM_MAX_LENGTH);
+
+        for i in 0..count as GLuint {
+            let name = read_name(max_len, |len, written, name| unsafe {
+                let (mut size, mut ty) = (0, 0);
+                gl::GetActiveUniform(self.id, i, len, written, &mut size, &mut ty, name);
+            });
+
+            let cname = CString::new(name.as_str()).unwrap();
+            let location = unsafe { gl::GetUniformLocation(self.id, cname.as_ptr()) };
+
+            // Members of uniform blocks have no location, they're set through the block's buffer
+            if location < 0 {
+                continue;
+            }
+
+            // Arrays are reported as their first element
+            let name = name.strip_suffix("[0]").unwrap_or(&name).to_string();
+            self.uniforms.insert(name, location);
+        }
+
+        let count = self.parameter(ACTIVE_UNIFORM_BLOCKS);
+        let max_len = self.parameter(ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH);
+
+        for i in 0..count as GLuint {
+            let name = read_name(max_len, |len, written, name| unsafe {
+                gl::GetActiveUniformBlockName(self.id, i, len, written, name);
+            });
+            self.blocks.insert(name, i);
+        }
+    }
+
+    fn parameter(&self, name: GLenum) -> GLint {
+        let mut value = 0;
+        unsafe { gl::GetProgramiv(self.id, name, &mut value) };
+        value
     }
 
     pub fn from_vert_frag(vert: &str, frag: &str) -> Result<Self, String> {
-        let p = Self::new().ok_or_else(|| "Couldn't allocate a program".to_string())?;
+        let mut p = Self::new().ok_or_else(|| "Couldn't allocate a program".to_string())?;
         let v = Shader::from_source(ShaderType::Vert, vert)
             .map_err(|e| format!("Vertex Compile Error: {}", e))?;
         let f = Shader::from_source(ShaderType::Frag, frag)
@@ -459,6 +919,7 @@ impl ShaderProgram {
         f.delete();
 
         if p.link_success() {
+            p.reflect();
             return Ok(p);
         }
 
@@ -467,3 +928,54 @@ impl ShaderProgram {
         Err(out)
     }
 }
+
+impl ShaderFiles {
+    // Later modification time of the two, fails with the path of a file that can't be read
+    fn modified(&self) -> Result<SystemTime, String> {
+        let modified = |path: &Path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        Ok(modified(&self.vert)?.max(modified(&self.frag)?))
+    }
+}
+
+// Sets up a freshly built program for drawing. Fails if the program lacks anything the window
+// sends every frame, e.g. because a reloaded shader stopped using it
+fn prepare_shader(program: &ShaderProgram) -> Result<(), String> {
+    program.bind_block("Camera", CAMERA_BINDING)?;
+
+    for name in LIGHT_UNIFORMS {
+        program.uniform(name)?;
+    }
+
+    Ok(())
+}
+
+// Reads a name of at most `max_len` bytes including the terminator through `get`, which is passed
+// the buffer size, where to write the length and the buffer
+fn read_name(max_len: GLint, get: impl FnOnce(GLint, &mut GLint, *mut GLchar)) -> String {
+    let mut name = vec![0_u8; max_len.max(1) as usize];
+    let mut written = 0;
+
+    get(name.len() as GLint, &mut written, name.as_mut_ptr().cast());
+    name.truncate(written.max(0) as usize);
+
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+impl InstanceData {
+    fn new(model: &Mat4, material: &Material) -> Self {
+        let linear: Mat3 = model.fixed_view::<3, 3>(0, 0).into();
+        let normal = linear.try_inverse().unwrap_or(linear).transpose();
+        let (metallic, roughness) = material.preview_params();
+
+        Self {
+            model: (*model).into(),
+            normal: normal.into(),
+            albedo_metallic: material.base_color().push(metallic).into(),
+            emission_roughness: material.emission().push(roughness).into(),
+        }
+    }
+}
@@ -1,9 +1,94 @@
 #version 460 core
 
+#define MAX_LIGHTS 16
+
+#define LIGHT_POINT 0
+#define LIGHT_SPOT 1
+#define LIGHT_DIRECTIONAL 2
+#define LIGHT_AREA 3
+
+const float PI = 3.14159265;
+
+in vec3 world_pos;
+in vec3 world_normal;
+in vec2 tex_coord;
+in vec3 vertex_color;
+// The object's material, the same for every vertex of an instance
+flat in vec3 albedo;
+flat in vec3 emission;
+flat in float metallic;
+flat in float roughness;
+
 out vec4 final_color;
 
-uniform vec4 color;
+// Shared by every program, see `CameraBlock`
+layout (std140) uniform Camera {
+  mat4 cam_view;
+  mat4 cam_projection;
+  vec4 cam_position;
+};
+
+uniform int light_count;
+// xyz position, w type
+uniform vec4 light_position[MAX_LIGHTS];
+// xyz direction the light travels (spot, directional) or faces (area)
+uniform vec4 light_direction[MAX_LIGHTS];
+// rgb color * intensity
+uniform vec4 light_color[MAX_LIGHTS];
+// spot: cos(inner), cos(outer). area: z = area
+uniform vec4 light_params[MAX_LIGHTS];
 
 void main() {
-  final_color = color;
+  vec3 base_color = albedo * vertex_color;
+
+  // Scenes without lights keep the flat preview
+  if (light_count == 0) {
+    final_color = vec4(base_color + emission, 1.0);
+    return;
+  }
+
+  // Meshes without vertex normals are flat shaded with the face normal
+  vec3 n = dot(world_normal, world_normal) > 1e-8
+    ? normalize(world_normal)
+    : normalize(cross(dFdx(world_pos), dFdy(world_pos)));
+  vec3 v = normalize(cam_position.xyz - world_pos);
+  if (dot(n, v) < 0.0) {
+    n = -n;
+  }
+
+  float shininess = 2.0 / max(pow(roughness, 4.0), 1e-4) - 2.0;
+  vec3 diffuse_color = base_color * (1.0 - metallic);
+  vec3 specular_color = mix(vec3(0.04), base_color, metallic);
+
+  vec3 color = emission + diffuse_color * 0.03;
+
+  for (int i = 0; i < light_count; i++) {
+    int type = int(light_position[i].w);
+    vec3 l;
+    vec3 radiance = light_color[i].rgb;
+
+    if (type == LIGHT_DIRECTIONAL) {
+      l = -light_direction[i].xyz;
+    } else {
+      vec3 offset = light_position[i].xyz - world_pos;
+      float dist2 = max(dot(offset, offset), 1e-8);
+      l = offset * inversesqrt(dist2);
+      radiance /= dist2;
+
+      if (type == LIGHT_SPOT) {
+        float cos_angle = dot(-l, light_direction[i].xyz);
+        radiance *= smoothstep(light_params[i].y, light_params[i].x, cos_angle);
+      } else if (type == LIGHT_AREA) {
+        radiance *= max(dot(-l, light_direction[i].xyz), 0.0) * light_params[i].z;
+      }
+    }
+
+    float n_dot_l = max(dot(n, l), 0.0);
+    vec3 h = normalize(l + v);
+    float spec = (shininess + 8.0) / (8.0 * PI) * pow(max(dot(n, h), 0.0), shininess);
+
+    color += radiance * n_dot_l * (diffuse_color / PI + specular_color * spec);
+  }
+
+  final_color = vec4(color, 1.0);
 }
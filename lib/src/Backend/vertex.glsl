@@ -1,43 +1,43 @@
 #version 460 core
 
 layout (location = 0) in vec3 pos;
-
-uniform mat4 cam_view;
-uniform mat4 cam_projection;
-uniform mat4 cam_orientation;
-
-uniform vec3 obj_position;
-uniform vec3 obj_scale;
-uniform mat4 obj_rotation;
-
-// uniform mat4 obj_mat;
+layout (location = 1) in vec3 normal;
+layout (location = 2) in vec2 uv;
+layout (location = 3) in vec3 color;
+
+// Per instance, see `InstanceData`. Object to world, including the placement of every parent
+layout (location = 4) in mat4 obj_mat;
+layout (location = 8) in mat3 obj_normal_mat;
+// Material parameters packed in pairs
+layout (location = 11) in vec4 albedo_metallic;
+layout (location = 12) in vec4 emission_roughness;
+
+out vec3 world_pos;
+out vec3 world_normal;
+out vec2 tex_coord;
+out vec3 vertex_color;
+flat out vec3 albedo;
+flat out vec3 emission;
+flat out float metallic;
+flat out float roughness;
+
+// Shared by every program, see `CameraBlock`
+layout (std140) uniform Camera {
+  mat4 cam_view;
+  mat4 cam_projection;
+  vec4 cam_position;
+};
 
 void main() {
-  mat4 oscale = mat4(
-    obj_scale.x, 0, 0, 0,
-    0, obj_scale.y, 0, 0,
-    0, 0, obj_scale.y, 0,
-    0, 0, 0, 1
-  );
-
-  mat4 opos = mat4(
-    1, 0, 0, 0,
-    0, 1, 0, 0,
-    0, 0, 1, 0,
-    obj_position.x, obj_position.y, obj_position.z, 1
-  ); 
-
-  // 1 2 3 4    1 5 9 4
-  // 5 6 7 8    2 6 1 5
-  // 9 1 2 3    3 7 2 6
-  // 4 5 6 7    4 8 3 7
-
-  mat4 obj_mat = opos * obj_rotation * oscale;
-
-  // vec4 obj_pos = vec4(obj_position, 1.0) * obj_rotation * vec4(obj_scale, 1.0) * vec4(pos, 1.0);
-  // vec4 obj_pos = vec4(pos, 1.0);
-  // vec4 obj_pos = obj_mat * vec4(pos, 1.0);
-  // gl_Position = cam_projection * cam_orientation * cam_view * obj_pos;
-  gl_Position = cam_projection * cam_orientation * cam_view * obj_mat * vec4(pos, 1.0);
-  // gl_Position = cam_projection * cam_view * obj_mat * vec4(pos, 1.0);
+  vec4 world = obj_mat * vec4(pos, 1.0);
+  world_pos = world.xyz;
+  // Zero normals stay zero so the mesh is flat shaded
+  world_normal = obj_normal_mat * normal;
+  tex_coord = uv;
+  vertex_color = color;
+  albedo = albedo_metallic.rgb;
+  metallic = albedo_metallic.a;
+  emission = emission_roughness.rgb;
+  roughness = emission_roughness.a;
+  gl_Position = cam_projection * cam_view * world;
 }
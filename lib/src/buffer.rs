@@ -1,13 +1,14 @@
 use gl::{
     types::{GLenum, GLuint},
-    BindBuffer, BindVertexArray, BufferData, GenBuffers, GenVertexArrays, ARRAY_BUFFER,
-    ELEMENT_ARRAY_BUFFER,
+    BindBuffer, BindBufferBase, BindVertexArray, BufferData, BufferSubData, GenBuffers,
+    GenVertexArrays, ARRAY_BUFFER, ELEMENT_ARRAY_BUFFER, UNIFORM_BUFFER,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BufferType {
     Array = ARRAY_BUFFER as isize,
     ElementArray = ELEMENT_ARRAY_BUFFER as isize,
+    Uniform = UNIFORM_BUFFER as isize,
 }
 
 #[derive(Debug)]
@@ -58,6 +59,12 @@ impl Buffer {
         unsafe { BindBuffer(self.ty as GLenum, self.bo) }
     }
 
+    // Attaches the buffer to an indexed binding point, for uniform buffers the one shader blocks
+    // are bound to
+    pub fn bind_base(&self, index: GLuint) {
+        unsafe { BindBufferBase(self.ty as GLenum, index, self.bo) }
+    }
+
     pub fn clear_binding(ty: BufferType) {
         unsafe { BindBuffer(ty as GLenum, 0) }
     }
@@ -72,4 +79,16 @@ impl Buffer {
             )
         }
     }
+
+    // Overwrites part of the buffer, which must already be large enough
+    pub fn buffer_sub_data(&self, offset: usize, data: &[u8]) {
+        unsafe {
+            BufferSubData(
+                self.ty as GLenum,
+                offset.try_into().unwrap(),
+                data.len().try_into().unwrap(),
+                data.as_ptr().cast(),
+            )
+        }
+    }
 }
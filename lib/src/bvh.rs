@@ -0,0 +1,501 @@
+#[cfg(test)]
+mod tests;
+
+use std::time::{Duration, Instant};
+
+use glm::{vec4, Mat4, Vec3};
+
+use crate::Ray;
+
+// Number of buckets the centroid range is split into when searching for the best SAH split
+const BINS: usize = 16;
+// Relative cost of visiting a node versus intersecting a primitive
+const TRAVERSAL_COST: f32 = 1.;
+const INTERSECTION_COST: f32 = 1.;
+// Leaves may be larger than this if splitting doesn't help, but only when the primitives can't
+// be separated at all
+const MAX_LEAF_SIZE: usize = 8;
+// Traversal keeps its stack in a fixed size array, deeper subtrees end in one larger leaf
+const MAX_DEPTH: usize = 64;
+// A refitted tree whose SAH cost has grown past this factor of the freshly built cost is rebuilt
+const REBUILD_THRESHOLD: f32 = 2.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub depth: usize,
+    // Surface area heuristic cost of the whole tree, lower is better
+    pub sah_cost: f32,
+    // Time of the last full build, refits don't change it
+    pub build_time: Duration,
+}
+
+// Nodes still to visit. Holds at most one node per level of the tree
+struct Stack {
+    nodes: [u32; MAX_DEPTH],
+    len: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    // Index of the first primitive in `Bvh::indices` for leaves, of the left child otherwise. The
+    // right child always directly follows the left one
+    first: u32,
+    // Zero for interior nodes
+    count: u32,
+}
+
+// Bounding volume hierarchy over anything that can be bounded by an `Aabb`. Used both for the
+// triangles of a mesh and for the objects placed in a scene
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<u32>,
+    stats: BvhStats,
+    // SAH cost right after the last build, refits are compared against it
+    built_cost: f32,
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3::repeat(f32::INFINITY),
+            max: Vec3::repeat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(Self::empty(), |b, p| b.grow(&p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn grow(&self, point: &Vec3) -> Self {
+        Self {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.;
+        }
+
+        let d = self.max - self.min;
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // Bounds of the box after transforming all eight corners
+    pub fn transform(&self, mat: &Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let mut out = Self::empty();
+        for i in 0..8 {
+            let x = if i & 1 == 0 { self.min.x } else { self.max.x };
+            let y = if i & 2 == 0 { self.min.y } else { self.max.y };
+            let z = if i & 4 == 0 { self.min.z } else { self.max.z };
+
+            out = out.grow(&(mat * vec4(x, y, z, 1.)).xyz());
+        }
+
+        out
+    }
+
+    // Slab test, returns the entry distance if the ray overlaps the box within (0, max_t)
+    pub fn hit(&self, origin: &Vec3, inv_dir: &Vec3, max_t: f32) -> Option<f32> {
+        let t0 = (self.min - origin).component_mul(inv_dir);
+        let t1 = (self.max - origin).component_mul(inv_dir);
+
+        // `f32::min`/`max` ignore the NaNs produced by rays lying exactly on a slab plane
+        let near = t0.x.min(t1.x).max(t0.y.min(t1.y)).max(t0.z.min(t1.z));
+        let far = t0.x.max(t1.x).min(t0.y.max(t1.y)).min(t0.z.max(t1.z));
+
+        (near <= far && far > 0. && near < max_t).then_some(near.max(0.))
+    }
+}
+
+impl Bvh {
+    // Builds the tree over the given primitive bounds, primitives with empty bounds are left out
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let start = Instant::now();
+
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(bounds.len() * 2),
+            indices: (0..bounds.len() as u32)
+                .filter(|i| !bounds[*i as usize].is_empty())
+                .collect(),
+            stats: BvhStats::default(),
+            built_cost: 0.,
+        };
+
+        if bvh.indices.is_empty() {
+            return bvh;
+        }
+
+        let centroids: Vec<Vec3> = bounds.iter().map(|b| b.centroid()).collect();
+
+        bvh.nodes.push(Node {
+            bounds: Aabb::empty(),
+            first: 0,
+            count: bvh.indices.len() as u32,
+        });
+        let depth = bvh.subdivide(0, 1, bounds, &centroids);
+
+        bvh.stats = BvhStats {
+            nodes: bvh.nodes.len(),
+            leaves: bvh.nodes.iter().filter(|n| n.count > 0).count(),
+            depth,
+            sah_cost: bvh.sah_cost(),
+            build_time: start.elapsed(),
+        };
+        bvh.built_cost = bvh.stats.sah_cost;
+
+        bvh
+    }
+
+    // Returns the depth of the subtree, `level` is the node's depth with the root at 1
+    fn subdivide(
+        &mut self,
+        node: usize,
+        level: usize,
+        bounds: &[Aabb],
+        centroids: &[Vec3],
+    ) -> usize {
+        let first = self.nodes[node].first as usize;
+        let count = self.nodes[node].count as usize;
+        let prims = &mut self.indices[first..first + count];
+
+        let node_bounds = prims
+            .iter()
+            .fold(Aabb::empty(), |b, i| b.union(&bounds[*i as usize]));
+        let centroid_bounds = Aabb::from_points(prims.iter().map(|i| centroids[*i as usize]));
+        self.nodes[node].bounds = node_bounds;
+
+        if count == 1 || level == MAX_DEPTH {
+            return 1;
+        }
+
+        let Some((axis, split, cost)) = find_split(prims, bounds, centroids, &centroid_bounds)
+        else {
+            // Every centroid is in the same place, nothing can separate them
+            return 1;
+        };
+
+        let leaf_cost = count as f32 * INTERSECTION_COST;
+        let split_cost = TRAVERSAL_COST + cost / node_bounds.surface_area().max(f32::MIN_POSITIVE);
+
+        if split_cost >= leaf_cost && count <= MAX_LEAF_SIZE {
+            return 1;
+        }
+
+        let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+        let bin_of = |c: &Vec3| {
+            (((c[axis] - centroid_bounds.min[axis]) / extent * BINS as f32) as usize).min(BINS - 1)
+        };
+
+        // Partition in place, primitives left of the split bin first
+        let mut left = 0;
+        for i in 0..count {
+            if bin_of(&centroids[prims[i] as usize]) < split {
+                prims.swap(i, left);
+                left += 1;
+            }
+        }
+
+        if left == 0 || left == count {
+            return 1;
+        }
+
+        let left_child = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: Aabb::empty(),
+            first: first as u32,
+            count: left as u32,
+        });
+        self.nodes.push(Node {
+            bounds: Aabb::empty(),
+            first: (first + left) as u32,
+            count: (count - left) as u32,
+        });
+
+        self.nodes[node].first = left_child as u32;
+        self.nodes[node].count = 0;
+
+        let left_depth = self.subdivide(left_child, level + 1, bounds, centroids);
+        let right_depth = self.subdivide(left_child + 1, level + 1, bounds, centroids);
+
+        1 + left_depth.max(right_depth)
+    }
+
+    // Updates the node bounds for primitives that moved without changing the tree's topology.
+    // `bounds` must hold the same primitives, in the same order, as the ones the tree was built with
+    pub fn refit(&mut self, bounds: &[Aabb]) {
+        // Children are always stored after their parent
+        for i in (0..self.nodes.len()).rev() {
+            let node = self.nodes[i];
+
+            self.nodes[i].bounds = if node.count > 0 {
+                let first = node.first as usize;
+                self.indices[first..first + node.count as usize]
+                    .iter()
+                    .fold(Aabb::empty(), |b, p| b.union(&bounds[*p as usize]))
+            } else {
+                let left = &self.nodes[node.first as usize].bounds;
+                left.union(&self.nodes[node.first as usize + 1].bounds)
+            };
+        }
+
+        self.stats.sah_cost = self.sah_cost();
+    }
+
+    // Refits the tree when possible, rebuilding it when the primitives changed or when refitting
+    // has made it too slow to traverse
+    pub fn update(&mut self, bounds: &[Aabb]) {
+        let primitives = bounds.iter().filter(|b| !b.is_empty()).count();
+        let same_primitives = primitives == self.indices.len()
+            && self
+                .indices
+                .iter()
+                .all(|i| bounds.get(*i as usize).is_some_and(|b| !b.is_empty()));
+
+        if self.nodes.is_empty() || !same_primitives {
+            *self = Self::build(bounds);
+            return;
+        }
+
+        self.refit(bounds);
+
+        if self.stats.sah_cost > self.built_cost * REBUILD_THRESHOLD {
+            *self = Self::build(bounds);
+        }
+    }
+
+    pub fn stats(&self) -> BvhStats {
+        self.stats
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or_else(Aabb::empty, |n| n.bounds)
+    }
+
+    // Visits every primitive whose leaf the ray passes through, nearest nodes first. `hit` is
+    // called with the primitive index and the current closest distance, and returns the distance
+    // of a closer hit if it found one
+    pub fn traverse(
+        &self,
+        ray: &Ray,
+        mut max_t: f32,
+        mut hit: impl FnMut(usize, f32) -> Option<f32>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inv_dir = ray.dir.map(|d| 1. / d);
+        let mut stack = Stack::new();
+
+        if self.nodes[0]
+            .bounds
+            .hit(&ray.origin, &inv_dir, max_t)
+            .is_some()
+        {
+            stack.push(0);
+        }
+
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+
+            if node.count > 0 {
+                let first = node.first as usize;
+                for prim in &self.indices[first..first + node.count as usize] {
+                    if let Some(t) = hit(*prim as usize, max_t) {
+                        max_t = max_t.min(t);
+                    }
+                }
+                continue;
+            }
+
+            let left = node.first as usize;
+            let right = left + 1;
+            let left_t = self.nodes[left].bounds.hit(&ray.origin, &inv_dir, max_t);
+            let right_t = self.nodes[right].bounds.hit(&ray.origin, &inv_dir, max_t);
+
+            // Push the far child first so the near one is visited first
+            match (left_t, right_t) {
+                (Some(l), Some(r)) if l <= r => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                (Some(_), Some(_)) => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                (Some(_), None) => stack.push(left),
+                (None, Some(_)) => stack.push(right),
+                (None, None) => {}
+            }
+        }
+    }
+
+    // Like `traverse` but stops as soon as `hit` reports a hit, in no particular order. Meant for
+    // shadow rays
+    pub fn any_hit(&self, ray: &Ray, max_t: f32, mut hit: impl FnMut(usize, f32) -> bool) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_dir = ray.dir.map(|d| 1. / d);
+        let mut stack = Stack::new();
+        stack.push(0);
+
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+
+            if node.bounds.hit(&ray.origin, &inv_dir, max_t).is_none() {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.first as usize);
+                stack.push(node.first as usize + 1);
+                continue;
+            }
+
+            let first = node.first as usize;
+            for prim in &self.indices[first..first + node.count as usize] {
+                if hit(*prim as usize, max_t) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn sah_cost(&self) -> f32 {
+        let Some(root) = self.nodes.first() else {
+            return 0.;
+        };
+
+        let root_area = root.bounds.surface_area().max(f32::MIN_POSITIVE);
+
+        self.nodes
+            .iter()
+            .map(|n| {
+                let cost = if n.count > 0 {
+                    n.count as f32 * INTERSECTION_COST
+                } else {
+                    TRAVERSAL_COST
+                };
+
+                cost * n.bounds.surface_area() / root_area
+            })
+            .sum()
+    }
+}
+
+impl Stack {
+    fn new() -> Self {
+        Self {
+            nodes: [0; MAX_DEPTH],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, node: usize) {
+        self.nodes[self.len] = node as u32;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        self.len = self.len.checked_sub(1)?;
+        Some(self.nodes[self.len] as usize)
+    }
+}
+
+// Binned SAH over all three axes. Returns the axis, the first bin of the right side and the
+// unnormalized cost of the split
+fn find_split(
+    prims: &[u32],
+    bounds: &[Aabb],
+    centroids: &[Vec3],
+    centroid_bounds: &Aabb,
+) -> Option<(usize, usize, f32)> {
+    let mut best: Option<(usize, usize, f32)> = None;
+
+    let ranges = centroid_bounds.min.iter().zip(centroid_bounds.max.iter());
+
+    for (axis, (&min, &max)) in ranges.enumerate() {
+        let extent = max - min;
+
+        if extent <= 0. {
+            continue;
+        }
+
+        let mut bins = [(Aabb::empty(), 0_usize); BINS];
+        for prim in prims {
+            let c = centroids[*prim as usize][axis];
+            let bin = (((c - min) / extent * BINS as f32) as usize).min(BINS - 1);
+
+            bins[bin].0 = bins[bin].0.union(&bounds[*prim as usize]);
+            bins[bin].1 += 1;
+        }
+
+        // Sweep from the right to get the area and count of every right side
+        let mut right_area = [0.; BINS];
+        let mut right_count = [0; BINS];
+        let mut acc = (Aabb::empty(), 0);
+        for i in (1..BINS).rev() {
+            acc = (acc.0.union(&bins[i].0), acc.1 + bins[i].1);
+            right_area[i] = acc.0.surface_area();
+            right_count[i] = acc.1;
+        }
+
+        let mut acc = (Aabb::empty(), 0);
+        for split in 1..BINS {
+            acc = (acc.0.union(&bins[split - 1].0), acc.1 + bins[split - 1].1);
+
+            if acc.1 == 0 || right_count[split] == 0 {
+                continue;
+            }
+
+            let cost = (acc.0.surface_area() * acc.1 as f32
+                + right_area[split] * right_count[split] as f32)
+                * INTERSECTION_COST;
+
+            if best.is_none_or(|b| cost < b.2) {
+                best = Some((axis, split, cost));
+            }
+        }
+    }
+
+    best
+}
@@ -0,0 +1,215 @@
+use glm::{vec3, Vec3};
+use proptest::prelude::*;
+
+use super::{Aabb, Bvh};
+use crate::Ray;
+
+fn vector(range: f32) -> impl Strategy<Value = Vec3> {
+    prop::array::uniform3(-range..range).prop_map(Vec3::from)
+}
+
+// Some boxes are empty, the tree has to leave them out
+fn aabb() -> impl Strategy<Value = Aabb> {
+    let half = prop::array::uniform3(0f32..2.).prop_map(Vec3::from);
+    (vector(10.), half, prop::bool::weighted(0.1)).prop_map(|(center, half, empty)| {
+        if empty {
+            Aabb::empty()
+        } else {
+            Aabb::new(center - half, center + half)
+        }
+    })
+}
+
+fn shift(aabb: &Aabb, offset: &Vec3) -> Aabb {
+    if aabb.is_empty() {
+        *aabb
+    } else {
+        Aabb::new(aabb.min + offset, aabb.max + offset)
+    }
+}
+
+fn ray() -> impl Strategy<Value = Ray> {
+    (vector(15.), vector(1.))
+        .prop_filter("no direction", |(_, dir)| dir.norm() > 0.01)
+        .prop_map(|(origin, dir)| Ray::new(origin, dir))
+}
+
+// Both the bounds a tree was built with and the same boxes moved around
+fn moved_boxes() -> impl Strategy<Value = (Vec<Aabb>, Vec<Aabb>)> {
+    prop::collection::vec(aabb(), 0..64).prop_flat_map(|bounds| {
+        let offsets = prop::collection::vec(vector(5.), bounds.len());
+        (Just(bounds), offsets).prop_map(|(bounds, offsets)| {
+            let moved = bounds
+                .iter()
+                .zip(&offsets)
+                .map(|(b, o)| shift(b, o))
+                .collect();
+            (bounds, moved)
+        })
+    })
+}
+
+fn inv_dir(ray: &Ray) -> Vec3 {
+    ray.dir.map(|d| 1. / d)
+}
+
+// The slab test of an empty box spans everything, but the tree leaves those boxes out
+fn hit(aabb: &Aabb, ray: &Ray, max_t: f32) -> Option<f32> {
+    if aabb.is_empty() {
+        return None;
+    }
+    aabb.hit(&ray.origin, &inv_dir(ray), max_t)
+}
+
+fn brute_closest(bounds: &[Aabb], ray: &Ray, max_t: f32) -> Option<f32> {
+    bounds
+        .iter()
+        .filter_map(|b| hit(b, ray, max_t))
+        .min_by(f32::total_cmp)
+}
+
+// Checks `traverse` and `any_hit` against testing every box
+fn check(bvh: &Bvh, bounds: &[Aabb], ray: &Ray, max_t: f32) -> Result<(), TestCaseError> {
+    let hits: Vec<bool> = bounds
+        .iter()
+        .map(|b| hit(b, ray, max_t).is_some())
+        .collect();
+
+    // Never shrinking the distance, every box the ray passes through is visited exactly once
+    let mut visits = vec![0; bounds.len()];
+    bvh.traverse(ray, max_t, |i, _| {
+        visits[i] += 1;
+        None
+    });
+    for (i, (hit, visits)) in hits.iter().zip(&visits).enumerate() {
+        prop_assert!(*visits <= 1, "primitive {} visited {} times", i, visits);
+        prop_assert!(!hit || *visits == 1, "primitive {} missed", i);
+    }
+
+    let mut closest = None;
+    bvh.traverse(ray, max_t, |i, max_t| {
+        let t = hit(&bounds[i], ray, max_t)?;
+        closest = Some(t);
+        Some(t)
+    });
+    prop_assert_eq!(closest, brute_closest(bounds, ray, max_t));
+
+    let any = bvh.any_hit(ray, max_t, |i, max_t| hit(&bounds[i], ray, max_t).is_some());
+    prop_assert_eq!(any, hits.contains(&true));
+
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn built_tree_matches_brute_force(
+        bounds in prop::collection::vec(aabb(), 0..64),
+        rays in prop::collection::vec(ray(), 8),
+        max_t in 0.1f32..100.,
+    ) {
+        let bvh = Bvh::build(&bounds);
+        for ray in &rays {
+            check(&bvh, &bounds, ray, max_t)?;
+        }
+    }
+
+    #[test]
+    fn refitted_tree_matches_brute_force(
+        (bounds, moved) in moved_boxes(),
+        rays in prop::collection::vec(ray(), 8),
+        max_t in 0.1f32..100.,
+    ) {
+        let mut bvh = Bvh::build(&bounds);
+        bvh.refit(&moved);
+
+        prop_assert_eq!(bvh.bounds(), Bvh::build(&moved).bounds());
+        for ray in &rays {
+            check(&bvh, &moved, ray, max_t)?;
+        }
+    }
+
+    #[test]
+    fn updated_tree_matches_brute_force(
+        (bounds, moved) in moved_boxes(),
+        removed in any::<prop::sample::Index>(),
+        rays in prop::collection::vec(ray(), 8),
+        max_t in 0.1f32..100.,
+    ) {
+        let mut bvh = Bvh::build(&bounds);
+        bvh.update(&moved);
+        for ray in &rays {
+            check(&bvh, &moved, ray, max_t)?;
+        }
+
+        // Losing a primitive changes the tree's topology, so this has to rebuild
+        let mut fewer = moved;
+        if !fewer.is_empty() {
+            let i = removed.index(fewer.len());
+            fewer[i] = Aabb::empty();
+        }
+        bvh.update(&fewer);
+        for ray in &rays {
+            check(&bvh, &fewer, ray, max_t)?;
+        }
+    }
+}
+
+#[test]
+fn empty_tree_hits_nothing() {
+    let ray = Ray::new(Vec3::zeros(), vec3(0., 0., 1.));
+
+    for bounds in [vec![], vec![Aabb::empty(); 3]] {
+        let mut bvh = Bvh::build(&bounds);
+        assert!(bvh.bounds().is_empty());
+        assert_eq!(bvh.stats().nodes, 0);
+
+        bvh.traverse(&ray, f32::INFINITY, |_, _| panic!("visited a primitive"));
+        assert!(!bvh.any_hit(&ray, f32::INFINITY, |_, _| true));
+
+        bvh.refit(&bounds);
+        bvh.update(&bounds);
+        assert!(!bvh.any_hit(&ray, f32::INFINITY, |_, _| true));
+    }
+}
+
+#[test]
+fn single_primitive() {
+    let bounds = [Aabb::new(vec3(-1., -1., 4.), vec3(1., 1., 6.))];
+    let mut bvh = Bvh::build(&bounds);
+    assert_eq!(bvh.bounds(), bounds[0]);
+    assert_eq!(bvh.stats().leaves, 1);
+
+    let toward = Ray::new(Vec3::zeros(), vec3(0., 0., 1.));
+    let away = Ray::new(Vec3::zeros(), vec3(0., 0., -1.));
+    for (ray, max_t) in [(toward, f32::INFINITY), (toward, 3.), (away, f32::INFINITY)] {
+        check(&bvh, &bounds, &ray, max_t).unwrap();
+    }
+
+    // Moved behind the origin, only the other ray reaches it now
+    let moved = [Aabb::new(vec3(-1., -1., -6.), vec3(1., 1., -4.))];
+    bvh.update(&moved);
+    assert_eq!(bvh.bounds(), moved[0]);
+    assert!(!bvh.any_hit(&toward, f32::INFINITY, |_, _| true));
+    assert!(bvh.any_hit(&away, f32::INFINITY, |_, _| true));
+}
+
+#[test]
+fn deep_trees_stay_traversable() {
+    // Spread over the whole exponent range every split only peels off the few boxes at the far
+    // end, which makes for a tree about 50 levels deep
+    let bounds: Vec<_> = (-149..127)
+        .map(|i| {
+            let x = 2_f32.powi(i);
+            Aabb::new(vec3(x, -1., -1.), vec3(x * 1.5, 1., 1.))
+        })
+        .collect();
+    let bvh = Bvh::build(&bounds);
+    assert!(bvh.stats().depth > 32);
+    assert!(bvh.stats().depth <= super::MAX_DEPTH);
+
+    for origin in [vec3(-1., 0., 0.), vec3(1e30, 0., 0.)] {
+        for dir in [vec3(1., 0., 0.), vec3(-1., 0., 0.)] {
+            check(&bvh, &bounds, &Ray::new(origin, dir), f32::INFINITY).unwrap();
+        }
+    }
+}
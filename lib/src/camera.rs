@@ -1,29 +1,82 @@
-use crate::Quaternion;
-use glm::{identity, perspective, translate, vec3, Mat4, Vec3};
+mod aperture;
 
-#[allow(dead_code)]
-#[derive(Clone, Copy)]
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use glm::{identity, ortho, perspective, translate, vec3, vec4, Mat3, Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::sampler::{orthonormal_basis, Rng};
+use crate::{Quaternion, Ray};
+
+pub use aperture::{ApertureMask, ApertureShape};
+
+// The raster backend can only draw linear projections, wider views are shown as a perspective
+// projection clamped to this vertical field of view
+const MAX_RASTER_FOV: f32 = 150.;
+
+#[derive(Clone)]
 pub struct Camera {
-    pub(super) view: Mat4,
-    pub(super) projection: Mat4,
     pub orientation: Quaternion,
     pub position: Vec3,
+    // Vertical field of view in degrees
+    pub(super) fov: f32,
+    // Width over height, windows showing the scene keep it in sync with their framebuffer
+    pub(super) aspect: f32,
+    pub(super) near: f32,
+    pub(super) far: f32,
+    pub(super) projection: Projection,
+    // Thin lens, the ray tracer blurs whatever is away from the focus distance. A radius of zero
+    // is a pinhole with everything in focus
+    pub(super) aperture_radius: f32,
+    pub(super) focus_distance: f32,
+    pub(super) aperture_shape: ApertureShape,
+}
+
+// A camera with the inverses of its view and projection worked out once, for tracing many rays
+pub(crate) struct CameraRays<'a> {
+    camera: &'a Camera,
+    inv_view: Mat4,
+    inv_projection: Mat4,
+}
+
+// How view directions map to the image. The ray tracer follows every model exactly, the raster
+// backend draws the ones that aren't linear as the closest perspective projection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    // Pinhole camera using the camera's field of view
+    #[default]
+    Perspective,
+    // Parallel rays, `height` is the height of the view in world units
+    Orthographic {
+        height: f32,
+    },
+    // Equidistant fisheye, the angle to the view direction grows linearly with the distance from
+    // the image center. The camera's field of view spans the image height, close to 180 degrees
+    // gives a hemisphere
+    Fisheye,
+    // Latitude-longitude panorama of every direction, 360 degrees across and 180 degrees up.
+    // Meant for a 2:1 image
+    Equirectangular,
 }
 
 impl Camera {
     pub fn new(aspect: f32) -> Self {
         Self {
-            view: identity(),
-            projection: perspective(aspect, 45.0, 0.1, 100.0),
-            orientation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            orientation: Quaternion::IDENTITY,
             position: vec3(0., 0., 0.),
+            fov: 45.,
+            aspect,
+            near: 0.1,
+            far: 100.,
+            projection: Projection::Perspective,
+            aperture_radius: 0.,
+            focus_distance: 10.,
+            aperture_shape: ApertureShape::Circle,
         }
     }
 
     pub fn translate(&mut self, translation: Vec3) -> &mut Self {
         self.position += translation;
-        let translation = translation * -1.0;
-        self.view = translate(&self.view, &translation);
         self
     }
 
@@ -32,4 +85,251 @@ impl Camera {
         self.orientation.normalize();
         self
     }
+
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    // Vertical field of view in degrees, ignored unless `0 < degrees < 180`
+    pub fn set_fov(&mut self, degrees: f32) -> &mut Self {
+        if Self::valid_fov(degrees) {
+            self.fov = degrees;
+        }
+        self
+    }
+
+    pub(crate) fn valid_fov(degrees: f32) -> bool {
+        degrees > 0. && degrees < 180.
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    // Ignored unless above zero, e.g. for a minimized window
+    pub fn set_aspect(&mut self, aspect: f32) -> &mut Self {
+        if aspect > 0. && aspect.is_finite() {
+            self.aspect = aspect;
+        }
+        self
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    // Distances to the near and far clip planes, ignored unless `0 < near < far`
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) -> &mut Self {
+        if Self::valid_clip_planes(near, far) {
+            self.near = near;
+            self.far = far;
+        }
+        self
+    }
+
+    pub(crate) fn valid_clip_planes(near: f32, far: f32) -> bool {
+        near > 0. && far > near && far.is_finite()
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn aperture_radius(&self) -> f32 {
+        self.aperture_radius
+    }
+
+    // In world units, zero turns depth of field off. Ignored unless `radius >= 0`
+    pub fn set_aperture_radius(&mut self, radius: f32) -> &mut Self {
+        if Self::valid_aperture_radius(radius) {
+            self.aperture_radius = radius;
+        }
+        self
+    }
+
+    pub(crate) fn valid_aperture_radius(radius: f32) -> bool {
+        radius >= 0. && radius.is_finite()
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    // Distance from the camera to the plane in focus, or the sphere in focus for the panoramic
+    // projections. Ignored unless `distance > 0`
+    pub fn set_focus_distance(&mut self, distance: f32) -> &mut Self {
+        if Self::valid_focus_distance(distance) {
+            self.focus_distance = distance;
+        }
+        self
+    }
+
+    pub(crate) fn valid_focus_distance(distance: f32) -> bool {
+        distance > 0. && distance.is_finite()
+    }
+
+    pub fn aperture_shape(&self) -> &ApertureShape {
+        &self.aperture_shape
+    }
+
+    pub fn set_aperture_shape(&mut self, shape: ApertureShape) -> &mut Self {
+        self.aperture_shape = shape;
+        self
+    }
+
+    // World to view transform, view space looks down -z with +y up
+    pub(crate) fn view(&self) -> Mat4 {
+        self.orientation.as_matrix() * translate(&identity(), &-self.position)
+    }
+
+    // View to clip space transform of the raster backend
+    pub(crate) fn projection_matrix(&self) -> Mat4 {
+        let fov = match self.projection {
+            Projection::Orthographic { height } => {
+                let (x, y) = (height * self.aspect / 2., height / 2.);
+                return ortho(-x, x, -y, y, self.near, self.far);
+            }
+            Projection::Perspective => self.fov,
+            Projection::Fisheye => self.fov.min(MAX_RASTER_FOV),
+            Projection::Equirectangular => MAX_RASTER_FOV,
+        };
+
+        perspective(self.aspect, fov.to_radians(), self.near, self.far)
+    }
+
+    // Camera with the given world to view transform, which must be a rotation and translation.
+    // The lens settings are the defaults of `new`
+    pub(crate) fn from_view(view: &Mat4, aspect: f32) -> Self {
+        let position = view
+            .try_inverse()
+            .map_or(Vec3::zeros(), |inverse| inverse.column(3).xyz());
+        let rotation: Mat3 = view.fixed_view::<3, 3>(0, 0).into();
+
+        Self {
+            // `Quaternion::as_matrix` is the transpose of the rotation it describes
+            orientation: Quaternion::from_matrix3(&rotation.transpose()),
+            position,
+            ..Self::new(aspect)
+        }
+    }
+
+    // World space ray through the image position (u, v) in [0, 1], with (0, 0) at the top left.
+    // Starts at the center of the lens, see `CameraRays::lens_ray` for depth of field
+    pub fn ray(&self, u: f32, v: f32) -> Ray {
+        self.rays().ray(u, v)
+    }
+
+    // Ray through a pixel position, e.g. the cursor position in a window of the given size
+    pub fn screen_ray(&self, x: f32, y: f32, width: f32, height: f32) -> Ray {
+        self.ray(x / width.max(1.), y / height.max(1.))
+    }
+
+    pub(crate) fn rays(&self) -> CameraRays<'_> {
+        CameraRays {
+            camera: self,
+            inv_view: self.view().try_inverse().unwrap_or_else(Mat4::identity),
+            inv_projection: self
+                .projection_matrix()
+                .try_inverse()
+                .unwrap_or_else(Mat4::identity),
+        }
+    }
+}
+
+impl CameraRays<'_> {
+    // See `Camera::ray`
+    pub(crate) fn ray(&self, u: f32, v: f32) -> Ray {
+        let (origin, dir) = self.view_ray(u, v);
+        self.to_world(origin, dir)
+    }
+
+    // Like `ray`, but from a random point on the lens towards where the center ray meets the
+    // focus distance
+    pub(crate) fn lens_ray(&self, u: f32, v: f32, rng: &mut Rng) -> Ray {
+        let camera = self.camera;
+        let (origin, dir) = self.view_ray(u, v);
+        if camera.aperture_radius <= 0. {
+            return self.to_world(origin, dir);
+        }
+
+        // The lens faces the view direction, or the ray for panoramic projections
+        let (focus, (right, up)) = match camera.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                let t = (-camera.focus_distance - origin.z) / dir.z;
+                (origin + dir * t, (Vec3::x(), Vec3::y()))
+            }
+            Projection::Fisheye | Projection::Equirectangular => {
+                let dir = dir.normalize();
+                (
+                    origin + dir * camera.focus_distance,
+                    orthonormal_basis(&dir),
+                )
+            }
+        };
+
+        let offset = camera.aperture_shape.sample(rng) * camera.aperture_radius;
+        let origin = origin + right * offset.x + up * offset.y;
+
+        self.to_world(origin, focus - origin)
+    }
+
+    // Origin and direction of the center ray through (u, v) in view space
+    fn view_ray(&self, u: f32, v: f32) -> (Vec3, Vec3) {
+        let camera = self.camera;
+        // Image position in [-1, 1] with +y up
+        let (x, y) = (u * 2. - 1., 1. - v * 2.);
+
+        match camera.projection {
+            // Uses the same matrices as the raster backend so both agree on what is visible
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                let near = self.inv_projection * vec4(x, y, -1., 1.);
+                let far = self.inv_projection * vec4(x, y, 1., 1.);
+                let (near, far) = (near.xyz() / near.w, far.xyz() / far.w);
+
+                // Orthographic rays start on the near plane, where the raster backend clips
+                let origin = match camera.projection {
+                    Projection::Perspective => Vec3::zeros(),
+                    _ => near,
+                };
+                (origin, far - origin)
+            }
+            Projection::Fisheye => {
+                let x = x * camera.aspect;
+                let theta = (x * x + y * y).sqrt() * camera.fov.to_radians() / 2.;
+                let phi = y.atan2(x);
+                let dir = vec3(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                );
+                (Vec3::zeros(), dir)
+            }
+            // The image center looks down -z
+            Projection::Equirectangular => {
+                let (longitude, latitude) = (x * PI, y * FRAC_PI_2);
+                let dir = vec3(
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    -latitude.cos() * longitude.cos(),
+                );
+                (Vec3::zeros(), dir)
+            }
+        }
+    }
+
+    fn to_world(&self, origin: Vec3, dir: Vec3) -> Ray {
+        let origin = (self.inv_view * origin.push(1.)).xyz();
+        let dir = (self.inv_view * dir.push(0.)).xyz().normalize();
+
+        Ray::new(origin, dir)
+    }
 }
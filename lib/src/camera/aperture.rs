@@ -0,0 +1,159 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use glm::{vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::sampler::{sample_disk, Rng};
+use crate::Film;
+
+// Shape of the lens opening, which is the shape out of focus highlights take
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum ApertureShape {
+    #[default]
+    Circle,
+    // Regular polygon with `blades` corners on the aperture circle, turned by `rotation` degrees
+    Polygon {
+        blades: u32,
+        rotation: f32,
+    },
+    // Any shape, the mask covers the square around the aperture circle
+    Mask(Arc<ApertureMask>),
+}
+
+// Grayscale image of the aperture, brighter pixels let more light through. Kept with its
+// cumulative distribution so points are picked in proportion to their brightness
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "MaskPixels", into = "MaskPixels")]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    pixels: Vec<f32>,
+    cdf: Vec<f32>,
+}
+
+// What scene files store of a mask
+#[derive(Serialize, Deserialize)]
+struct MaskPixels {
+    width: usize,
+    height: usize,
+    pixels: Vec<f32>,
+}
+
+impl ApertureShape {
+    // Point on the aperture in [-1, 1], +y up
+    pub(crate) fn sample(&self, rng: &mut Rng) -> Vec2 {
+        match self {
+            ApertureShape::Circle => sample_disk(rng),
+            ApertureShape::Polygon { blades, rotation } => {
+                sample_polygon(*blades, rotation.to_radians(), rng)
+            }
+            ApertureShape::Mask(mask) => mask.sample(rng),
+        }
+    }
+}
+
+impl ApertureMask {
+    // Pixels are row major from the top left. `None` if the size doesn't match or no pixel lets
+    // light through
+    pub fn new(width: usize, height: usize, pixels: Vec<f32>) -> Option<Self> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return None;
+        }
+
+        let cdf: Vec<f32> = pixels
+            .iter()
+            .scan(0., |sum, p| {
+                *sum += p.max(0.);
+                Some(*sum)
+            })
+            .collect();
+
+        let total = *cdf.last()?;
+        if total <= 0. || !total.is_finite() {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            cdf: cdf.into_iter().map(|c| c / total).collect(),
+            pixels,
+        })
+    }
+
+    // Mask from the average of each pixel's channels
+    pub fn from_film(film: &Film) -> Option<Self> {
+        let pixels = film.pixels().iter().map(|p| p.mean()).collect();
+        Self::new(film.width(), film.height(), pixels)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Vec2 {
+        let target = rng.next_f32();
+        let index = self
+            .cdf
+            .partition_point(|c| *c <= target)
+            .min(self.cdf.len() - 1);
+
+        let x = (index % self.width) as f32 + rng.next_f32();
+        let y = (index / self.width) as f32 + rng.next_f32();
+
+        vec2(
+            x / self.width as f32 * 2. - 1.,
+            1. - y / self.height as f32 * 2.,
+        )
+    }
+}
+
+impl TryFrom<MaskPixels> for ApertureMask {
+    type Error = String;
+
+    fn try_from(mask: MaskPixels) -> Result<Self, Self::Error> {
+        let (width, height) = (mask.width, mask.height);
+        Self::new(width, height, mask.pixels).ok_or_else(|| {
+            format!(
+                "aperture mask needs {}x{} pixels with at least one above zero",
+                width, height
+            )
+        })
+    }
+}
+
+impl From<ApertureMask> for MaskPixels {
+    fn from(mask: ApertureMask) -> Self {
+        Self {
+            width: mask.width,
+            height: mask.height,
+            pixels: mask.pixels,
+        }
+    }
+}
+
+// Uniform point in a regular polygon inscribed in the unit circle, by picking one of the
+// triangles between the center and an edge
+fn sample_polygon(blades: u32, rotation: f32, rng: &mut Rng) -> Vec2 {
+    let blades = blades.max(3);
+    let step = 2. * PI / blades as f32;
+
+    let edge = (rng.next_f32() * blades as f32) as u32 % blades;
+    let a = rotation + edge as f32 * step;
+    let (p, q) = (
+        vec2(a.cos(), a.sin()),
+        vec2((a + step).cos(), (a + step).sin()),
+    );
+
+    let (mut s, mut t) = (rng.next_f32(), rng.next_f32());
+    if s + t > 1. {
+        (s, t) = (1. - s, 1. - t);
+    }
+
+    p * s + q * t
+}
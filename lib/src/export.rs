@@ -0,0 +1,48 @@
+mod gltf;
+mod obj;
+mod ply;
+
+use std::io;
+
+use glm::{Mat3, Mat4, Vec3};
+
+use crate::object::ObjectType;
+use crate::{Mesh, ObjectManager};
+
+pub use ply::PlyFormat;
+
+fn object_type(objects: &ObjectManager, id: usize) -> io::Result<&ObjectType> {
+    objects.get(id).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("No object type with id {}", id),
+        )
+    })
+}
+
+// Mesh of an object type with an object's world transform applied, for formats and tools that
+// don't place meshes themselves
+fn bake(ty: &ObjectType, model: &Mat4) -> Mesh {
+    let linear: Mat3 = model.fixed_view::<3, 3>(0, 0).into();
+    let normal_matrix = linear.try_inverse().unwrap_or(linear).transpose();
+
+    let mut mesh = ty.mesh();
+
+    for v in &mut mesh.verts {
+        *v = glm::vec4_to_vec3(&(model * Vec3::from(*v).push(1.))).into();
+    }
+    // Zero normals mark flat shaded vertices and stay zero
+    for n in &mut mesh.normals {
+        let normal = normal_matrix * Vec3::from(*n);
+        *n = normal.try_normalize(0.).unwrap_or(normal).into();
+    }
+
+    // Mirroring turns the triangles inside out
+    if linear.determinant() < 0. {
+        for tri in &mut mesh.tris {
+            tri.swap(1, 2);
+        }
+    }
+
+    mesh
+}
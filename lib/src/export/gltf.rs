@@ -0,0 +1,294 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use base64::Engine;
+use gltf::binary::{Glb, Header};
+use gltf::json::accessor::{ComponentType, GenericComponentType, Type};
+use gltf::json::buffer::Target;
+use gltf::json::extensions::material as khr;
+use gltf::json::material::{EmissiveFactor, PbrBaseColorFactor, StrengthFactor};
+use gltf::json::mesh::{Mode, Semantic};
+use gltf::json::validation::Checked::Valid;
+use gltf::json::validation::USize64;
+use gltf::json::{self, Index};
+
+use super::{bake, object_type};
+use crate::{Material, Mesh, ObjectManager, Scene};
+
+// glTF document being built, with the one buffer every accessor points into
+#[derive(Default)]
+struct Document {
+    root: json::Root,
+    data: Vec<u8>,
+}
+
+impl Scene {
+    // Writes every object as a node of its own with the transform baked into its mesh, and the
+    // scene's materials. Paths ending in .glb get a binary file, anything else a .gltf with the
+    // buffer embedded
+    pub fn save_gltf(&self, objects: &ObjectManager, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let binary = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("glb"));
+
+        let mut document = Document::default();
+        for material in &self.materials {
+            document.material(material);
+        }
+
+        let world = self.world_matrices();
+
+        let mut nodes = Vec::new();
+        for (i, (handle, object)) in self.iter().enumerate() {
+            let ty = object_type(objects, object.object_type)?;
+            let name = format!("{}.{}", ty.name, i);
+            let mesh = bake(ty, &world[handle.index as usize]);
+
+            let material = object.material.index(&self.materials);
+            nodes.push(document.mesh(name, &mesh, material as u32));
+        }
+
+        // Scenes need at least one node
+        if !nodes.is_empty() {
+            let scene = document.root.push(json::Scene {
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                nodes,
+            });
+            document.root.scene = Some(scene);
+        }
+
+        document.save(path, binary)
+    }
+}
+
+impl Document {
+    // Inverse of the importer's conversion, so materials survive a round trip
+    fn material(&mut self, material: &Material) {
+        let mut converted = json::Material::default();
+        let mut extensions = khr::Material::default();
+        let pbr = &mut converted.pbr_metallic_roughness;
+
+        match *material {
+            Material::Diffuse { albedo } => {
+                pbr.base_color_factor = PbrBaseColorFactor([albedo.x, albedo.y, albedo.z, 1.]);
+                pbr.metallic_factor = StrengthFactor(0.);
+            }
+            Material::Metal { albedo, roughness } => {
+                pbr.base_color_factor = PbrBaseColorFactor([albedo.x, albedo.y, albedo.z, 1.]);
+                pbr.roughness_factor = StrengthFactor(roughness);
+            }
+            Material::Dielectric { ior, tint } => {
+                pbr.base_color_factor = PbrBaseColorFactor([tint.x, tint.y, tint.z, 1.]);
+                pbr.metallic_factor = StrengthFactor(0.);
+                pbr.roughness_factor = StrengthFactor(0.);
+
+                extensions.transmission = Some(khr::Transmission {
+                    transmission_factor: khr::TransmissionFactor(1.),
+                    transmission_texture: None,
+                    extras: Default::default(),
+                });
+                extensions.ior = Some(khr::Ior {
+                    ior: khr::IndexOfRefraction(ior),
+                    extras: Default::default(),
+                });
+                self.uses("KHR_materials_transmission");
+                self.uses("KHR_materials_ior");
+            }
+            // The emissive factor is limited to 0..1, anything brighter goes into the strength
+            Material::Emissive { color, strength } => {
+                pbr.base_color_factor = PbrBaseColorFactor([0., 0., 0., 1.]);
+                pbr.metallic_factor = StrengthFactor(0.);
+
+                let peak = color.max().max(1.);
+                converted.emissive_factor = EmissiveFactor((color / peak).into());
+
+                if strength * peak != 1. {
+                    extensions.emissive_strength = Some(khr::EmissiveStrength {
+                        emissive_strength: khr::EmissiveStrengthFactor(strength * peak),
+                    });
+                    self.uses("KHR_materials_emissive_strength");
+                }
+            }
+        }
+
+        let has_extensions = extensions.transmission.is_some()
+            || extensions.ior.is_some()
+            || extensions.emissive_strength.is_some();
+        if has_extensions {
+            converted.extensions = Some(extensions);
+        }
+
+        self.root.push(converted);
+    }
+
+    fn uses(&mut self, extension: &str) {
+        if !self.root.extensions_used.iter().any(|e| e == extension) {
+            self.root.extensions_used.push(extension.to_string());
+        }
+    }
+
+    // Adds a mesh and a node showing it, returns the node
+    fn mesh(&mut self, name: String, mesh: &Mesh, material: u32) -> Index<json::Node> {
+        let mut attributes = BTreeMap::new();
+
+        // Positions are the one attribute the bounds are required for
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for v in &mesh.verts {
+            for i in 0..3 {
+                min[i] = min[i].min(v[i]);
+                max[i] = max[i].max(v[i]);
+            }
+        }
+
+        let positions = self.accessor(mesh.verts.as_flattened(), Type::Vec3);
+        self.root.accessors[positions.value()].min = Some(json::Value::from(min.to_vec()));
+        self.root.accessors[positions.value()].max = Some(json::Value::from(max.to_vec()));
+        attributes.insert(Valid(Semantic::Positions), positions);
+
+        if !mesh.normals.is_empty() {
+            let normals = self.accessor(mesh.normals.as_flattened(), Type::Vec3);
+            attributes.insert(Valid(Semantic::Normals), normals);
+        }
+        if !mesh.uvs.is_empty() {
+            let uvs = self.accessor(mesh.uvs.as_flattened(), Type::Vec2);
+            attributes.insert(Valid(Semantic::TexCoords(0)), uvs);
+        }
+        if !mesh.colors.is_empty() {
+            let colors = self.accessor(mesh.colors.as_flattened(), Type::Vec3);
+            attributes.insert(Valid(Semantic::Colors(0)), colors);
+        }
+
+        let indices = self.indices(mesh.tris.as_flattened());
+
+        let primitive = json::mesh::Primitive {
+            attributes,
+            extensions: Default::default(),
+            extras: Default::default(),
+            indices: Some(indices),
+            material: Some(Index::new(material)),
+            mode: Valid(Mode::Triangles),
+            targets: None,
+        };
+
+        let mesh = self.root.push(json::Mesh {
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some(name.clone()),
+            primitives: vec![primitive],
+            weights: None,
+        });
+
+        self.root.push(json::Node {
+            mesh: Some(mesh),
+            name: Some(name),
+            ..Default::default()
+        })
+    }
+
+    fn accessor(&mut self, values: &[f32], ty: Type) -> Index<json::Accessor> {
+        let bytes = values.iter().flat_map(|v| v.to_le_bytes());
+        let count = values.len() / ty.multiplicity();
+
+        self.view(bytes, count, ty, ComponentType::F32, Target::ArrayBuffer)
+    }
+
+    fn indices(&mut self, indices: &[i32]) -> Index<json::Accessor> {
+        let bytes = indices.iter().flat_map(|i| (*i as u32).to_le_bytes());
+
+        self.view(
+            bytes,
+            indices.len(),
+            Type::Scalar,
+            ComponentType::U32,
+            Target::ElementArrayBuffer,
+        )
+    }
+
+    // Appends the data to the buffer in a view of its own. Every component is 4 bytes, so views
+    // stay aligned
+    fn view(
+        &mut self,
+        bytes: impl Iterator<Item = u8>,
+        count: usize,
+        ty: Type,
+        component: ComponentType,
+        target: Target,
+    ) -> Index<json::Accessor> {
+        let offset = self.data.len();
+        self.data.extend(bytes);
+
+        let view = self.root.push(json::buffer::View {
+            buffer: Index::new(0),
+            byte_length: USize64::from(self.data.len() - offset),
+            byte_offset: Some(USize64::from(offset)),
+            byte_stride: None,
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: None,
+            target: Some(Valid(target)),
+        });
+
+        self.root.push(json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: USize64::from(count),
+            component_type: Valid(GenericComponentType(component)),
+            extensions: Default::default(),
+            extras: Default::default(),
+            type_: Valid(ty),
+            min: None,
+            max: None,
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+
+    fn save(mut self, path: &Path, binary: bool) -> io::Result<()> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        // Buffers can't be empty, scenes without objects have none
+        if !self.data.is_empty() {
+            let uri = (!binary).then(|| {
+                let encoded = base64::engine::general_purpose::STANDARD.encode(&self.data);
+                format!("data:application/octet-stream;base64,{}", encoded)
+            });
+
+            self.root.push(json::Buffer {
+                byte_length: USize64::from(self.data.len()),
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                uri,
+            });
+        }
+
+        if !binary {
+            let text = json::serialize::to_string_pretty(&self.root).map_err(invalid)?;
+            return fs::write(path, text + "\n");
+        }
+
+        let glb = Glb {
+            // `to_writer` works the length out itself
+            header: Header {
+                magic: *b"glTF",
+                version: 2,
+                length: 0,
+            },
+            json: Cow::Owned(json::serialize::to_vec(&self.root).map_err(invalid)?),
+            bin: (!self.data.is_empty()).then_some(Cow::Owned(self.data)),
+        };
+
+        let mut out = BufWriter::new(File::create(path)?);
+        glb.to_writer(&mut out)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        out.flush()
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use glm::Vec3;
+
+use super::{bake, object_type};
+use crate::{linear_to_srgb, Material, ObjectManager, Scene};
+
+impl Scene {
+    // Writes every object as an OBJ object of its own with the transform baked into the vertices,
+    // and the scene's materials to an MTL file of the same name next to it. Vertex colors are
+    // appended to the positions as sRGB, which most tools read
+    pub fn save_obj(&self, objects: &ObjectManager, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mtl_path = path.with_extension("mtl");
+        let mtl_name = mtl_path.file_name().unwrap_or_default().to_string_lossy();
+
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "mtllib {}", mtl_name)?;
+
+        // Indices count from 1 across the whole file
+        let mut first_vert = 1;
+        let mut first_uv = 1;
+        let mut first_normal = 1;
+
+        let world = self.world_matrices();
+
+        for (i, (handle, object)) in self.iter().enumerate() {
+            let ty = object_type(objects, object.object_type)?;
+            let mesh = bake(ty, &world[handle.index as usize]);
+
+            writeln!(out, "o {}.{}", ty.name, i)?;
+            let material = object.material.index(&self.materials);
+            writeln!(out, "usemtl material{}", material)?;
+
+            for (v, vert) in mesh.verts.iter().enumerate() {
+                write!(out, "v {} {} {}", vert[0], vert[1], vert[2])?;
+                if let Some(color) = mesh.colors.get(v) {
+                    let [r, g, b] = color.map(linear_to_srgb);
+                    write!(out, " {} {} {}", r, g, b)?;
+                }
+                writeln!(out)?;
+            }
+            for uv in &mesh.uvs {
+                writeln!(out, "vt {} {}", uv[0], uv[1])?;
+            }
+            for normal in &mesh.normals {
+                writeln!(out, "vn {} {} {}", normal[0], normal[1], normal[2])?;
+            }
+
+            let has_uvs = !mesh.uvs.is_empty();
+            let has_normals = !mesh.normals.is_empty();
+
+            for tri in &mesh.tris {
+                write!(out, "f")?;
+                for &index in tri {
+                    let v = first_vert + index as usize;
+                    let uv = first_uv + index as usize;
+                    let n = first_normal + index as usize;
+
+                    match (has_uvs, has_normals) {
+                        (true, true) => write!(out, " {}/{}/{}", v, uv, n)?,
+                        (true, false) => write!(out, " {}/{}", v, uv)?,
+                        (false, true) => write!(out, " {}//{}", v, n)?,
+                        (false, false) => write!(out, " {}", v)?,
+                    }
+                }
+                writeln!(out)?;
+            }
+
+            first_vert += mesh.verts.len();
+            first_uv += mesh.uvs.len();
+            first_normal += mesh.normals.len();
+        }
+
+        out.flush()?;
+        save_mtl(&self.materials, &mtl_path)
+    }
+}
+
+// Uses the PBR extension for metals so `load_obj` reads every material back unchanged
+fn save_mtl(materials: &[Material], path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let color = |c: Vec3| format!("{} {} {}", c.x, c.y, c.z);
+
+    for (i, material) in materials.iter().enumerate() {
+        writeln!(out, "newmtl material{}", i)?;
+
+        match *material {
+            Material::Diffuse { albedo } => {
+                writeln!(out, "Kd {}\nillum 1", color(albedo))?;
+            }
+            Material::Metal { albedo, roughness } => {
+                writeln!(out, "Kd {}\nKs {}", color(albedo), color(albedo))?;
+                writeln!(out, "Pm 1\nPr {}\nillum 3", roughness)?;
+            }
+            Material::Dielectric { ior, tint } => {
+                writeln!(out, "Kd 0 0 0\nTf {}\nNi {}\nillum 7", color(tint), ior)?;
+            }
+            Material::Emissive { color: c, strength } => {
+                writeln!(out, "Kd 0 0 0\nKe {}\nillum 0", color(c * strength))?;
+            }
+        }
+
+        writeln!(out)?;
+    }
+
+    out.flush()
+}
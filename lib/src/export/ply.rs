@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::{linear_to_srgb, ObjectManager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+impl ObjectManager {
+    // Writes the triangles of an object type. Normals, texture coordinates and colors are only
+    // written when the type has them, colors as 8 bit sRGB
+    pub fn save_ply(&self, id: usize, path: impl AsRef<Path>, format: PlyFormat) -> io::Result<()> {
+        let ty = super::object_type(self, id)?;
+
+        let has_normals = ty.normals.iter().any(|n| *n != [0.; 3]);
+        let has_uvs = ty.uvs.iter().any(|uv| *uv != [0.; 2]);
+        let has_colors = ty.colors.iter().any(|c| *c != [1.; 3]);
+
+        let mut out = BufWriter::new(File::create(path)?);
+
+        let name = match format {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+            PlyFormat::BinaryBigEndian => "binary_big_endian",
+        };
+        writeln!(out, "ply\nformat {} 1.0", name)?;
+        writeln!(out, "comment {}", ty.name)?;
+        writeln!(out, "element vertex {}", ty.verts.len())?;
+        writeln!(out, "property float x\nproperty float y\nproperty float z")?;
+        if has_normals {
+            writeln!(
+                out,
+                "property float nx\nproperty float ny\nproperty float nz"
+            )?;
+        }
+        if has_uvs {
+            writeln!(out, "property float s\nproperty float t")?;
+        }
+        if has_colors {
+            writeln!(
+                out,
+                "property uchar red\nproperty uchar green\nproperty uchar blue"
+            )?;
+        }
+        writeln!(out, "element face {}", ty.tris.len())?;
+        writeln!(out, "property list uchar int vertex_indices\nend_header")?;
+
+        let mut row = Row::new(format);
+        for i in 0..ty.verts.len() {
+            row.floats(&ty.verts[i]);
+            if has_normals {
+                row.floats(&ty.normals[i]);
+            }
+            if has_uvs {
+                row.floats(&ty.uvs[i]);
+            }
+            if has_colors {
+                for c in ty.colors[i] {
+                    row.byte((linear_to_srgb(c).clamp(0., 1.) * 255.).round() as u8);
+                }
+            }
+            row.finish(&mut out)?;
+        }
+
+        for tri in &ty.tris {
+            row.byte(3);
+            for i in tri {
+                row.int(*i);
+            }
+            row.finish(&mut out)?;
+        }
+
+        out.flush()
+    }
+}
+
+// One vertex or face, encoded into a reused buffer so binary files are written in large chunks
+struct Row {
+    format: PlyFormat,
+    data: Vec<u8>,
+}
+
+impl Row {
+    fn new(format: PlyFormat) -> Self {
+        Self {
+            format,
+            data: Vec::new(),
+        }
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        for value in values {
+            match self.format {
+                PlyFormat::Ascii => self.text(value),
+                PlyFormat::BinaryLittleEndian => self.data.extend(value.to_le_bytes()),
+                PlyFormat::BinaryBigEndian => self.data.extend(value.to_be_bytes()),
+            }
+        }
+    }
+
+    fn int(&mut self, value: i32) {
+        match self.format {
+            PlyFormat::Ascii => self.text(&value),
+            PlyFormat::BinaryLittleEndian => self.data.extend(value.to_le_bytes()),
+            PlyFormat::BinaryBigEndian => self.data.extend(value.to_be_bytes()),
+        }
+    }
+
+    fn byte(&mut self, value: u8) {
+        match self.format {
+            PlyFormat::Ascii => self.text(&value),
+            _ => self.data.push(value),
+        }
+    }
+
+    fn text(&mut self, value: &impl std::fmt::Display) {
+        if !self.data.is_empty() {
+            self.data.push(b' ');
+        }
+        // Writing into a Vec can't fail
+        let _ = write!(self.data, "{}", value);
+    }
+
+    fn finish(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.format == PlyFormat::Ascii {
+            self.data.push(b'\n');
+        }
+        out.write_all(&self.data)?;
+        self.data.clear();
+        Ok(())
+    }
+}
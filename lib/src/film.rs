@@ -0,0 +1,182 @@
+mod hdr;
+mod png;
+mod ppm;
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use glm::Vec3;
+
+pub use ppm::PpmFormat;
+
+// A linear RGB framebuffer. Pixels are stored row major starting at the top left corner
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Vec3::zeros(); width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Vec3>) -> Option<Self> {
+        if pixels.len() != width * height {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Vec3) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn pixels(&self) -> &[Vec3] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Vec3] {
+        &mut self.pixels
+    }
+
+    // Quantizes the film to 8 bit RGB, applying the encoding and dithering in `opts`
+    pub fn to_rgb8(&self, opts: &WriteOptions) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.pixels.len() * 3);
+
+        for (i, pixel) in self.pixels.iter().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            let offset = if opts.dither {
+                BAYER_4X4[y % 4][x % 4] / 16. - 0.5
+            } else {
+                0.
+            };
+
+            for channel in pixel.iter() {
+                let value = match opts.encoding {
+                    Encoding::Linear => *channel,
+                    Encoding::Srgb => linear_to_srgb(*channel),
+                };
+
+                out.push((value * 255. + 0.5 + offset).clamp(0., 255.) as u8);
+            }
+        }
+
+        out
+    }
+
+    pub fn write_ppm(
+        &self,
+        path: impl AsRef<Path>,
+        format: PpmFormat,
+        opts: &WriteOptions,
+    ) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        ppm::write(self, &mut file, format, opts)
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>, opts: &WriteOptions) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        png::write(self, file, opts)
+    }
+
+    // Radiance RGBE, always stores linear values
+    pub fn write_hdr(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        hdr::write(self, &mut file)
+    }
+
+    // Picks the format from the file extension (ppm, png or hdr). PPM files are written as binary
+    pub fn save(&self, path: impl AsRef<Path>, opts: &WriteOptions) -> io::Result<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("ppm") => self.write_ppm(path, PpmFormat::Binary, opts),
+            Some("png") => self.write_png(path, opts),
+            Some("hdr") => self.write_hdr(path),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported image extension for '{}'", path.display()),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    // Values are written as is
+    Linear,
+    // Values are converted from linear to the sRGB transfer curve before quantizing
+    Srgb,
+}
+
+// Options for the 8 bit formats
+#[derive(Debug, Clone, Copy)]
+pub struct WriteOptions {
+    pub encoding: Encoding,
+    // Ordered dithering before quantizing, hides banding in smooth gradients
+    pub dither: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Srgb,
+            dither: false,
+        }
+    }
+}
+
+const BAYER_4X4: [[f32; 4]; 4] = [
+    [0.5, 8.5, 2.5, 10.5],
+    [12.5, 4.5, 14.5, 6.5],
+    [3.5, 11.5, 1.5, 9.5],
+    [15.5, 7.5, 13.5, 5.5],
+];
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0., 1.);
+
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    let value = value.clamp(0., 1.);
+
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,42 @@
+use std::io::{self, Write};
+
+use glm::Vec3;
+
+use super::Film;
+
+// Writes flat (not run length encoded) scanlines, which every Radiance reader accepts
+pub(super) fn write(film: &Film, out: &mut impl Write) -> io::Result<()> {
+    write!(
+        out,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        film.height(),
+        film.width()
+    )?;
+
+    for pixel in film.pixels() {
+        out.write_all(&to_rgbe(pixel))?;
+    }
+
+    out.flush()
+}
+
+fn to_rgbe(color: &Vec3) -> [u8; 4] {
+    // NaNs turn black and infinities the brightest value RGBE can hold
+    let color = color.map(|c| c.max(0.)).inf(&Vec3::repeat(f32::MAX));
+    let max = color.max();
+
+    if max < 1e-32 {
+        return [0; 4];
+    }
+
+    // frexp, max = mantissa * 2^exponent with mantissa in [0.5, 1). The exponent byte stops at 127
+    let exponent = (max.log2().floor() as i32 + 1).min(127);
+    let scale = 256. / 2_f32.powi(exponent);
+
+    [
+        (color.x * scale).min(255.) as u8,
+        (color.y * scale).min(255.) as u8,
+        (color.z * scale).min(255.) as u8,
+        (exponent + 128).clamp(0, 255) as u8,
+    ]
+}
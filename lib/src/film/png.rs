@@ -0,0 +1,21 @@
+use std::io::{self, Write};
+
+use super::{Encoding, Film, WriteOptions};
+
+pub(super) fn write(film: &Film, out: impl Write, opts: &WriteOptions) -> io::Result<()> {
+    let mut encoder = ::png::Encoder::new(out, film.width() as u32, film.height() as u32);
+    encoder.set_color(::png::ColorType::Rgb);
+    encoder.set_depth(::png::BitDepth::Eight);
+
+    // Tag the file so viewers don't apply the sRGB curve a second time to linear data
+    match opts.encoding {
+        Encoding::Srgb => encoder.set_source_srgb(::png::SrgbRenderingIntent::Perceptual),
+        Encoding::Linear => encoder.set_source_gamma(::png::ScaledFloat::new(1.0)),
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&film.to_rgb8(opts))?;
+    writer.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::io::{self, Write};
+
+use super::{Film, WriteOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpmFormat {
+    // P6
+    Binary,
+    // P3
+    Ascii,
+}
+
+pub(super) fn write(
+    film: &Film,
+    out: &mut impl Write,
+    format: PpmFormat,
+    opts: &WriteOptions,
+) -> io::Result<()> {
+    let data = film.to_rgb8(opts);
+
+    match format {
+        PpmFormat::Binary => {
+            write!(out, "P6\n{} {}\n255\n", film.width(), film.height())?;
+            out.write_all(&data)?;
+        }
+        PpmFormat::Ascii => {
+            write!(out, "P3\n{} {}\n255\n", film.width(), film.height())?;
+
+            // One pixel per line keeps lines well under the 70 character limit
+            for pixel in data.chunks_exact(3) {
+                writeln!(out, "{} {} {}", pixel[0], pixel[1], pixel[2])?;
+            }
+        }
+    }
+
+    out.flush()
+}
@@ -0,0 +1,110 @@
+mod gltf;
+mod obj;
+mod ply;
+
+use std::fs;
+use std::path::Path;
+
+use glm::{Mat4, Vec3};
+
+use crate::object::MeshSource;
+use crate::{Camera, Material, ObjectHandle, ObjectManager, Quaternion, Scene, Transform};
+
+// Meshes and materials read from a model file. The meshes are already registered with the
+// `ObjectManager` that loaded them, `add_to_scene` places them in a scene
+#[derive(Clone, Default)]
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    // Named as in the file
+    pub materials: Vec<(String, Material)>,
+    pub instances: Vec<ModelInstance>,
+    // Cameras the file defines, placed relative to the model's origin
+    pub cameras: Vec<Camera>,
+    // Parts of the file that were skipped, each starting with the file's path
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelMesh {
+    pub name: String,
+    pub object_type: usize,
+    // Index into `Model::materials`, `None` uses the scene's default material
+    pub material: Option<usize>,
+}
+
+// One placement of a mesh, files with a node hierarchy can place the same mesh many times
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInstance {
+    // Index into `Model::meshes`
+    pub mesh: usize,
+    // Relative to the model's origin
+    pub transform: Mat4,
+}
+
+impl Model {
+    // Adds the model's materials to the scene and an object for every instance, placed relative
+    // to the given position, scale and rotation
+    pub fn add_to_scene(
+        &self,
+        scene: &mut Scene,
+        position: Vec3,
+        scale: Vec3,
+        rotation: Quaternion,
+    ) -> Vec<ObjectHandle> {
+        let materials: Vec<_> = self
+            .materials
+            .iter()
+            .map(|(_, material)| scene.add_material(*material))
+            .collect();
+
+        let placement = Transform::new(position, rotation, scale).matrix();
+
+        self.instances
+            .iter()
+            .map(|instance| {
+                let mesh = &self.meshes[instance.mesh];
+                let material = mesh.material.map(|i| materials[i]).unwrap_or_default();
+                let placed = Transform::from_matrix(&(placement * instance.transform));
+
+                scene.add_object(
+                    mesh.object_type,
+                    placed.position,
+                    placed.scale,
+                    placed.orientation,
+                    material,
+                )
+            })
+            .collect()
+    }
+}
+
+impl ObjectManager {
+    // Picks the loader from the file extension (obj, gltf, glb or ply)
+    pub fn load_model(&mut self, path: impl AsRef<Path>) -> Result<Model, String> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("obj") => self.load_obj(path),
+            Some("gltf" | "glb") => self.load_gltf(path),
+            Some("ply") => self.load_ply(path),
+            _ => Err(format!("{}: unsupported model format", path.display())),
+        }
+    }
+
+    // Remembers where the model's meshes came from, scene files refer to them by file
+    fn record_sources(&mut self, model: &Model, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        for (mesh, model_mesh) in model.meshes.iter().enumerate() {
+            let source = MeshSource::File {
+                path: path.clone(),
+                mesh,
+            };
+            self.set_source(model_mesh.object_type, source);
+        }
+    }
+}
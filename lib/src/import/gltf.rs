@@ -0,0 +1,308 @@
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use base64::Engine;
+use glm::{vec3, Mat4, Vec3};
+use gltf::buffer::Source;
+use gltf::camera::Projection;
+use gltf::mesh::Mode;
+use gltf::Gltf;
+
+use crate::import::{Model, ModelInstance, ModelMesh};
+use crate::{camera, Camera, Material, Mesh, ObjectManager, Transform};
+
+impl ObjectManager {
+    // Registers every mesh primitive of a glTF 2.0 file (.gltf or .glb) as its own object type and
+    // places them as the default scene's node hierarchy does. Buffers may be embedded or files
+    // next to the model, textures are not loaded
+    pub fn load_gltf(&mut self, path: impl AsRef<Path>) -> Result<Model, String> {
+        let path = path.as_ref();
+        let error = |e: String| format!("{}: {}", path.display(), e);
+
+        let gltf = Gltf::open(path).map_err(|e| error(e.to_string()))?;
+        let buffers = load_buffers(&gltf, path.parent().unwrap_or(Path::new(""))).map_err(error)?;
+
+        let mut model = Model {
+            materials: gltf.materials().map(convert_material).collect(),
+            ..Default::default()
+        };
+
+        // Model meshes of every glTF mesh, one per primitive
+        let mut primitives = Vec::new();
+        for mesh in gltf.meshes() {
+            let mut registered = Vec::new();
+
+            for primitive in mesh.primitives() {
+                let name = mesh
+                    .name()
+                    .map_or_else(|| format!("mesh{}", mesh.index()), str::to_string);
+
+                let Some(data) = read_primitive(&primitive, &buffers).map_err(&error)? else {
+                    continue;
+                };
+                let object_type = self
+                    .register_mesh(&name, data)
+                    .ok_or_else(|| error(format!("invalid primitive in mesh '{}'", name)))?;
+
+                registered.push(model.meshes.len());
+                model.meshes.push(ModelMesh {
+                    name,
+                    object_type,
+                    material: primitive.material().index(),
+                });
+            }
+
+            primitives.push(registered);
+        }
+
+        let scene = gltf.default_scene().or_else(|| gltf.scenes().next());
+        for node in scene.iter().flat_map(|scene| scene.nodes()) {
+            add_node(&mut model, &node, &Mat4::identity(), &primitives);
+        }
+        for warning in &mut model.warnings {
+            *warning = error(mem::take(warning));
+        }
+
+        self.record_sources(&model, path);
+        Ok(model)
+    }
+}
+
+fn add_node(model: &mut Model, node: &gltf::Node, parent: &Mat4, primitives: &[Vec<usize>]) {
+    let transform = parent * Mat4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        for mesh in &primitives[mesh.index()] {
+            model.instances.push(ModelInstance {
+                mesh: *mesh,
+                transform,
+            });
+        }
+    }
+
+    if let Some(camera) = node.camera() {
+        let converted = convert_camera(&camera, &transform, &mut model.warnings);
+        model.cameras.push(converted);
+    }
+
+    for child in node.children() {
+        add_node(model, &child, &transform, primitives);
+    }
+}
+
+fn load_buffers(gltf: &Gltf, dir: &Path) -> Result<Vec<Vec<u8>>, String> {
+    let mut buffers = Vec::new();
+
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or("buffer refers to a missing binary chunk")?,
+            Source::Uri(uri) if uri.starts_with("data:") => {
+                let (_, encoded) = uri.split_once(";base64,").ok_or("unsupported data URI")?;
+                base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .map_err(|e| format!("buffer {}: {}", buffer.index(), e))?
+            }
+            Source::Uri(uri) => {
+                let file = dir.join(percent_decode(uri));
+                fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?
+            }
+        };
+
+        if data.len() < buffer.length() {
+            return Err(format!(
+                "buffer {} is {} bytes, expected {}",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            ));
+        }
+
+        buffers.push(data);
+    }
+
+    Ok(buffers)
+}
+
+// Relative URIs may escape characters, spaces in file names being the common case
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match escaped {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// `None` for primitives that aren't made of triangles, points and lines can't be drawn
+fn read_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[Vec<u8>],
+) -> Result<Option<Mesh>, String> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+    let verts: Vec<_> = reader
+        .read_positions()
+        .ok_or("primitive without positions")?
+        .collect();
+    let normals = reader.read_normals().map_or(Vec::new(), Iterator::collect);
+    let uvs = reader
+        .read_tex_coords(0)
+        .map_or(Vec::new(), |uvs| uvs.into_f32().collect());
+    let colors = reader
+        .read_colors(0)
+        .map_or(Vec::new(), |colors| colors.into_rgb_f32().collect());
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..verts.len() as u32).collect(),
+    };
+
+    let tris = match primitive.mode() {
+        Mode::Triangles => indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect(),
+        // Every other strip triangle is wound the other way around
+        Mode::TriangleStrip => indices
+            .windows(3)
+            .enumerate()
+            .map(|(i, t)| {
+                if i % 2 == 0 {
+                    [t[0], t[1], t[2]]
+                } else {
+                    [t[1], t[0], t[2]]
+                }
+            })
+            .collect(),
+        Mode::TriangleFan => indices
+            .windows(2)
+            .skip(1)
+            .map(|t| [indices[0], t[0], t[1]])
+            .collect::<Vec<_>>(),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(Mesh {
+        verts,
+        normals,
+        uvs,
+        colors,
+        tris: tris.into_iter().map(|t| t.map(|i| i as i32)).collect(),
+    }))
+}
+
+// Closest of the tracer's materials to a metallic-roughness material. Textures are ignored, only
+// the constant factors are used
+fn convert_material(material: gltf::Material) -> (String, Material) {
+    let name = material.name().map_or_else(
+        || format!("material{}", material.index().unwrap_or_default()),
+        str::to_string,
+    );
+
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let base = vec3(r, g, b);
+
+    let emission = Vec3::from(material.emissive_factor());
+    let transmission = material
+        .transmission()
+        .map_or(0., |t| t.transmission_factor());
+
+    let converted = if emission.max() > 0. {
+        Material::Emissive {
+            color: emission,
+            strength: material.emissive_strength().unwrap_or(1.),
+        }
+    } else if transmission > 0.5 {
+        Material::Dielectric {
+            ior: material.ior().unwrap_or(1.5),
+            tint: base,
+        }
+    } else if pbr.metallic_factor() > 0.5 {
+        Material::Metal {
+            albedo: base,
+            roughness: pbr.roughness_factor(),
+        }
+    } else {
+        Material::Diffuse { albedo: base }
+    };
+
+    (name, converted)
+}
+
+fn convert_camera(camera: &gltf::Camera, transform: &Mat4, warnings: &mut Vec<String>) -> Camera {
+    // Cameras look down -z like the raster backend, scale doesn't apply to them
+    let placement = Transform::from_matrix(transform);
+
+    let mut converted = Camera::new(1.);
+    converted.position = placement.position;
+    // The camera's orientation rotates world space into view space
+    converted.orientation = placement.orientation.conjugate();
+
+    let (aspect, near, far) = match camera.projection() {
+        Projection::Perspective(p) => {
+            let fov = p.yfov().to_degrees();
+            if !Camera::valid_fov(fov) {
+                warnings.push(format!(
+                    "camera {}: ignoring field of view {}",
+                    camera.index(),
+                    fov
+                ));
+            }
+            converted.set_fov(fov);
+            (
+                p.aspect_ratio().unwrap_or(1.),
+                p.znear(),
+                p.zfar().unwrap_or(1000.),
+            )
+        }
+        // The magnifications are half the view's width and height
+        Projection::Orthographic(o) => {
+            converted.set_projection(camera::Projection::Orthographic {
+                height: o.ymag() * 2.,
+            });
+            (o.xmag() / o.ymag(), o.znear(), o.zfar())
+        }
+    };
+
+    // The camera keeps its defaults for values it can't project with
+    if !(aspect > 0. && aspect.is_finite()) {
+        warnings.push(format!(
+            "camera {}: ignoring aspect ratio {}",
+            camera.index(),
+            aspect
+        ));
+    }
+    if !Camera::valid_clip_planes(near, far) {
+        warnings.push(format!(
+            "camera {}: ignoring clip planes {} to {}",
+            camera.index(),
+            near,
+            far
+        ));
+    }
+    converted.set_aspect(aspect).set_clip_planes(near, far);
+
+    converted
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use glm::{vec3, Mat4, Vec2, Vec3};
+
+use crate::import::{Model, ModelInstance, ModelMesh};
+use crate::{Material, Mesh, ObjectManager};
+
+// Position, texture coordinate and normal index of a face vertex
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+type Materials = Vec<(String, Material)>;
+
+// Faces of one object/group using one material. Switching either starts a new mesh
+struct Group {
+    name: String,
+    material: Option<String>,
+    mesh: Mesh,
+    // Every combination of indices already in `mesh`
+    vertices: HashMap<FaceVertex, i32>,
+}
+
+// Statements of an MTL material the conversion to `Material` cares about
+struct MtlParams {
+    diffuse: Vec3,
+    specular: Vec3,
+    emission: Vec3,
+    shininess: f32,
+    ior: Option<f32>,
+    dissolve: f32,
+    filter: Vec3,
+    illum: u32,
+    roughness: Option<f32>,
+    metallic: Option<f32>,
+}
+
+impl ObjectManager {
+    // Registers every object/group of a Wavefront OBJ file as its own object type, split further
+    // wherever the material changes. Materials come from the MTL files the OBJ references, looked
+    // up relative to it. MTL files that can't be read are skipped with a warning, their materials
+    // fall back to the scene's default
+    pub fn load_obj(&mut self, path: impl AsRef<Path>) -> Result<Model, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let (groups, materials, warnings) =
+            parse_obj(&source, &path.display().to_string(), |name| {
+                let mtl_path = dir.join(name);
+                let mtl = fs::read_to_string(&mtl_path).map_err(|e| e.to_string())?;
+                parse_mtl(&mtl, &mtl_path.display().to_string())
+            })?;
+
+        let mut model = Model {
+            materials,
+            warnings,
+            ..Default::default()
+        };
+
+        for group in groups {
+            let object_type = self
+                .register_mesh(&group.name, group.mesh)
+                .ok_or_else(|| format!("{}: invalid mesh '{}'", path.display(), group.name))?;
+
+            let material = group
+                .material
+                .and_then(|name| model.materials.iter().position(|(n, _)| *n == name));
+
+            model.instances.push(ModelInstance {
+                mesh: model.meshes.len(),
+                transform: Mat4::identity(),
+            });
+            model.meshes.push(ModelMesh {
+                name: group.name,
+                object_type,
+                material,
+            });
+        }
+
+        self.record_sources(&model, path);
+        Ok(model)
+    }
+}
+
+fn parse_obj(
+    source: &str,
+    file: &str,
+    mut load_mtl: impl FnMut(&str) -> Result<Materials, String>,
+) -> Result<(Vec<Group>, Materials, Vec<String>), String> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut materials = Vec::new();
+    let mut warnings = Vec::new();
+    let mut name = String::from("default");
+    let mut material: Option<String> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("{}:{}: {}", file, number + 1, message);
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&args, 3).map_err(error)?),
+            "vn" => normals.push(parse_vec3(&args, 3).map_err(error)?),
+            "vt" => {
+                let uv = parse_floats(&args, 1, 3).map_err(error)?;
+                uvs.push(Vec2::new(uv[0], uv.get(1).copied().unwrap_or(0.)));
+            }
+            // A bare `o` or `g` keeps the current name
+            "o" | "g" if !args.is_empty() => name = args.join(" "),
+            "usemtl" => material = args.first().map(|s| s.to_string()),
+            "mtllib" => {
+                for lib in &args {
+                    match load_mtl(lib) {
+                        Ok(loaded) => materials.extend(loaded),
+                        Err(e) => warnings.push(error(format!("skipping mtllib {}: {}", lib, e))),
+                    }
+                }
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(format!(
+                        "face needs at least 3 vertices, got {}",
+                        args.len()
+                    )));
+                }
+
+                let same_group = groups
+                    .last()
+                    .is_some_and(|g| g.name == name && g.material == material);
+                if !same_group {
+                    groups.push(Group {
+                        name: name.clone(),
+                        material: material.clone(),
+                        mesh: Mesh::default(),
+                        vertices: HashMap::new(),
+                    });
+                }
+                let group = groups.last_mut().unwrap();
+
+                let mut face = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let key = parse_face_vertex(arg, positions.len(), uvs.len(), normals.len())
+                        .map_err(error)?;
+                    face.push(group.vertex(key, &positions, &uvs, &normals));
+                }
+
+                // Fan triangulation, fine for the convex polygons exporters write
+                for i in 1..face.len() - 1 {
+                    group.mesh.tris.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            // Smoothing groups, lines, points and free form geometry are not supported
+            _ => {}
+        }
+    }
+
+    // Attributes only some faces specify are dropped, the mesh would be half shaded otherwise
+    for group in &mut groups {
+        if !group.vertices.keys().all(|(_, uv, _)| uv.is_some()) {
+            group.mesh.uvs.clear();
+        }
+        if !group.vertices.keys().all(|(_, _, normal)| normal.is_some()) {
+            group.mesh.normals.clear();
+        }
+    }
+
+    Ok((groups, materials, warnings))
+}
+
+impl Group {
+    // Index of the vertex in the mesh, adding it the first time the combination is used
+    fn vertex(
+        &mut self,
+        key: FaceVertex,
+        positions: &[Vec3],
+        uvs: &[Vec2],
+        normals: &[Vec3],
+    ) -> i32 {
+        let mesh = &mut self.mesh;
+
+        *self.vertices.entry(key).or_insert_with(|| {
+            let (position, uv, normal) = key;
+            let uv = uv.map_or(Vec2::zeros(), |i| uvs[i]);
+            let normal = normal.map_or(Vec3::zeros(), |i| normals[i]);
+
+            mesh.verts.push(positions[position].into());
+            mesh.uvs.push(uv.into());
+            mesh.normals.push(normal.into());
+            mesh.verts.len() as i32 - 1
+        })
+    }
+}
+
+// `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolved to zero based indices
+fn parse_face_vertex(
+    arg: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> Result<FaceVertex, String> {
+    let mut parts = arg.split('/');
+
+    let position = parts.next().unwrap_or("");
+    let uv = parts.next().filter(|s| !s.is_empty());
+    let normal = parts.next().filter(|s| !s.is_empty());
+
+    if parts.next().is_some() {
+        return Err(format!("invalid face vertex '{}'", arg));
+    }
+
+    Ok((
+        resolve_index(position, positions, "vertex")?,
+        uv.map(|s| resolve_index(s, uvs, "texture coordinate"))
+            .transpose()?,
+        normal
+            .map(|s| resolve_index(s, normals, "normal"))
+            .transpose()?,
+    ))
+}
+
+// OBJ indices start at 1, negative ones count back from the last element defined so far
+fn resolve_index(s: &str, len: usize, kind: &str) -> Result<usize, String> {
+    let index: i64 = s
+        .parse()
+        .map_err(|_| format!("invalid {} index '{}'", kind, s))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "{} index {} out of range, {} defined",
+            kind, index, len
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f32>, String> {
+    if args.len() < min {
+        return Err(format!(
+            "expected at least {} numbers, got {}",
+            min,
+            args.len()
+        ));
+    }
+
+    args.iter()
+        .take(max)
+        .map(|s| s.parse().map_err(|_| format!("invalid number '{}'", s)))
+        .collect()
+}
+
+fn parse_vec3(args: &[&str], min: usize) -> Result<Vec3, String> {
+    let v = parse_floats(args, min, 3)?;
+    Ok(match v[..] {
+        [x, y, z] => vec3(x, y, z),
+        // MTL colors may give a single value for all channels
+        [x] => Vec3::repeat(x),
+        _ => return Err(format!("expected 3 numbers, got {}", v.len())),
+    })
+}
+
+fn parse_mtl(source: &str, file: &str) -> Result<Materials, String> {
+    let mut materials = Vec::new();
+    let mut current: Option<(String, MtlParams)> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("{}:{}: {}", file, number + 1, message);
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        let args: Vec<&str> = words.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, params)) = current.take() {
+                materials.push((name, params.to_material()));
+            }
+
+            current = Some((args.join(" "), MtlParams::default()));
+            continue;
+        }
+
+        let Some((_, params)) = &mut current else {
+            return Err(error(format!("'{}' before the first newmtl", keyword)));
+        };
+        let float = |args: &[&str]| parse_floats(args, 1, 1).map(|v| v[0]);
+
+        match keyword {
+            "Kd" => params.diffuse = parse_vec3(&args, 1).map_err(error)?,
+            "Ks" => params.specular = parse_vec3(&args, 1).map_err(error)?,
+            "Ke" => params.emission = parse_vec3(&args, 1).map_err(error)?,
+            "Tf" => params.filter = parse_vec3(&args, 1).map_err(error)?,
+            "Ns" => params.shininess = float(&args).map_err(error)?,
+            "Ni" => params.ior = Some(float(&args).map_err(error)?),
+            "d" => params.dissolve = float(&args).map_err(error)?,
+            "Tr" => params.dissolve = 1. - float(&args).map_err(error)?,
+            "Pr" => params.roughness = Some(float(&args).map_err(error)?),
+            "Pm" => params.metallic = Some(float(&args).map_err(error)?),
+            "illum" => {
+                params.illum = args
+                    .first()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| error(format!("invalid illumination model '{}'", line)))?
+            }
+            // Texture maps and the remaining statements are ignored
+            _ => {}
+        }
+    }
+
+    if let Some((name, params)) = current {
+        materials.push((name, params.to_material()));
+    }
+
+    Ok(materials)
+}
+
+impl Default for MtlParams {
+    fn default() -> Self {
+        Self {
+            diffuse: Vec3::repeat(0.8),
+            specular: Vec3::zeros(),
+            emission: Vec3::zeros(),
+            shininess: 0.,
+            ior: None,
+            dissolve: 1.,
+            filter: Vec3::repeat(1.),
+            illum: 2,
+            roughness: None,
+            metallic: None,
+        }
+    }
+}
+
+impl MtlParams {
+    // Closest of the tracer's materials. MTL describes Phong surfaces, so this is a guess based
+    // on the illumination model and the PBR extension where present
+    fn to_material(&self) -> Material {
+        if self.emission.max() > 0. {
+            return Material::Emissive {
+                color: self.emission,
+                strength: 1.,
+            };
+        }
+
+        if self.dissolve < 1. || matches!(self.illum, 4 | 6 | 7 | 9) {
+            return Material::Dielectric {
+                ior: self.ior.filter(|ior| *ior > 1.).unwrap_or(1.5),
+                tint: self.filter,
+            };
+        }
+
+        let metallic = match self.metallic {
+            Some(metallic) => metallic > 0.5,
+            None => matches!(self.illum, 3 | 5),
+        };
+        if metallic {
+            // The PBR extension keeps the base color in Kd, classic files in Ks
+            let albedo = if self.metallic.is_some() || self.specular.max() == 0. {
+                self.diffuse
+            } else {
+                self.specular
+            };
+            let roughness = self
+                .roughness
+                .unwrap_or_else(|| (2. / (self.shininess + 2.)).sqrt());
+
+            return Material::Metal { albedo, roughness };
+        }
+
+        Material::Diffuse {
+            albedo: self.diffuse,
+        }
+    }
+}
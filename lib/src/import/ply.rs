@@ -0,0 +1,408 @@
+#[cfg(test)]
+mod tests;
+
+use std::fs;
+use std::path::Path;
+
+use glm::Mat4;
+
+use crate::import::{Model, ModelInstance, ModelMesh};
+use crate::{srgb_to_linear, Mesh, ObjectManager};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Property)>,
+}
+
+// Where each vertex attribute is found in a vertex row
+struct VertexLayout {
+    position: Option<[usize; 3]>,
+    normal: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+    color: Option<([usize; 3], Scalar)>,
+}
+
+// Reads the values of a PLY body one at a time
+trait Values {
+    fn scalar(&mut self, ty: Scalar) -> Result<f64, String>;
+}
+
+struct AsciiValues<'a> {
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+struct BinaryValues<'a, const BIG_ENDIAN: bool> {
+    data: &'a [u8],
+}
+
+impl ObjectManager {
+    // Registers the faces of a PLY file (ASCII or binary) as one object type. Vertex positions,
+    // normals, texture coordinates and colors are read, other elements and properties are skipped
+    pub fn load_ply(&mut self, path: impl AsRef<Path>) -> Result<Model, String> {
+        let path = path.as_ref();
+        let error = |e: String| format!("{}: {}", path.display(), e);
+
+        let data = fs::read(path).map_err(|e| error(e.to_string()))?;
+        let mesh = parse_ply(&data).map_err(error)?;
+
+        let name = path
+            .file_stem()
+            .map_or_else(|| "ply".to_string(), |s| s.to_string_lossy().into_owned());
+        let object_type = self
+            .register_mesh(&name, mesh)
+            .ok_or_else(|| error("face index out of range".to_string()))?;
+
+        let model = Model {
+            meshes: vec![ModelMesh {
+                name,
+                object_type,
+                material: None,
+            }],
+            instances: vec![ModelInstance {
+                mesh: 0,
+                transform: Mat4::identity(),
+            }],
+            ..Default::default()
+        };
+
+        self.record_sources(&model, path);
+        Ok(model)
+    }
+}
+
+fn parse_ply(data: &[u8]) -> Result<Mesh, String> {
+    let (format, elements, body) = parse_header(data)?;
+    let max_rows = body.len();
+
+    match format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body).map_err(|e| e.to_string())?;
+            let mut values = AsciiValues {
+                tokens: text.split_ascii_whitespace(),
+            };
+            read_body(&elements, &mut values, max_rows)
+        }
+        Format::BinaryLittleEndian => {
+            let mut values = BinaryValues::<false> { data: body };
+            read_body(&elements, &mut values, max_rows)
+        }
+        Format::BinaryBigEndian => {
+            let mut values = BinaryValues::<true> { data: body };
+            read_body(&elements, &mut values, max_rows)
+        }
+    }
+}
+
+// Header lines up to `end_header` and the bytes that follow it
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, &[u8]), String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut rest = data;
+
+    for number in 1.. {
+        let end = rest
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or("header is missing end_header")?;
+        let line = std::str::from_utf8(&rest[..end])
+            .map_err(|_| format!("header line {} is not text", number))?
+            .trim();
+        rest = &rest[end + 1..];
+
+        let error = |message: &str| format!("header line {}: {}", number, message);
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words[..] {
+            ["ply"] if number == 1 => {}
+            _ if number == 1 => return Err(error("not a PLY file")),
+            ["format", name, _version] => {
+                format = Some(match name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(error("unknown format")),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| error("invalid element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List {
+                    count: parse_scalar(count).ok_or_else(|| error("unknown type"))?,
+                    item: parse_scalar(item).ok_or_else(|| error("unknown type"))?,
+                };
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before element"))?;
+                element.properties.push((name.to_string(), property));
+            }
+            ["property", ty, name] => {
+                let property =
+                    Property::Scalar(parse_scalar(ty).ok_or_else(|| error("unknown type"))?);
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before element"))?;
+                element.properties.push((name.to_string(), property));
+            }
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(error("unexpected statement")),
+        }
+    }
+
+    Ok((format.ok_or("header is missing format")?, elements, rest))
+}
+
+fn parse_scalar(name: &str) -> Option<Scalar> {
+    Some(match name {
+        "char" | "int8" => Scalar::I8,
+        "uchar" | "uint8" => Scalar::U8,
+        "short" | "int16" => Scalar::I16,
+        "ushort" | "uint16" => Scalar::U16,
+        "int" | "int32" => Scalar::I32,
+        "uint" | "uint32" => Scalar::U32,
+        "float" | "float32" => Scalar::F32,
+        "double" | "float64" => Scalar::F64,
+        _ => return None,
+    })
+}
+
+// Every row takes up at least a byte of the body, so counts in the header above `max_rows` can't
+// be right and only get as much memory reserved as `max_rows` rows need
+fn read_body(
+    elements: &[Element],
+    values: &mut impl Values,
+    max_rows: usize,
+) -> Result<Mesh, String> {
+    let mut mesh = Mesh::default();
+
+    for element in elements {
+        let capacity = element.count.min(max_rows);
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, values, &mut mesh, capacity)?,
+            "face" => read_faces(element, values, &mut mesh, capacity)?,
+            _ => skip_element(element, values)?,
+        }
+    }
+
+    Ok(mesh)
+}
+
+fn read_vertices(
+    element: &Element,
+    values: &mut impl Values,
+    mesh: &mut Mesh,
+    capacity: usize,
+) -> Result<(), String> {
+    let layout = VertexLayout::new(element);
+    let position = layout.position.ok_or("vertices have no position")?;
+
+    // Lists in vertex rows are skipped, only scalars get a slot
+    let mut row = vec![0.; element.properties.len()];
+
+    mesh.verts.reserve(capacity);
+    for i in 0..element.count {
+        for (slot, (_, property)) in row.iter_mut().zip(&element.properties) {
+            *slot = match property {
+                Property::Scalar(ty) => values.scalar(*ty),
+                Property::List { count, item } => skip_list(values, *count, *item).map(|_| 0.),
+            }
+            .map_err(|e| format!("vertex {}: {}", i, e))?;
+        }
+
+        mesh.verts.push(gather(&row, position));
+
+        if let Some(normal) = layout.normal {
+            mesh.normals.push(gather(&row, normal));
+        }
+        if let Some(uv) = layout.uv {
+            mesh.uvs.push(gather(&row, uv));
+        }
+        if let Some((color, ty)) = layout.color {
+            // Integer colors are 8 or 16 bit sRGB, floats are taken as linear
+            let scale = match ty {
+                Scalar::U8 | Scalar::I8 => Some(255.),
+                Scalar::U16 | Scalar::I16 => Some(65535.),
+                _ => None,
+            };
+            let color = gather(&row, color).map(|c| scale.map_or(c, |s| srgb_to_linear(c / s)));
+            mesh.colors.push(color);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(
+    element: &Element,
+    values: &mut impl Values,
+    mesh: &mut Mesh,
+    capacity: usize,
+) -> Result<(), String> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|(name, _)| name == "vertex_indices" || name == "vertex_index")
+        .ok_or("faces have no vertex_indices")?;
+
+    let mut face = Vec::new();
+    mesh.tris.reserve(capacity);
+
+    for i in 0..element.count {
+        let error = |e: String| format!("face {}: {}", i, e);
+
+        for (p, (_, property)) in element.properties.iter().enumerate() {
+            match property {
+                Property::List { count, item } if p == indices => {
+                    let n = values.scalar(*count).map_err(error)? as usize;
+
+                    face.clear();
+                    for _ in 0..n {
+                        face.push(values.scalar(*item).map_err(error)? as i32);
+                    }
+                }
+                Property::List { count, item } => {
+                    skip_list(values, *count, *item).map_err(error)?
+                }
+                Property::Scalar(ty) => {
+                    values.scalar(*ty).map_err(error)?;
+                }
+            }
+        }
+
+        // Fan triangulation, faces are nearly always triangles or convex quads
+        for j in 1..face.len().saturating_sub(1) {
+            mesh.tris.push([face[0], face[j], face[j + 1]]);
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_element(element: &Element, values: &mut impl Values) -> Result<(), String> {
+    for _ in 0..element.count {
+        for (name, property) in &element.properties {
+            let skipped = match property {
+                Property::Scalar(ty) => values.scalar(*ty).map(|_| ()),
+                Property::List { count, item } => skip_list(values, *count, *item),
+            };
+            skipped.map_err(|e| format!("{} {}: {}", element.name, name, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_list(values: &mut impl Values, count: Scalar, item: Scalar) -> Result<(), String> {
+    let n = values.scalar(count)? as usize;
+    for _ in 0..n {
+        values.scalar(item)?;
+    }
+
+    Ok(())
+}
+
+fn gather<const N: usize>(row: &[f64], indices: [usize; N]) -> [f32; N] {
+    indices.map(|i| row[i] as f32)
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Self {
+        let find = |names: &[&str]| {
+            element.properties.iter().position(|(name, property)| {
+                names.contains(&name.as_str()) && matches!(property, Property::Scalar(_))
+            })
+        };
+        let find_all =
+            |names: &[&[&str]; 3]| Some([find(names[0])?, find(names[1])?, find(names[2])?]);
+
+        let color = find_all(&[&["red", "r"], &["green", "g"], &["blue", "b"]]).map(|indices| {
+            let ty = match element.properties[indices[0]].1 {
+                Property::Scalar(ty) => ty,
+                Property::List { .. } => Scalar::F32,
+            };
+            (indices, ty)
+        });
+
+        Self {
+            position: find_all(&[&["x"], &["y"], &["z"]]),
+            normal: find_all(&[&["nx"], &["ny"], &["nz"]]),
+            uv: find(&["s", "u", "texture_u"])
+                .zip(find(&["t", "v", "texture_v"]))
+                .map(|(u, v)| [u, v]),
+            color,
+        }
+    }
+}
+
+impl Values for AsciiValues<'_> {
+    fn scalar(&mut self, _: Scalar) -> Result<f64, String> {
+        let token = self.tokens.next().ok_or("unexpected end of file")?;
+        token
+            .parse()
+            .map_err(|_| format!("invalid number '{}'", token))
+    }
+}
+
+impl<const BIG_ENDIAN: bool> BinaryValues<'_, BIG_ENDIAN> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let (bytes, rest) = self
+            .data
+            .split_first_chunk::<N>()
+            .ok_or("unexpected end of file")?;
+        self.data = rest;
+
+        Ok(if BIG_ENDIAN {
+            let mut bytes = *bytes;
+            bytes.reverse();
+            bytes
+        } else {
+            *bytes
+        })
+    }
+}
+
+impl<const BIG_ENDIAN: bool> Values for BinaryValues<'_, BIG_ENDIAN> {
+    // Bytes are swapped into little endian order by `take`
+    fn scalar(&mut self, ty: Scalar) -> Result<f64, String> {
+        Ok(match ty {
+            Scalar::I8 => i8::from_le_bytes(self.take()?) as f64,
+            Scalar::U8 => u8::from_le_bytes(self.take()?) as f64,
+            Scalar::I16 => i16::from_le_bytes(self.take()?) as f64,
+            Scalar::U16 => u16::from_le_bytes(self.take()?) as f64,
+            Scalar::I32 => i32::from_le_bytes(self.take()?) as f64,
+            Scalar::U32 => u32::from_le_bytes(self.take()?) as f64,
+            Scalar::F32 => f32::from_le_bytes(self.take()?) as f64,
+            Scalar::F64 => f64::from_le_bytes(self.take()?),
+        })
+    }
+}
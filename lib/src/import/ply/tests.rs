@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::parse_ply;
+use crate::{Mesh, ObjectManager, PlyFormat};
+
+// Two triangles with every attribute the writer supports
+fn quad() -> Mesh {
+    Mesh {
+        verts: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+        normals: vec![[0., 0., 1.]; 4],
+        uvs: vec![[0., 0.], [1., 0.], [1., 1.], [0.25, 0.75]],
+        colors: vec![[1., 0., 0.], [0., 1., 0.], [0., 0., 1.], [0.5, 0.5, 0.5]],
+        tris: vec![[0, 1, 2], [0, 2, 3]],
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("kobold-{}-{}.ply", name, std::process::id()))
+}
+
+fn round_trip(format: PlyFormat, name: &str) {
+    let mut objects = ObjectManager::new();
+    let original = quad();
+    let id = objects.register_mesh("quad", original.clone()).unwrap();
+
+    let path = temp_path(name);
+    objects.save_ply(id, &path, format).unwrap();
+    let model = objects.load_ply(&path);
+    fs::remove_file(&path).unwrap();
+
+    let model = model.unwrap();
+    let loaded = objects.get(model.meshes[0].object_type).unwrap().mesh();
+
+    assert_eq!(loaded.verts, original.verts);
+    assert_eq!(loaded.normals, original.normals);
+    assert_eq!(loaded.uvs, original.uvs);
+    assert_eq!(loaded.tris, original.tris);
+
+    // Colors are stored as 8 bit sRGB
+    assert_eq!(loaded.colors.len(), original.colors.len());
+    let pairs = loaded
+        .colors
+        .iter()
+        .flatten()
+        .zip(original.colors.iter().flatten());
+    for (a, b) in pairs {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+}
+
+#[test]
+fn ascii_round_trip() {
+    round_trip(PlyFormat::Ascii, "ascii");
+}
+
+#[test]
+fn little_endian_round_trip() {
+    round_trip(PlyFormat::BinaryLittleEndian, "le");
+}
+
+#[test]
+fn big_endian_round_trip() {
+    round_trip(PlyFormat::BinaryBigEndian, "be");
+}
+
+#[test]
+fn huge_counts_fail_without_allocating() {
+    for format in ["ascii", "binary_little_endian"] {
+        let header = format!(
+            "ply\nformat {} 1.0\nelement vertex {}\nproperty float x\nproperty float y\n\
+             property float z\nelement face {}\nproperty list uchar int vertex_indices\n\
+             end_header\n",
+            format,
+            usize::MAX,
+            usize::MAX
+        );
+        assert!(parse_ply(header.as_bytes()).is_err());
+    }
+}
@@ -1,12 +1,23 @@
 #[allow(non_snake_case)]
 mod Backend;
 mod buffer;
+mod bvh;
 mod camera;
+mod export;
+mod film;
+mod import;
+mod light;
+mod material;
 mod object;
 mod prelude;
 mod quaternion;
+mod ray;
+mod sampler;
 mod scene;
+mod tracer;
+mod transform;
 
+use std::path::PathBuf;
 use std::time::SystemTime;
 
 use glfw::{fail_on_errors, Glfw, OpenGlProfileHint, WindowHint};
@@ -117,6 +128,9 @@ pub struct WindowOptions {
     pub height: usize,
     pub scene: usize,
     pub title: String,
+    // Directory to read vertex.glsl and fragment.glsl from instead of using the built in shaders.
+    // They are rebuilt whenever either file changes, keeping the last working ones on errors
+    pub shader_dir: Option<PathBuf>,
 }
 
 pub(crate) mod r#macro {
@@ -0,0 +1,148 @@
+use glm::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::sampler::{sample_unit_sphere, Rng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    // Emits equally in every direction. A radius above zero turns it into a spherical light
+    // with soft shadows
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        radius: f32,
+    },
+    // Point light limited to a cone around `direction`. Angles are the half angles of the cone
+    // in radians, the light fades out between `inner_angle` and `outer_angle`
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        radius: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    // Infinitely far away light, like the sun. `direction` is the direction the light travels
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+    },
+    // One sided rectangle spanned by the edges `u` and `v` from the corner at `position`. Emits
+    // towards `u x v`
+    Area {
+        position: Vec3,
+        u: Vec3,
+        v: Vec3,
+        color: Vec3,
+        intensity: f32,
+    },
+}
+
+// Stays valid until the light is removed, handles of removed lights are never reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightHandle(pub(crate) usize);
+
+pub(crate) struct LightSample {
+    // Unit vector from the shaded point towards the light
+    pub dir: Vec3,
+    pub distance: f32,
+    // Light arriving at the shaded point from the sample, before the surface's cosine term
+    pub radiance: Vec3,
+}
+
+impl Light {
+    // Samples a point on the light as seen from `point`, `None` if no light can reach it
+    pub(crate) fn sample(&self, point: &Vec3, rng: &mut Rng) -> Option<LightSample> {
+        match *self {
+            Light::Point {
+                position,
+                color,
+                intensity,
+                radius,
+            } => {
+                let target = position + sample_unit_sphere(rng) * radius;
+                Self::sample_point(point, &target, color * intensity)
+            }
+            Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                radius,
+                inner_angle,
+                outer_angle,
+            } => {
+                let target = position + sample_unit_sphere(rng) * radius;
+                let mut sample = Self::sample_point(point, &target, color * intensity)?;
+
+                let cos = (-sample.dir).dot(&direction.normalize());
+                let falloff = smoothstep(outer_angle.cos(), inner_angle.cos(), cos);
+                if falloff <= 0. {
+                    return None;
+                }
+
+                sample.radiance *= falloff;
+                Some(sample)
+            }
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => Some(LightSample {
+                dir: -direction.normalize(),
+                distance: f32::INFINITY,
+                radiance: color * intensity,
+            }),
+            Light::Area {
+                position,
+                u,
+                v,
+                color,
+                intensity,
+            } => {
+                let target = position + u * rng.next_f32() + v * rng.next_f32();
+                let normal = u.cross(&v);
+                let area = normal.norm();
+
+                let mut sample = Self::sample_point(point, &target, color * intensity)?;
+
+                // Uniform area sampling, converted to solid angle by the light's cosine
+                let cos = (-sample.dir).dot(&(normal / area));
+                if cos <= 0. {
+                    return None;
+                }
+
+                sample.radiance *= cos * area;
+                Some(sample)
+            }
+        }
+    }
+
+    // Inverse square falloff towards a point on the light
+    fn sample_point(point: &Vec3, target: &Vec3, power: Vec3) -> Option<LightSample> {
+        let offset = target - point;
+        let distance = offset.norm();
+
+        if distance <= 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            dir: offset / distance,
+            distance,
+            radiance: power / (distance * distance),
+        })
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1. } else { 0. };
+    }
+
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
@@ -0,0 +1,206 @@
+use std::f32::consts::PI;
+
+use glm::{vec3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::sampler::{sample_cosine_hemisphere, sample_unit_sphere, Rng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Material {
+    // Lambertian reflector
+    Diffuse { albedo: Vec3 },
+    // Conductor, `albedo` is the reflectance at normal incidence. A roughness of 0 is a perfect
+    // mirror, 1 scatters almost like a diffuse surface
+    Metal { albedo: Vec3, roughness: f32 },
+    // Smooth glass like surface, `tint` filters every bounce
+    Dielectric { ior: f32, tint: Vec3 },
+    // Emits `color * strength` and reflects nothing
+    Emissive { color: Vec3, strength: f32 },
+}
+
+// Reflectance at normal incidence of common metals, by chemical symbol
+pub(crate) fn metal_albedo(element: &str) -> Option<Vec3> {
+    Some(match element {
+        "Ag" => vec3(0.97, 0.96, 0.92),
+        "Al" => vec3(0.91, 0.92, 0.92),
+        "Au" => vec3(1.0, 0.78, 0.34),
+        "Cr" => vec3(0.55, 0.56, 0.55),
+        "Cu" => vec3(0.96, 0.64, 0.54),
+        "CuZn" => vec3(0.94, 0.80, 0.56),
+        "Fe" => vec3(0.56, 0.57, 0.58),
+        "Ni" => vec3(0.66, 0.61, 0.53),
+        "Pt" => vec3(0.67, 0.64, 0.59),
+        "Ti" => vec3(0.54, 0.50, 0.45),
+        _ => return None,
+    })
+}
+
+// Reflectance at normal incidence of a conductor with the complex index of refraction `eta + k i`
+pub(crate) fn conductor_albedo(eta: &Vec3, k: &Vec3) -> Vec3 {
+    eta.zip_map(k, |n, k| {
+        ((n - 1.).powi(2) + k * k) / ((n + 1.).powi(2) + k * k)
+    })
+}
+
+// Index into a scene's material table. The default handle is the scene's default material
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MaterialHandle(pub(crate) usize);
+
+impl MaterialHandle {
+    // Handles from another scene fall back to the default material
+    pub(crate) fn resolve(self, materials: &[Material]) -> &Material {
+        &materials[self.index(materials)]
+    }
+
+    // Index of the material `resolve` picks
+    pub(crate) fn index(self, materials: &[Material]) -> usize {
+        if self.0 < materials.len() {
+            self.0
+        } else {
+            0
+        }
+    }
+}
+
+pub(crate) struct Scatter {
+    pub dir: Vec3,
+    pub attenuation: Vec3,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::Diffuse {
+            albedo: vec3(0.8, 0.8, 0.8),
+        }
+    }
+}
+
+impl Material {
+    // Color the raster preview draws the surface with
+    pub fn base_color(&self) -> Vec3 {
+        match *self {
+            Material::Diffuse { albedo } | Material::Metal { albedo, .. } => albedo,
+            Material::Dielectric { tint, .. } => tint,
+            Material::Emissive { color, .. } => color,
+        }
+    }
+
+    // Blinn-Phong parameters for the raster preview, (metallic, roughness)
+    pub(crate) fn preview_params(&self) -> (f32, f32) {
+        match *self {
+            Material::Diffuse { .. } | Material::Emissive { .. } => (0., 1.),
+            Material::Metal { roughness, .. } => (1., roughness),
+            Material::Dielectric { .. } => (0., 0.05),
+        }
+    }
+
+    pub fn emission(&self) -> Vec3 {
+        match *self {
+            Material::Emissive { color, strength } => color * strength,
+            _ => Vec3::zeros(),
+        }
+    }
+
+    // Picks the next direction for a path arriving along the unit vector `dir` at a surface with
+    // the outward facing unit `normal`. Returns `None` if the path is absorbed
+    pub(crate) fn scatter(&self, dir: &Vec3, normal: &Vec3, rng: &mut Rng) -> Option<Scatter> {
+        let front_face = dir.dot(normal) < 0.;
+        let facing = if front_face { *normal } else { -normal };
+
+        match *self {
+            Material::Diffuse { albedo } => Some(Scatter {
+                dir: sample_cosine_hemisphere(&facing, rng),
+                attenuation: albedo,
+            }),
+            Material::Metal { albedo, roughness } => {
+                let fuzz = sample_unit_sphere(rng) * roughness.clamp(0., 1.);
+                let reflected = (reflect(dir, &facing) + fuzz).normalize();
+
+                // Fuzzed below the surface, the microfacets shadow it
+                let cos = reflected.dot(&facing);
+                if cos <= 0. {
+                    return None;
+                }
+
+                Some(Scatter {
+                    dir: reflected,
+                    attenuation: schlick(&albedo, cos),
+                })
+            }
+            Material::Dielectric { ior, tint } => {
+                let eta = if front_face { 1. / ior } else { ior };
+                let cos_i = (-dir.dot(&facing)).min(1.);
+                let sin2_t = eta * eta * (1. - cos_i * cos_i);
+
+                let f0 = ((1. - eta) / (1. + eta)).powi(2);
+                let reflectance = f0 + (1. - f0) * (1. - cos_i).powi(5);
+
+                let dir = if sin2_t > 1. || rng.next_f32() < reflectance {
+                    reflect(dir, &facing)
+                } else {
+                    (dir * eta + facing * (eta * cos_i - (1. - sin2_t).sqrt())).normalize()
+                };
+
+                Some(Scatter {
+                    dir,
+                    attenuation: tint,
+                })
+            }
+            Material::Emissive { .. } => None,
+        }
+    }
+
+    // Light from the unit direction `to_light` that leaves along `-dir`, per unit of incoming
+    // radiance and including the cosine at the surface. Zero for mirrors and glass, which only
+    // scatter into single directions, so light samples can never land on them
+    pub(crate) fn reflectance(&self, dir: &Vec3, normal: &Vec3, to_light: &Vec3) -> Vec3 {
+        let facing = if dir.dot(normal) < 0. {
+            *normal
+        } else {
+            -normal
+        };
+        let cos = to_light.dot(&facing);
+        if cos <= 0. {
+            return Vec3::zeros();
+        }
+
+        match *self {
+            Material::Diffuse { albedo } => albedo * cos / PI,
+            Material::Metal { albedo, roughness } if roughness > 0. => {
+                // `scatter` is weighted by the Fresnel term alone, so the cosine is already part of
+                // how likely it is to pick `to_light`
+                let reflected = reflect(dir, &facing);
+                schlick(&albedo, cos) * fuzz_density(&reflected, to_light, roughness.min(1.))
+            }
+            _ => Vec3::zeros(),
+        }
+    }
+}
+
+// Density per solid angle of `scatter`'s metal directions, the mirror direction `reflected` pushed
+// by a uniform point in a ball of radius `fuzz`. Integrates the ball along the ray towards `dir`
+fn fuzz_density(reflected: &Vec3, dir: &Vec3, fuzz: f32) -> f32 {
+    let cos = reflected.dot(dir);
+    let disc = cos * cos - (1. - fuzz * fuzz);
+    if disc < 0. {
+        return 0.;
+    }
+
+    // With the origin inside the ball the ray starts in it, otherwise it must point towards it
+    let far = cos + disc.sqrt();
+    let near = if fuzz >= 1. { 0. } else { cos - disc.sqrt() };
+    if far <= 0. {
+        return 0.;
+    }
+
+    (far.powi(3) - near.powi(3)) / (4. * PI * fuzz.powi(3))
+}
+
+fn reflect(dir: &Vec3, normal: &Vec3) -> Vec3 {
+    dir - normal * 2. * dir.dot(normal)
+}
+
+// Schlick's approximation of the Fresnel reflectance for a tinted conductor
+fn schlick(f0: &Vec3, cos: f32) -> Vec3 {
+    f0 + (Vec3::repeat(1.) - f0) * (1. - cos).powi(5)
+}
@@ -1,27 +1,110 @@
-use glm::Vec3;
-use glm::Vec4;
+use std::path::PathBuf;
+use std::sync::OnceLock;
 
-use crate::Quaternion;
+use glm::{Vec2, Vec3};
 
-use crate::r#macro;
+use crate::{Aabb, Bvh, BvhStats, MaterialHandle, Transform};
 
-r#macro::use_backend!(ObjectInformation);
+mod primitives;
+mod shape;
+
+pub(crate) use shape::Shape;
 
 pub type Vertex = [f32; 3];
+pub type TexCoord = [f32; 2];
 pub type TriangleIndecies = [i32; 3];
 
+// Finer icospheres have millions of triangles, and past level 15 their indices overflow
+const MAX_ICOSPHERE_LEVEL: u32 = 8;
+
+// Object types every `ObjectManager` starts with, the parametric ones use a default tessellation.
+// Register another one with e.g. `register_torus` for a different tessellation
 #[allow(non_snake_case)]
 pub mod Primitive {
     pub static SPHERE: usize = 0;
     pub static CUBE: usize = 1;
+    pub static PLANE: usize = 2;
+    pub static DISK: usize = 3;
+    pub static CYLINDER: usize = 4;
+    pub static CONE: usize = 5;
+    pub static TORUS: usize = 6;
+    pub static CAPSULE: usize = 7;
+    pub static UV_SPHERE: usize = 8;
 }
 
+// Vertex attributes and triangles of a mesh. `normals`, `uvs` and `colors` are either empty or
+// have one entry per vertex, meshes without normals are flat shaded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub verts: Vec<Vertex>,
+    pub normals: Vec<Vertex>,
+    pub uvs: Vec<TexCoord>,
+    // Linear RGB, multiplies the material's color
+    pub colors: Vec<Vertex>,
+    pub tris: Vec<TriangleIndecies>,
+}
+
+// Mesh data only, the backends upload their own copy the first time a type is drawn. This keeps
+// the manager usable without a graphics context (e.g. for the ray tracer)
 #[derive(Debug)]
 pub(crate) struct ObjectType {
-    verts: Vec<Vertex>,
+    pub(crate) verts: Vec<Vertex>,
+    // Same length as `verts`, zero normals are flat shaded
+    pub(crate) normals: Vec<Vertex>,
+    pub(crate) uvs: Vec<TexCoord>,
+    // Same length as `verts`, white when the mesh has no colors
+    pub(crate) colors: Vec<Vertex>,
     pub(crate) tris: Vec<TriangleIndecies>,
-    name: String,
-    pub(crate) info: ObjectInformation,
+    pub(crate) name: String,
+    // How the ray tracer intersects this type, the mesh is only used for rasterizing when the
+    // shape is analytic
+    pub(crate) shape: Shape,
+    pub(crate) source: MeshSource,
+    // Bottom level BVH over `tris`, built the first time the type is traced
+    blas: OnceLock<Bvh>,
+}
+
+// How an object type was made, so scene files can make it again
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MeshSource {
+    // Registered from code, only the vertex data can recreate it
+    Data,
+    Sphere,
+    Cube,
+    Plane {
+        subdivisions: usize,
+    },
+    Disk {
+        segments: usize,
+    },
+    Cylinder {
+        segments: usize,
+    },
+    Cone {
+        segments: usize,
+    },
+    Torus {
+        minor_radius: f32,
+        segments: usize,
+        sides: usize,
+    },
+    Capsule {
+        radius: f32,
+        segments: usize,
+        rings: usize,
+    },
+    Icosphere {
+        level: u32,
+    },
+    UvSphere {
+        segments: usize,
+        rings: usize,
+    },
+    // Index into `Model::meshes` of the model file at `path`
+    File {
+        path: PathBuf,
+        mesh: usize,
+    },
 }
 
 #[derive(Debug)]
@@ -29,20 +112,47 @@ pub struct ObjectManager {
     registered_objects: Vec<ObjectType>,
 }
 
-pub(crate) struct Object {
+#[derive(Debug, Clone)]
+pub struct Object {
     // Index into list of all registered object types
     pub(crate) object_type: usize,
-    pub position: Vec3,
-    pub orientation: Quaternion,
-    pub scale: Vec3,
-    pub color: Vec4,
+    // Relative to the parent, see `Scene::set_parent`
+    pub transform: Transform,
+    pub material: MaterialHandle,
+    pub(crate) parent: Option<ObjectHandle>,
+}
+
+// Stays valid until the object is removed. Slots of removed objects are reused, the generation
+// tells handles to the old and the new object apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectHandle {
+    pub(crate) index: u32,
+    pub(crate) generation: u32,
+}
+
+impl Default for ObjectManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ObjectManager {
-    pub(crate) fn new() -> Self {
-        let registered_objects = vec![Self::generate_sphere(), Self::generate_cube()];
+    pub fn new() -> Self {
+        let mut manager = Self {
+            registered_objects: vec![Self::generate_sphere()],
+        };
+
+        let cube = MeshSource::Cube;
+        manager.register_shape("cube", Mesh::cube(), Shape::Cube, cube);
+        manager.register_plane(1);
+        manager.register_disk(32);
+        manager.register_cylinder(32);
+        manager.register_cone(32);
+        manager.register_torus(0.25, 48, 24);
+        manager.register_capsule(0.5, 32, 8);
+        manager.register_uv_sphere(32, 16);
 
-        Self { registered_objects }
+        manager
     }
 
     pub fn register_object<'a>(
@@ -51,80 +161,257 @@ impl ObjectManager {
         verts: &'a [Vertex],
         tris: &'a [TriangleIndecies],
     ) -> Option<usize> {
-        let id = self.registered_objects.len();
-        let info = ObjectInformation::new((verts, tris))?;
-
-        self.registered_objects.push(ObjectType {
+        let mesh = Mesh {
             verts: verts.to_vec(),
             tris: tris.to_vec(),
-            name: name.to_string(),
-            info,
-        });
+            ..Default::default()
+        };
+
+        self.register_mesh(name, mesh)
+    }
+
+    pub fn register_mesh(&mut self, name: &str, mesh: Mesh) -> Option<usize> {
+        let count = mesh.verts.len();
+
+        // Reject meshes that index outside of their own vertex list
+        let in_bounds = |i: &i32| *i >= 0 && (*i as usize) < count;
+        if !mesh.tris.iter().flatten().all(in_bounds) {
+            return None;
+        }
+
+        let attribute_ok = |len: usize| len == 0 || len == count;
+        let attributes = [mesh.normals.len(), mesh.uvs.len(), mesh.colors.len()];
+        if !attributes.into_iter().all(attribute_ok) {
+            return None;
+        }
 
-        Some(id)
+        Some(self.register_shape(name, mesh, Shape::Mesh, MeshSource::Data))
+    }
+
+    // Square in the xz plane from -1 to 1 facing +y
+    pub fn register_plane(&mut self, subdivisions: usize) -> usize {
+        let mesh = Mesh::plane(subdivisions);
+        self.register_shape(
+            "plane",
+            mesh,
+            Shape::Plane,
+            MeshSource::Plane { subdivisions },
+        )
+    }
+
+    // Radius 1 in the xz plane facing +y
+    pub fn register_disk(&mut self, segments: usize) -> usize {
+        let mesh = Mesh::disk(segments);
+        self.register_shape("disk", mesh, Shape::Disk, MeshSource::Disk { segments })
+    }
+
+    // Capped, radius 1 from y = -1 to 1
+    pub fn register_cylinder(&mut self, segments: usize) -> usize {
+        let mesh = Mesh::cylinder(segments);
+        let source = MeshSource::Cylinder { segments };
+        self.register_shape("cylinder", mesh, Shape::Cylinder, source)
+    }
+
+    // Capped, radius 1 at y = -1 narrowing to the apex at y = 1
+    pub fn register_cone(&mut self, segments: usize) -> usize {
+        let mesh = Mesh::cone(segments);
+        self.register_shape("cone", mesh, Shape::Cone, MeshSource::Cone { segments })
+    }
+
+    // Ring of radius 1 around the y axis with a tube of `minor_radius`, which is clamped to (0, 1].
+    // `segments` go around the ring and `sides` around the tube
+    pub fn register_torus(&mut self, minor_radius: f32, segments: usize, sides: usize) -> usize {
+        let minor_radius = minor_radius.clamp(f32::EPSILON, 1.);
+        let mesh = Mesh::torus(minor_radius, segments, sides);
+        let source = MeshSource::Torus {
+            minor_radius,
+            segments,
+            sides,
+        };
+        self.register_shape("torus", mesh, Shape::Torus { minor_radius }, source)
+    }
+
+    // Spans y = -1 to 1 with hemispherical caps of `radius`, which is clamped to [0, 1]. `rings`
+    // is the number of latitude bands per cap
+    pub fn register_capsule(&mut self, radius: f32, segments: usize, rings: usize) -> usize {
+        let radius = radius.clamp(0., 1.);
+        let shape = Shape::Capsule {
+            radius,
+            half_height: 1. - radius,
+        };
+
+        let mesh = Mesh::capsule(radius, segments, rings);
+        let source = MeshSource::Capsule {
+            radius,
+            segments,
+            rings,
+        };
+        self.register_shape("capsule", mesh, shape, source)
+    }
+
+    // Radius 1, made of an icosahedron whose faces are split in four `level` times. Every level
+    // quadruples the triangle count, starting from 20. `level` is clamped to 8
+    pub fn register_icosphere(&mut self, level: u32) -> usize {
+        let level = level.min(MAX_ICOSPHERE_LEVEL);
+        let mesh = Mesh::icosphere(level);
+        let source = MeshSource::Icosphere { level };
+        self.register_shape("icosphere", mesh, Shape::Sphere { radius: 1. }, source)
+    }
+
+    // Radius 1 with `rings` latitude bands from pole to pole
+    pub fn register_uv_sphere(&mut self, segments: usize, rings: usize) -> usize {
+        let mesh = Mesh::uv_sphere(segments, rings);
+        let source = MeshSource::UvSphere { segments, rings };
+        self.register_shape("uv sphere", mesh, Shape::Sphere { radius: 1. }, source)
+    }
+
+    fn register_shape(
+        &mut self,
+        name: &str,
+        mesh: Mesh,
+        shape: Shape,
+        source: MeshSource,
+    ) -> usize {
+        self.registered_objects
+            .push(ObjectType::new(name.to_string(), mesh, shape, source));
+        self.registered_objects.len() - 1
+    }
+
+    // First object type made from `source`, types registered from code never match
+    pub(crate) fn find_source(&self, source: &MeshSource) -> Option<usize> {
+        if *source == MeshSource::Data {
+            return None;
+        }
+
+        self.registered_objects
+            .iter()
+            .position(|ty| ty.source == *source)
+    }
+
+    pub(crate) fn set_source(&mut self, id: usize, source: MeshSource) {
+        self.registered_objects[id].source = source;
     }
 
     pub(crate) fn from_id(&self, id: usize) -> &ObjectType {
         &self.registered_objects[id]
     }
 
+    pub(crate) fn get(&self, id: usize) -> Option<&ObjectType> {
+        self.registered_objects.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.registered_objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registered_objects.is_empty()
+    }
+
+    // Statistics of the object type's bottom level BVH, building it if needed. Analytic shapes
+    // have no BVH and report an empty tree
+    pub fn blas_stats(&self, id: usize) -> Option<BvhStats> {
+        let ty = self.registered_objects.get(id)?;
+
+        match ty.shape {
+            Shape::Mesh => Some(ty.blas().stats()),
+            _ => Some(BvhStats::default()),
+        }
+    }
+
+    // Keeps the circumradius of the icosahedron the sphere used to be, so existing scenes don't
+    // change size
     fn generate_sphere() -> ObjectType {
-        let t = (1. + (5_f32).sqrt()) / 2.;
-        let verts = vec![
-            [-1., t, 0.],
-            [1., t, 0.],
-            [-1., -t, 0.],
-            [1., -t, 0.],
-            [0., -1., t],
-            [0., 1., t],
-            [0., -1., -t],
-            [0., 1., -t],
-            [t, 0., -1.],
-            [t, 0., 1.],
-            [-t, 0., -1.],
-            [-t, 0., 1.],
-        ];
-
-        let tris = vec![
-            [0, 11, 5],
-            [0, 5, 1],
-            [0, 1, 7],
-            [0, 7, 10],
-            [0, 10, 11],
-            [1, 5, 9],
-            [5, 11, 4],
-            [11, 10, 2],
-            [10, 7, 6],
-            [7, 1, 8],
-            [3, 9, 4],
-            [3, 4, 2],
-            [3, 2, 6],
-            [3, 6, 8],
-            [3, 8, 9],
-            [4, 9, 5],
-            [2, 4, 11],
-            [6, 2, 10],
-            [8, 6, 7],
-            [9, 8, 1],
-        ];
-
-        let info =
-            ObjectInformation::new((&verts, &tris)).expect("Unable to generate primitive 'SPHERE'");
-
-        ObjectType {
-            name: "sphere".to_string(),
-            verts,
-            tris,
-            info,
+        let t = (1. + 5_f32.sqrt()) / 2.;
+        let radius = (1. + t * t).sqrt();
+
+        let mut mesh = Mesh::icosphere(3);
+        for v in &mut mesh.verts {
+            *v = v.map(|x| x * radius);
         }
+
+        let shape = Shape::Sphere { radius };
+        ObjectType::new("sphere".to_string(), mesh, shape, MeshSource::Sphere)
     }
+}
+
+impl ObjectType {
+    // Fills in missing attributes, the mesh must already be validated
+    fn new(name: String, mesh: Mesh, shape: Shape, source: MeshSource) -> Self {
+        let count = mesh.verts.len();
+        let mut normals = mesh.normals;
+        let mut uvs = mesh.uvs;
+        let mut colors = mesh.colors;
+        normals.resize(count, [0.; 3]);
+        uvs.resize(count, [0.; 2]);
+        colors.resize(count, [1.; 3]);
 
-    fn generate_cube() -> ObjectType {
-        ObjectType {
-            name: "cube".to_string(),
-            verts: vec![],
-            tris: vec![],
-            info: ObjectInformation::new((&[], &[])).expect("Unable to generate primitive 'CUBE'"),
+        Self {
+            verts: mesh.verts,
+            normals,
+            uvs,
+            colors,
+            tris: mesh.tris,
+            name,
+            shape,
+            source,
+            blas: OnceLock::new(),
         }
     }
+
+    // Copy of the mesh data, leaving out the attributes `new` filled in
+    pub(crate) fn mesh(&self) -> Mesh {
+        let used = |default: bool| if default { 0 } else { self.verts.len() };
+        let normals = used(self.normals.iter().all(|n| *n == [0.; 3]));
+        let uvs = used(self.uvs.iter().all(|uv| *uv == [0.; 2]));
+        let colors = used(self.colors.iter().all(|c| *c == [1.; 3]));
+
+        Mesh {
+            verts: self.verts.clone(),
+            normals: self.normals[..normals].to_vec(),
+            uvs: self.uvs[..uvs].to_vec(),
+            colors: self.colors[..colors].to_vec(),
+            tris: self.tris.clone(),
+        }
+    }
+
+    pub(crate) fn local_bounds(&self) -> Aabb {
+        self.shape
+            .bounds()
+            .unwrap_or_else(|| Aabb::from_points(self.verts.iter().map(|v| Vec3::from(*v))))
+    }
+
+    // Vertex color interpolated across a triangle, white for hits on analytic shapes
+    pub(crate) fn color_at(&self, triangle: Option<usize>, barycentrics: &Vec2) -> Vec3 {
+        let Some(index) = triangle else {
+            return Vec3::repeat(1.);
+        };
+
+        let [c0, c1, c2] = self.tris[index].map(|i| Vec3::from(self.colors[i as usize]));
+        c0 * (1. - barycentrics.x - barycentrics.y) + c1 * barycentrics.x + c2 * barycentrics.y
+    }
+
+    pub(crate) fn triangle(&self, index: usize) -> [Vec3; 3] {
+        self.tris[index].map(|i| Vec3::from(self.verts[i as usize]))
+    }
+
+    pub(crate) fn blas(&self) -> &Bvh {
+        self.blas.get_or_init(|| {
+            let bounds: Vec<Aabb> = (0..self.tris.len())
+                .map(|i| Aabb::from_points(self.triangle(i)))
+                .collect();
+
+            Bvh::build(&bounds)
+        })
+    }
+}
+
+impl Object {
+    pub fn object_type(&self) -> usize {
+        self.object_type
+    }
+
+    pub fn parent(&self) -> Option<ObjectHandle> {
+        self.parent
+    }
 }
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use glm::{vec3, Vec3};
+
+use crate::object::{Mesh, TexCoord};
+
+// Tessellations of the analytic shapes in `Shape`. Every shape fits the same local frame the
+// analytic intersection uses, mostly the [-1, 1] cube with y up
+
+impl Mesh {
+    // Appends a (rows + 1) x (cols + 1) vertex grid. Each triangle is wound so it faces along its
+    // vertex normals, so the generators don't have to care about the direction they sweep in
+    fn push_grid(
+        &mut self,
+        rows: usize,
+        cols: usize,
+        vertex: impl Fn(usize, usize) -> (Vec3, Vec3, TexCoord),
+    ) {
+        let base = self.verts.len();
+
+        for r in 0..=rows {
+            for c in 0..=cols {
+                let (position, normal, uv) = vertex(r, c);
+                self.verts.push(position.into());
+                self.normals.push(normal.into());
+                self.uvs.push(uv);
+            }
+        }
+
+        let index = |r: usize, c: usize| base + r * (cols + 1) + c;
+        for r in 0..rows {
+            for c in 0..cols {
+                let (a, b) = (index(r, c), index(r, c + 1));
+                let (d, e) = (index(r + 1, c), index(r + 1, c + 1));
+
+                self.push_facing([a, d, b]);
+                self.push_facing([b, d, e]);
+            }
+        }
+    }
+
+    fn push_facing(&mut self, tri: [usize; 3]) {
+        let [p0, p1, p2] = tri.map(|i| Vec3::from(self.verts[i]));
+        let normal: Vec3 = tri.iter().map(|i| Vec3::from(self.normals[*i])).sum();
+
+        let tri = tri.map(|i| i as i32);
+        if (p1 - p0).cross(&(p2 - p0)).dot(&normal) < 0. {
+            self.tris.push([tri[0], tri[2], tri[1]]);
+        } else {
+            self.tris.push(tri);
+        }
+    }
+
+    // Flat disk of radius 1 around (0, y, 0) facing along `normal_y`
+    fn push_disk(&mut self, segments: usize, y: f32, normal_y: f32) {
+        self.push_grid(1, segments, |r, c| {
+            let angle = TAU * c as f32 / segments as f32;
+            let radius = r as f32;
+            let (x, z) = (angle.cos() * radius, angle.sin() * radius);
+
+            (
+                vec3(x, y, z),
+                vec3(0., normal_y, 0.),
+                [x * 0.5 + 0.5, z * 0.5 + 0.5],
+            )
+        });
+    }
+
+    pub(crate) fn cube() -> Mesh {
+        let mut mesh = Mesh::default();
+
+        for axis in 0..3 {
+            for sign in [-1., 1.] {
+                let normal = Vec3::ith(axis, sign);
+                let u = Vec3::ith((axis + 1) % 3, 1.);
+                let v = Vec3::ith((axis + 2) % 3, 1.);
+
+                mesh.push_grid(1, 1, |r, c| {
+                    let (s, t) = (c as f32, r as f32);
+                    let position = normal + u * (s * 2. - 1.) + v * (t * 2. - 1.);
+                    (position, normal, [s, t])
+                });
+            }
+        }
+
+        mesh
+    }
+
+    // Square in the xz plane facing +y, split into `subdivisions` quads along each side
+    pub(crate) fn plane(subdivisions: usize) -> Mesh {
+        let n = subdivisions.max(1);
+        let mut mesh = Mesh::default();
+
+        mesh.push_grid(n, n, |r, c| {
+            let (s, t) = (c as f32 / n as f32, r as f32 / n as f32);
+            (vec3(s * 2. - 1., 0., t * 2. - 1.), Vec3::y(), [s, t])
+        });
+
+        mesh
+    }
+
+    pub(crate) fn disk(segments: usize) -> Mesh {
+        let mut mesh = Mesh::default();
+        mesh.push_disk(segments.max(3), 0., 1.);
+        mesh
+    }
+
+    // Capped, radius 1 from y = -1 to 1
+    pub(crate) fn cylinder(segments: usize) -> Mesh {
+        let n = segments.max(3);
+        let mut mesh = Mesh::default();
+
+        mesh.push_grid(1, n, |r, c| {
+            let angle = TAU * c as f32 / n as f32;
+            let normal = vec3(angle.cos(), 0., angle.sin());
+            let y = r as f32 * 2. - 1.;
+
+            (
+                normal + Vec3::y() * y,
+                normal,
+                [c as f32 / n as f32, r as f32],
+            )
+        });
+        mesh.push_disk(n, -1., -1.);
+        mesh.push_disk(n, 1., 1.);
+
+        mesh
+    }
+
+    // Capped, radius 1 at y = -1 narrowing to the apex at y = 1
+    pub(crate) fn cone(segments: usize) -> Mesh {
+        let n = segments.max(3);
+        let mut mesh = Mesh::default();
+
+        mesh.push_grid(1, n, |r, c| {
+            let angle = TAU * c as f32 / n as f32;
+            let (cos, sin) = (angle.cos(), angle.sin());
+            let radius = 1. - r as f32;
+
+            (
+                vec3(cos * radius, r as f32 * 2. - 1., sin * radius),
+                vec3(cos * 2., 1., sin * 2.).normalize(),
+                [c as f32 / n as f32, r as f32],
+            )
+        });
+        mesh.push_disk(n, -1., -1.);
+
+        mesh
+    }
+
+    // Ring of radius 1 around the y axis, `minor_radius` is the radius of the tube
+    pub(crate) fn torus(minor_radius: f32, segments: usize, sides: usize) -> Mesh {
+        let (n, m) = (segments.max(3), sides.max(3));
+        let mut mesh = Mesh::default();
+
+        mesh.push_grid(m, n, |r, c| {
+            let (u, v) = (c as f32 / n as f32, r as f32 / m as f32);
+            let (major, minor) = (TAU * u, TAU * v);
+
+            let normal = vec3(
+                minor.cos() * major.cos(),
+                minor.sin(),
+                minor.cos() * major.sin(),
+            );
+            let center = vec3(major.cos(), 0., major.sin());
+
+            (center + normal * minor_radius, normal, [u, v])
+        });
+
+        mesh
+    }
+
+    // Cylinder with hemispherical caps spanning y = -1 to 1. `rings` is per hemisphere
+    pub(crate) fn capsule(radius: f32, segments: usize, rings: usize) -> Mesh {
+        let (n, m) = (segments.max(3), rings.max(1));
+        let half_height = (1. - radius).max(0.);
+        let mut mesh = Mesh::default();
+
+        // Row `m` is the top equator and row `m + 1` the bottom one, the band between them is the
+        // cylinder
+        mesh.push_grid(2 * m + 1, n, |r, c| {
+            let (latitude, center) = if r <= m {
+                (FRAC_PI_2 * r as f32 / m as f32, half_height)
+            } else {
+                (FRAC_PI_2 * (r - 1) as f32 / m as f32, -half_height)
+            };
+            let angle = TAU * c as f32 / n as f32;
+
+            let normal = vec3(
+                latitude.sin() * angle.cos(),
+                latitude.cos(),
+                latitude.sin() * angle.sin(),
+            );
+            let position = normal * radius + Vec3::y() * center;
+
+            (
+                position,
+                normal,
+                [c as f32 / n as f32, r as f32 / (2 * m + 1) as f32],
+            )
+        });
+
+        mesh
+    }
+
+    // Radius 1, `rings` is the number of latitude bands from pole to pole
+    pub(crate) fn uv_sphere(segments: usize, rings: usize) -> Mesh {
+        let (n, m) = (segments.max(3), rings.max(2));
+        let mut mesh = Mesh::default();
+
+        mesh.push_grid(m, n, |r, c| {
+            let (u, v) = (c as f32 / n as f32, r as f32 / m as f32);
+            let (longitude, latitude) = (TAU * u, PI * v);
+
+            let normal = vec3(
+                latitude.sin() * longitude.cos(),
+                latitude.cos(),
+                latitude.sin() * longitude.sin(),
+            );
+
+            (normal, normal, [u, v])
+        });
+
+        mesh
+    }
+
+    // Radius 1, `level` times midpoint subdivided icosahedron
+    pub(crate) fn icosphere(level: u32) -> Mesh {
+        let t = (1. + 5_f32.sqrt()) / 2.;
+        let mut verts: Vec<Vec3> = [
+            [-1., t, 0.],
+            [1., t, 0.],
+            [-1., -t, 0.],
+            [1., -t, 0.],
+            [0., -1., t],
+            [0., 1., t],
+            [0., -1., -t],
+            [0., 1., -t],
+            [t, 0., -1.],
+            [t, 0., 1.],
+            [-t, 0., -1.],
+            [-t, 0., 1.],
+        ]
+        .iter()
+        .map(|v| Vec3::from(*v).normalize())
+        .collect();
+
+        let mut tris: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..level {
+            // Neighbouring faces share the vertex on their common edge
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let v = (verts[a as usize] + verts[b as usize]).normalize();
+                    verts.push(v);
+                    verts.len() as u32 - 1
+                })
+            };
+
+            tris = tris
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let uvs = verts
+            .iter()
+            .map(|v| {
+                let u = (v.z.atan2(v.x) / TAU).rem_euclid(1.);
+                [u, v.y.clamp(-1., 1.).acos() / PI]
+            })
+            .collect();
+
+        Mesh {
+            verts: verts.iter().map(|v| (*v).into()).collect(),
+            normals: verts.iter().map(|v| (*v).into()).collect(),
+            uvs,
+            colors: Vec::new(),
+            tris: tris.iter().map(|t| t.map(|i| i as i32)).collect(),
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use std::f32::consts::{PI, TAU};
+
+use glm::{vec2, vec3, Vec2, Vec3};
+
+use crate::{Aabb, Ray};
+
+// How the ray tracer intersects an object type. Analytic shapes use the same local frame as the
+// tessellation the rasterizer draws, see `primitives.rs`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Shape {
+    Mesh,
+    // Centered on the origin
+    Sphere { radius: f32 },
+    // The [-1, 1] cube
+    Cube,
+    // [-1, 1] square in the xz plane facing +y
+    Plane,
+    // Radius 1 in the xz plane facing +y
+    Disk,
+    // Capped, radius 1 from y = -1 to 1
+    Cylinder,
+    // Capped, radius 1 at y = -1 narrowing to the apex at y = 1
+    Cone,
+    // Ring of radius 1 around the y axis
+    Torus { minor_radius: f32 },
+    // Cylinder of `radius` from y = -half_height to half_height, capped by hemispheres
+    Capsule { radius: f32, half_height: f32 },
+}
+
+// Local space hit on an analytic shape. The normal points out of the shape but is not normalized
+pub(crate) struct ShapeHit {
+    pub t: f32,
+    pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+// Keeps the nearest of several candidate hits within (0, max_t)
+struct Nearest {
+    max_t: f32,
+    hit: Option<ShapeHit>,
+}
+
+impl Nearest {
+    fn new(max_t: f32) -> Self {
+        Self { max_t, hit: None }
+    }
+
+    fn accepts(&self, t: f32) -> bool {
+        t > 0. && t < self.max_t
+    }
+
+    fn offer(&mut self, t: f32, normal: Vec3, uv: Vec2) {
+        if self.accepts(t) {
+            self.max_t = t;
+            self.hit = Some(ShapeHit { t, normal, uv });
+        }
+    }
+}
+
+impl Shape {
+    // `None` for meshes, their bounds come from the vertices
+    pub(crate) fn bounds(&self) -> Option<Aabb> {
+        let extent = match *self {
+            Shape::Mesh => return None,
+            Shape::Sphere { radius } => Vec3::repeat(radius),
+            Shape::Cube | Shape::Cylinder | Shape::Cone => Vec3::repeat(1.),
+            Shape::Plane | Shape::Disk => vec3(1., 0., 1.),
+            Shape::Torus { minor_radius } => {
+                vec3(1. + minor_radius, minor_radius, 1. + minor_radius)
+            }
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => vec3(radius, half_height + radius, radius),
+        };
+
+        Some(Aabb::new(-extent, extent))
+    }
+
+    // Nearest hit in (0, max_t) on the local space `ray`, always `None` for meshes
+    pub(crate) fn intersect(&self, ray: &Ray, max_t: f32) -> Option<ShapeHit> {
+        let mut nearest = Nearest::new(max_t);
+
+        match *self {
+            Shape::Mesh => {}
+            Shape::Sphere { radius } => {
+                intersect_sphere(ray, Vec3::zeros(), radius, &mut nearest, |_| true)
+            }
+            Shape::Cube => intersect_cube(ray, &mut nearest),
+            Shape::Plane => intersect_cap(ray, 0., 1., &mut nearest, |p| {
+                p.x.abs() <= 1. && p.z.abs() <= 1.
+            }),
+            Shape::Disk => {
+                intersect_cap(ray, 0., 1., &mut nearest, |p| p.x * p.x + p.z * p.z <= 1.)
+            }
+            Shape::Cylinder => {
+                intersect_tube(ray, 1., 1., &mut nearest);
+                for y in [-1., 1.] {
+                    intersect_cap(ray, y, y, &mut nearest, |p| p.x * p.x + p.z * p.z <= 1.);
+                }
+            }
+            Shape::Cone => {
+                intersect_cone(ray, &mut nearest);
+                intersect_cap(ray, -1., -1., &mut nearest, |p| p.x * p.x + p.z * p.z <= 1.);
+            }
+            Shape::Torus { minor_radius } => intersect_torus(ray, minor_radius, &mut nearest),
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => {
+                intersect_tube(ray, radius, half_height, &mut nearest);
+                intersect_sphere(ray, Vec3::y() * half_height, radius, &mut nearest, |p| {
+                    p.y >= half_height
+                });
+                intersect_sphere(ray, -Vec3::y() * half_height, radius, &mut nearest, |p| {
+                    p.y <= -half_height
+                });
+            }
+        }
+
+        nearest.hit
+    }
+}
+
+// Both roots of a t^2 + 2 half_b t + c, smallest first. Uses the numerically stable form that
+// avoids cancellation when one root is close to zero
+fn solve_quadratic(a: f32, half_b: f32, c: f32) -> Option<(f32, f32)> {
+    let disc = half_b * half_b - a * c;
+    if disc < 0. || a == 0. {
+        return None;
+    }
+
+    let q = -(half_b + disc.sqrt().copysign(half_b));
+    if q == 0. {
+        return Some((0., 0.));
+    }
+
+    let (r0, r1) = (q / a, c / q);
+    Some((r0.min(r1), r0.max(r1)))
+}
+
+// Longitude around the y axis in [0, 1), matching the u coordinate of the tessellations
+fn longitude(p: &Vec3) -> f32 {
+    (p.z.atan2(p.x) / TAU).rem_euclid(1.)
+}
+
+fn intersect_sphere(
+    ray: &Ray,
+    center: Vec3,
+    radius: f32,
+    nearest: &mut Nearest,
+    keep: impl Fn(&Vec3) -> bool,
+) {
+    let oc = ray.origin - center;
+    let roots = solve_quadratic(
+        ray.dir.norm_squared(),
+        oc.dot(&ray.dir),
+        oc.norm_squared() - radius * radius,
+    );
+    let Some((t0, t1)) = roots else {
+        return;
+    };
+
+    for t in [t0, t1] {
+        let p = ray.at(t);
+        if !nearest.accepts(t) || !keep(&p) {
+            continue;
+        }
+
+        let normal = p - center;
+        let latitude = (normal.y / radius).clamp(-1., 1.).acos();
+        nearest.offer(t, normal, vec2(longitude(&normal), latitude / PI));
+    }
+}
+
+// Plane at height `y` facing along `normal_y`, limited to where `inside` holds
+fn intersect_cap(
+    ray: &Ray,
+    y: f32,
+    normal_y: f32,
+    nearest: &mut Nearest,
+    inside: impl Fn(&Vec3) -> bool,
+) {
+    if ray.dir.y == 0. {
+        return;
+    }
+
+    let t = (y - ray.origin.y) / ray.dir.y;
+    let p = ray.at(t);
+    if nearest.accepts(t) && inside(&p) {
+        let uv = vec2(p.x * 0.5 + 0.5, p.z * 0.5 + 0.5);
+        nearest.offer(t, vec3(0., normal_y, 0.), uv);
+    }
+}
+
+// Open tube of `radius` around the y axis from -half_height to half_height
+fn intersect_tube(ray: &Ray, radius: f32, half_height: f32, nearest: &mut Nearest) {
+    let (o, d) = (ray.origin, ray.dir);
+    let roots = solve_quadratic(
+        d.x * d.x + d.z * d.z,
+        o.x * d.x + o.z * d.z,
+        o.x * o.x + o.z * o.z - radius * radius,
+    );
+    let Some((t0, t1)) = roots else {
+        return;
+    };
+
+    for t in [t0, t1] {
+        let p = ray.at(t);
+        if p.y.abs() <= half_height {
+            let v = (p.y + half_height) / (2. * half_height).max(f32::MIN_POSITIVE);
+            nearest.offer(t, vec3(p.x, 0., p.z), vec2(longitude(&p), v));
+        }
+    }
+}
+
+// Side of the cone, x^2 + z^2 = ((1 - y) / 2)^2 for y in [-1, 1]
+fn intersect_cone(ray: &Ray, nearest: &mut Nearest) {
+    let (o, d) = (ray.origin, ray.dir);
+    let k2 = 0.25;
+    let h = 1. - o.y;
+
+    let roots = solve_quadratic(
+        d.x * d.x + d.z * d.z - k2 * d.y * d.y,
+        o.x * d.x + o.z * d.z + k2 * h * d.y,
+        o.x * o.x + o.z * o.z - k2 * h * h,
+    );
+    let Some((t0, t1)) = roots else {
+        return;
+    };
+
+    for t in [t0, t1] {
+        let p = ray.at(t);
+        // The quadric continues past the apex into a mirrored cone
+        if p.y.abs() <= 1. {
+            let normal = vec3(p.x, k2 * (1. - p.y), p.z);
+            nearest.offer(t, normal, vec2(longitude(&p), (p.y + 1.) / 2.));
+        }
+    }
+}
+
+fn intersect_cube(ray: &Ray, nearest: &mut Nearest) {
+    let mut t_near = f32::NEG_INFINITY;
+    let mut t_far = f32::INFINITY;
+
+    for axis in 0..3 {
+        let inv = 1. / ray.dir[axis];
+        let t0 = (-1. - ray.origin[axis]) * inv;
+        let t1 = (1. - ray.origin[axis]) * inv;
+
+        t_near = t_near.max(t0.min(t1));
+        t_far = t_far.min(t0.max(t1));
+    }
+
+    if t_near > t_far || t_near.is_nan() || t_far.is_nan() {
+        return;
+    }
+
+    // Leaving the cube when the ray starts inside of it
+    for t in [t_near, t_far] {
+        if !nearest.accepts(t) {
+            continue;
+        }
+
+        let p = ray.at(t);
+        let axis = p.iamax();
+        let normal = Vec3::ith(axis, p[axis].signum());
+        let uv = vec2(p[(axis + 1) % 3], p[(axis + 2) % 3]).map(|x| x * 0.5 + 0.5);
+
+        nearest.offer(t, normal, uv);
+        return;
+    }
+}
+
+// (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) with R = 1, solved as a quartic in t
+fn intersect_torus(ray: &Ray, minor_radius: f32, nearest: &mut Nearest) {
+    let outer = 1. + minor_radius;
+
+    // Start the solve from where the ray enters the bounding sphere with a unit direction, the
+    // quartic loses a lot of precision for distant origins
+    let len = ray.dir.norm();
+    if len == 0. {
+        return;
+    }
+    let dir = ray.dir / len;
+    let Some((enter, exit)) = solve_quadratic(
+        1.,
+        ray.origin.dot(&dir),
+        ray.origin.norm_squared() - outer * outer,
+    ) else {
+        return;
+    };
+    if exit <= 0. {
+        return;
+    }
+    let start = enter.max(0.);
+
+    let o = (ray.origin + dir * start).cast::<f64>();
+    let d = dir.cast::<f64>();
+    let r2 = (minor_radius as f64).powi(2);
+
+    let e = o.dot(&d);
+    let g = o.norm_squared() + 1. - r2;
+    let coefficients = [
+        g * g - 4. * (o.x * o.x + o.z * o.z),
+        4. * e * g - 8. * (o.x * d.x + o.z * d.z),
+        4. * e * e + 2. * g - 4. * (d.x * d.x + d.z * d.z),
+        4. * e,
+        1.,
+    ];
+
+    let (roots, count) = solve_quartic(&coefficients);
+    for root in &roots[..count] {
+        let t = (start + *root as f32) / len;
+        if !nearest.accepts(t) {
+            continue;
+        }
+
+        let p = ray.at(t);
+        let ring = vec3(p.x, 0., p.z).normalize();
+        let normal = p - ring;
+        let v = (normal.y.atan2(normal.dot(&ring)) / TAU).rem_euclid(1.);
+
+        nearest.offer(t, normal, vec2(longitude(&p), v));
+    }
+}
+
+// Real roots of c[0] + c[1] x + c[2] x^2 + c[3] x^3 + c[4] x^4 (Ferrari's method, following
+// Schwarze in Graphics Gems I), polished with a few Newton steps
+fn solve_quartic(c: &[f64; 5]) -> ([f64; 4], usize) {
+    let mut roots = [0.; 4];
+    let mut count = 0;
+
+    // x^4 + a x^3 + b x^2 + c x + d, substituted with x = y - a / 4 to get rid of the cubic term
+    let (a, b, cc, d) = (c[3] / c[4], c[2] / c[4], c[1] / c[4], c[0] / c[4]);
+    let sq_a = a * a;
+    let p = -3. / 8. * sq_a + b;
+    let q = 1. / 8. * sq_a * a - 0.5 * a * b + cc;
+    let r = -3. / 256. * sq_a * sq_a + 1. / 16. * sq_a * b - 0.25 * a * cc + d;
+
+    if r.abs() < 1e-12 {
+        // y (y^3 + p y + q) = 0
+        let (cubic, n) = solve_cubic(&[q, p, 0., 1.]);
+        roots[..n].copy_from_slice(&cubic[..n]);
+        roots[n] = 0.;
+        count = n + 1;
+    } else {
+        // Any real root of the resolvent cubic splits the quartic into two quadratics
+        let (cubic, _) = solve_cubic(&[0.5 * r * p - 0.125 * q * q, -r, -0.5 * p, 1.]);
+        let z = cubic[0];
+
+        let u = z * z - r;
+        let v = 2. * z - p;
+        let (Some(u), Some(v)) = (clamped_sqrt(u), clamped_sqrt(v)) else {
+            return (roots, 0);
+        };
+        let v = if q < 0. { -v } else { v };
+
+        for (linear, constant) in [(v, z - u), (-v, z + u)] {
+            let disc = linear * linear / 4. - constant;
+            if disc >= 0. {
+                let s = disc.sqrt();
+                roots[count] = -linear / 2. + s;
+                roots[count + 1] = -linear / 2. - s;
+                count += 2;
+            }
+        }
+    }
+
+    for x in &mut roots[..count] {
+        *x -= 0.25 * a;
+
+        for _ in 0..2 {
+            let f = (((c[4] * *x + c[3]) * *x + c[2]) * *x + c[1]) * *x + c[0];
+            let df = ((4. * c[4] * *x + 3. * c[3]) * *x + 2. * c[2]) * *x + c[1];
+            if df != 0. {
+                *x -= f / df;
+            }
+        }
+    }
+
+    (roots, count)
+}
+
+// Treats values within rounding error of zero as zero, `None` for negative ones
+fn clamped_sqrt(x: f64) -> Option<f64> {
+    if x.abs() < 1e-12 {
+        Some(0.)
+    } else if x > 0. {
+        Some(x.sqrt())
+    } else {
+        None
+    }
+}
+
+// Real roots of c[0] + c[1] x + c[2] x^2 + c[3] x^3, at least one exists
+fn solve_cubic(c: &[f64; 4]) -> ([f64; 3], usize) {
+    let (a, b, cc) = (c[2] / c[3], c[1] / c[3], c[0] / c[3]);
+
+    // y^3 + 3 p y + 2 q = 0 with x = y - a / 3
+    let sq_a = a * a;
+    let p = 1. / 3. * (-1. / 3. * sq_a + b);
+    let q = 0.5 * (2. / 27. * a * sq_a - 1. / 3. * a * b + cc);
+    let cb_p = p * p * p;
+    let disc = q * q + cb_p;
+    let shift = a / 3.;
+
+    if disc.abs() < 1e-12 {
+        if q.abs() < 1e-12 {
+            ([-shift, 0., 0.], 1)
+        } else {
+            let u = (-q).cbrt();
+            ([2. * u - shift, -u - shift, 0.], 2)
+        }
+    } else if disc < 0. {
+        // Three real roots
+        let phi = 1. / 3. * (-q / (-cb_p).sqrt()).clamp(-1., 1.).acos();
+        let t = 2. * (-p).sqrt();
+        let third = std::f64::consts::PI / 3.;
+
+        (
+            [
+                t * phi.cos() - shift,
+                -t * (phi + third).cos() - shift,
+                -t * (phi - third).cos() - shift,
+            ],
+            3,
+        )
+    } else {
+        let s = disc.sqrt();
+        ([(s - q).cbrt() - (s + q).cbrt() - shift, 0., 0.], 1)
+    }
+}
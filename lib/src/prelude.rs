@@ -1,4 +1,12 @@
-pub use crate::camera::Camera;
+pub use crate::bvh::{Aabb, Bvh, BvhStats};
+pub use crate::camera::{ApertureMask, ApertureShape, Camera, Projection};
+pub use crate::export::PlyFormat;
+pub use crate::film::{linear_to_srgb, srgb_to_linear, Encoding, Film, PpmFormat, WriteOptions};
+pub use crate::import::{Model, ModelInstance, ModelMesh};
+pub use crate::light::{Light, LightHandle};
+pub use crate::material::{Material, MaterialHandle};
 pub use crate::object::*;
 pub use crate::quaternion::Quaternion;
-pub use crate::scene::Scene;
+pub use crate::ray::{Hit, Ray, TriangleHit};
+pub use crate::scene::{ImportedScene, Scene};
+pub use crate::transform::Transform;
@@ -1,9 +1,13 @@
 #![allow(dead_code)]
 
-use glm::{Mat3, Mat4, Vec3};
+#[cfg(test)]
+mod tests;
+
+use glm::{Mat3, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 use std::ops::{Mul, MulAssign};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Quaternion {
     i: f32,
     j: f32,
@@ -12,16 +16,40 @@ pub struct Quaternion {
 }
 
 impl Quaternion {
+    // No rotation
+    pub const IDENTITY: Self = Self {
+        i: 0.,
+        j: 0.,
+        k: 0.,
+        l: 1.,
+    };
+
+    // `i`, `j` and `k` are the vector part, `l` the scalar part
     pub fn new(i: f32, j: f32, k: f32, l: f32) -> Self {
         Self { i, j, k, l }
     }
 
     pub fn zero() -> Self {
-        Quaternion::from_euler(0.0, 0.0, 0.0)
+        Self::IDENTITY
     }
 
-    // pub fn from
+    pub fn i(&self) -> f32 {
+        self.i
+    }
+
+    pub fn j(&self) -> f32 {
+        self.j
+    }
+
+    pub fn k(&self) -> f32 {
+        self.k
+    }
+
+    pub fn l(&self) -> f32 {
+        self.l
+    }
 
+    // Rotation of `l` radians around the axis `v`
     pub fn from_two(l: f32, mut v: Vec3) -> Self {
         v = v.normalize();
 
@@ -38,6 +66,50 @@ impl Quaternion {
         ret
     }
 
+    // Inverse of `as_matrix3`, `mat` must be a rotation
+    pub fn from_matrix3(mat: &Mat3) -> Self {
+        let m = |r: usize, c: usize| mat[(r, c)];
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+
+        // Divide by the largest component to stay away from near zero divisors
+        let mut ret = if trace > 0. {
+            let s = (trace + 1.).sqrt() * 2.;
+            Self {
+                l: 0.25 * s,
+                i: (m(2, 1) - m(1, 2)) / s,
+                j: (m(0, 2) - m(2, 0)) / s,
+                k: (m(1, 0) - m(0, 1)) / s,
+            }
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1. + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.;
+            Self {
+                l: (m(2, 1) - m(1, 2)) / s,
+                i: 0.25 * s,
+                j: (m(0, 1) + m(1, 0)) / s,
+                k: (m(0, 2) + m(2, 0)) / s,
+            }
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1. + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.;
+            Self {
+                l: (m(0, 2) - m(2, 0)) / s,
+                i: (m(0, 1) + m(1, 0)) / s,
+                j: 0.25 * s,
+                k: (m(1, 2) + m(2, 1)) / s,
+            }
+        } else {
+            let s = (1. + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.;
+            Self {
+                l: (m(1, 0) - m(0, 1)) / s,
+                i: (m(0, 2) + m(2, 0)) / s,
+                j: (m(1, 2) + m(2, 1)) / s,
+                k: 0.25 * s,
+            }
+        };
+
+        ret.normalize();
+        ret
+    }
+
     pub fn as_matrix3(&self) -> Mat3 {
         let q0 = self.l;
         let q1 = self.i;
@@ -82,8 +154,120 @@ impl Quaternion {
         )
     }
 
+    // Inverse rotation for unit quaternions
+    pub fn conjugate(&self) -> Self {
+        Self {
+            i: -self.i,
+            j: -self.j,
+            k: -self.k,
+            l: self.l,
+        }
+    }
+
+    // Same as `conjugate` for unit quaternions, `None` for the zero quaternion
+    pub fn inverse(&self) -> Option<Self> {
+        let norm2 = self.dot(self);
+        if norm2 == 0. || !norm2.is_finite() {
+            return None;
+        }
+
+        let conjugate = self.conjugate();
+        Some(Self {
+            i: conjugate.i / norm2,
+            j: conjugate.j / norm2,
+            k: conjugate.k / norm2,
+            l: conjugate.l / norm2,
+        })
+    }
+
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.i * other.i + self.j * other.j + self.k * other.k + self.l * other.l
+    }
+
+    pub fn magnitude(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // Rotates `v` the way `as_matrix3` does, without building the matrix
+    pub fn rotate_vec3(&self, v: &Vec3) -> Vec3 {
+        let u = Vec3::new(self.i, self.j, self.k);
+        let t = 2. * u.cross(v);
+
+        v + self.l * t + u.cross(&t)
+    }
+
+    // Rotation turning -z towards `forward` with +y as close to `up` as it gets, which is how
+    // cameras are oriented. `None` if either is zero or they are parallel
+    pub fn look_rotation(forward: &Vec3, up: &Vec3) -> Option<Self> {
+        let back = -forward.try_normalize(f32::EPSILON)?;
+        let up = up.try_normalize(f32::EPSILON)?;
+
+        // Rounding leaves a tiny cross product for parallel vectors
+        let right = up.cross(&back).try_normalize(1e-5)?;
+        let up = back.cross(&right);
+
+        Some(Self::from_matrix3(&Mat3::from_columns(&[right, up, back])))
+    }
+
+    // Spherical interpolation along the shorter arc, `t` of 0 gives `self` and 1 gives `other`.
+    // Both must be unit quaternions
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        let (other, cos) = self.closest(other);
+
+        // Nearly the same rotation, the sine below would be close to zero
+        if cos > 0.9995 {
+            return self.nlerp(&other, t);
+        }
+
+        let angle = cos.acos();
+        let a = ((1. - t) * angle).sin() / angle.sin();
+        let b = (t * angle).sin() / angle.sin();
+
+        let mut ret = self.scaled(a).add(&other.scaled(b));
+        ret.normalize();
+        ret
+    }
+
+    // Normalized linear interpolation along the shorter arc. Cheaper than `slerp` but doesn't move
+    // at a constant angular speed
+    pub fn nlerp(&self, other: &Self, t: f32) -> Self {
+        let (other, _) = self.closest(other);
+
+        let mut ret = self.scaled(1. - t).add(&other.scaled(t));
+        ret.normalize();
+        ret
+    }
+
+    // `other` or its negation, whichever is closer to `self`. Both describe the same rotation
+    fn closest(&self, other: &Self) -> (Self, f32) {
+        let cos = self.dot(other);
+        if cos < 0. {
+            (other.scaled(-1.), -cos)
+        } else {
+            (*other, cos)
+        }
+    }
+
+    fn scaled(&self, s: f32) -> Self {
+        Self {
+            i: self.i * s,
+            j: self.j * s,
+            k: self.k * s,
+            l: self.l * s,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self {
+            i: self.i + other.i,
+            j: self.j + other.j,
+            k: self.k + other.k,
+            l: self.l + other.l,
+        }
+    }
+
     pub fn normalize(&mut self) -> &mut Self {
-        let mag = (self.i * self.i + self.j * self.j + self.k * self.k + self.l * self.l).sqrt();
+        let mag = self.magnitude();
         self.i /= mag;
         self.j /= mag;
         self.k /= mag;
@@ -91,6 +275,7 @@ impl Quaternion {
         self
     }
 
+    // Rotation around x by `roll`, then y by `pitch`, then z by `yaw`, in radians
     pub fn from_euler(roll: f32, pitch: f32, yaw: f32) -> Self {
         let cr = (roll / 2.0).cos();
         let sr = (roll / 2.0).sin();
@@ -109,6 +294,29 @@ impl Quaternion {
         ret.normalize();
         ret
     }
+
+    // Inverse of `from_euler`, returns (roll, pitch, yaw). Pitch is kept in [-pi/2, pi/2]
+    pub fn to_euler(&self) -> (f32, f32, f32) {
+        let (i, j, k, l) = (self.i, self.j, self.k, self.l);
+
+        let roll = (2. * (l * i + j * k)).atan2(1. - 2. * (i * i + j * j));
+        let pitch = (2. * (l * j - k * i)).clamp(-1., 1.).asin();
+        let yaw = (2. * (l * k + i * j)).atan2(1. - 2. * (j * j + k * k));
+
+        (roll, pitch, yaw)
+    }
+}
+
+impl From<Quat> for Quaternion {
+    fn from(q: Quat) -> Self {
+        Self::new(q.i, q.j, q.k, q.w)
+    }
+}
+
+impl From<Quaternion> for Quat {
+    fn from(q: Quaternion) -> Self {
+        Quat::new(q.l, q.i, q.j, q.k)
+    }
 }
 
 impl Mul for Quaternion {
@@ -0,0 +1,238 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use glm::{vec3, Mat3, Quat, Vec3};
+use proptest::prelude::*;
+
+use super::Quaternion;
+
+const EPS: f32 = 1e-4;
+
+fn unit_quaternion() -> impl Strategy<Value = Quaternion> {
+    prop::array::uniform4(-1f32..1.)
+        .prop_filter("too close to zero to normalize", |c| {
+            c.iter().map(|c| c * c).sum::<f32>() > 0.01
+        })
+        .prop_map(|[i, j, k, l]| *Quaternion::new(i, j, k, l).normalize())
+}
+
+fn vector() -> impl Strategy<Value = Vec3> {
+    prop::array::uniform3(-10f32..10.).prop_map(Vec3::from)
+}
+
+fn direction() -> impl Strategy<Value = Vec3> {
+    vector().prop_filter("too short to normalize", |v| v.norm() > 0.1)
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() <= EPS * a.abs().max(b.abs()).max(1.)
+}
+
+fn close_vec(a: &Vec3, b: &Vec3) -> bool {
+    (0..3).all(|i| close(a[i], b[i]))
+}
+
+fn close_mat(a: &Mat3, b: &Mat3) -> bool {
+    (0..9).all(|i| close(a[i], b[i]))
+}
+
+// q and -q are the same rotation
+fn same_rotation(a: &Quaternion, b: &Quaternion) -> bool {
+    close(a.dot(b).abs(), 1.)
+}
+
+proptest! {
+    #[test]
+    fn accessors_return_components(i in -1f32..1., j in -1f32..1., k in -1f32..1., l in -1f32..1.) {
+        let q = Quaternion::new(i, j, k, l);
+        prop_assert_eq!((q.i(), q.j(), q.k(), q.l()), (i, j, k, l));
+    }
+
+    #[test]
+    fn identity_leaves_vectors_unchanged(v in vector()) {
+        prop_assert_eq!(Quaternion::IDENTITY.rotate_vec3(&v), v);
+        prop_assert!(same_rotation(&Quaternion::zero(), &Quaternion::IDENTITY));
+    }
+
+    #[test]
+    fn normalize_gives_unit_length(q in unit_quaternion(), s in 0.1f32..10.) {
+        let mut scaled = q.scaled(s);
+        prop_assert!(close(scaled.normalize().magnitude(), 1.));
+    }
+
+    #[test]
+    fn matrix_is_a_rotation(q in unit_quaternion()) {
+        let m = q.as_matrix3();
+        prop_assert!(close_mat(&(m * m.transpose()), &Mat3::identity()));
+        prop_assert!(close(m.determinant(), 1.));
+    }
+
+    #[test]
+    fn rotate_vec3_matches_matrix(q in unit_quaternion(), v in vector()) {
+        prop_assert!(close_vec(&q.rotate_vec3(&v), &(q.as_matrix3() * v)));
+    }
+
+    #[test]
+    fn rotation_keeps_length(q in unit_quaternion(), v in vector()) {
+        prop_assert!(close(q.rotate_vec3(&v).norm(), v.norm()));
+    }
+
+    #[test]
+    fn as_matrix_is_transposed_matrix3(q in unit_quaternion()) {
+        let m = q.as_matrix();
+        let m3: Mat3 = m.fixed_view::<3, 3>(0, 0).into();
+        prop_assert!(close_mat(&m3, &q.as_matrix3().transpose()));
+        prop_assert_eq!(m[(3, 3)], 1.);
+    }
+
+    #[test]
+    fn product_composes_rotations(a in unit_quaternion(), b in unit_quaternion(), v in vector()) {
+        let composed = (a * b).rotate_vec3(&v);
+        prop_assert!(close_vec(&composed, &a.rotate_vec3(&b.rotate_vec3(&v))));
+        prop_assert!(close_mat(&(a * b).as_matrix3(), &(a.as_matrix3() * b.as_matrix3())));
+    }
+
+    #[test]
+    fn mul_assign_matches_mul(a in unit_quaternion(), b in unit_quaternion()) {
+        let mut c = a;
+        c *= b;
+        prop_assert!(same_rotation(&c, &(a * b)));
+    }
+
+    #[test]
+    fn conjugate_undoes_rotation(q in unit_quaternion(), v in vector()) {
+        prop_assert!(same_rotation(&(q * q.conjugate()), &Quaternion::IDENTITY));
+        prop_assert!(close_vec(&q.conjugate().rotate_vec3(&q.rotate_vec3(&v)), &v));
+    }
+
+    #[test]
+    fn inverse_of_any_length(q in unit_quaternion(), s in 0.1f32..10.) {
+        let scaled = q.scaled(s);
+        let product = scaled * scaled.inverse().unwrap();
+        prop_assert!(close(product.l(), 1.));
+        prop_assert!(close(product.i(), 0.) && close(product.j(), 0.) && close(product.k(), 0.));
+    }
+
+    #[test]
+    fn inverse_of_unit_is_conjugate(q in unit_quaternion()) {
+        let inverse = q.inverse().unwrap();
+        prop_assert!(close(inverse.dot(&q.conjugate()), 1.));
+    }
+
+    #[test]
+    fn matrix_round_trip(q in unit_quaternion()) {
+        prop_assert!(same_rotation(&Quaternion::from_matrix3(&q.as_matrix3()), &q));
+    }
+
+    #[test]
+    fn axis_angle_rotates_around_axis(axis in direction(), angle in -PI..PI, v in vector()) {
+        let q = Quaternion::from_two(angle, axis);
+        let axis = axis.normalize();
+
+        // The axis stays put and everything else turns by the angle around it
+        prop_assert!(close_vec(&q.rotate_vec3(&axis), &axis));
+        let rotated = q.rotate_vec3(&v);
+        prop_assert!(close(rotated.dot(&axis), v.dot(&axis)));
+        prop_assert!(close_mat(
+            &q.as_matrix3(),
+            &glm::rotation(angle, &axis).fixed_view::<3, 3>(0, 0).into()
+        ));
+    }
+
+    #[test]
+    fn euler_composes_axis_rotations(roll in -PI..PI, pitch in -PI..PI, yaw in -PI..PI) {
+        let composed = Quaternion::from_two(yaw, Vec3::z())
+            * Quaternion::from_two(pitch, Vec3::y())
+            * Quaternion::from_two(roll, Vec3::x());
+        prop_assert!(same_rotation(&Quaternion::from_euler(roll, pitch, yaw), &composed));
+    }
+
+    #[test]
+    fn euler_round_trip(roll in -3.1f32..3.1, pitch in -1.5f32..1.5, yaw in -3.1f32..3.1) {
+        let (r, p, y) = Quaternion::from_euler(roll, pitch, yaw).to_euler();
+        prop_assert!(close(r, roll) && close(p, pitch) && close(y, yaw), "{:?}", (r, p, y));
+    }
+
+    #[test]
+    fn to_euler_describes_the_rotation(q in unit_quaternion()) {
+        let (roll, pitch, yaw) = q.to_euler();
+        prop_assert!((-FRAC_PI_2..=FRAC_PI_2).contains(&pitch));
+
+        // Near gimbal lock the angles lose precision, compare what they do to vectors instead
+        let back = Quaternion::from_euler(roll, pitch, yaw);
+        prop_assert!((back.as_matrix3() - q.as_matrix3()).abs().max() < 1e-2);
+    }
+
+    #[test]
+    fn slerp_hits_endpoints(a in unit_quaternion(), b in unit_quaternion()) {
+        prop_assert!(same_rotation(&a.slerp(&b, 0.), &a));
+        prop_assert!(same_rotation(&a.slerp(&b, 1.), &b));
+    }
+
+    #[test]
+    fn slerp_moves_at_constant_speed(a in unit_quaternion(), b in unit_quaternion(), t in 0f32..1.) {
+        // Angle between rotations along the shorter arc
+        let angle = |p: &Quaternion, q: &Quaternion| p.dot(q).abs().min(1.).acos();
+
+        let total = angle(&a, &b);
+        prop_assume!(total > 0.05);
+
+        let c = a.slerp(&b, t);
+        prop_assert!((angle(&a, &c) - t * total).abs() < 1e-3);
+        prop_assert!((angle(&c, &b) - (1. - t) * total).abs() < 1e-3);
+    }
+
+    #[test]
+    fn slerp_takes_shorter_arc(a in unit_quaternion(), b in unit_quaternion(), t in 0f32..1.) {
+        // -b is the same rotation, so it must give the same path
+        prop_assert!(same_rotation(&a.slerp(&b, t), &a.slerp(&b.scaled(-1.), t)));
+    }
+
+    #[test]
+    fn nlerp_stays_on_arc(a in unit_quaternion(), b in unit_quaternion(), t in 0f32..1.) {
+        prop_assert!(same_rotation(&a.nlerp(&b, 0.), &a));
+        prop_assert!(same_rotation(&a.nlerp(&b, 1.), &b));
+
+        let c = a.nlerp(&b, t);
+        prop_assert!(close(c.magnitude(), 1.));
+
+        // Between the endpoints, so never further from either than they are from each other
+        let cos = a.dot(&b).abs();
+        prop_assert!(c.dot(&a).abs() >= cos - EPS && c.dot(&b).abs() >= cos - EPS);
+    }
+
+    #[test]
+    fn look_rotation_faces_forward(forward in direction(), up in direction()) {
+        prop_assume!(forward.normalize().cross(&up.normalize()).norm() > 0.1);
+
+        let q = Quaternion::look_rotation(&forward, &up).unwrap();
+        prop_assert!(close_vec(&q.rotate_vec3(&vec3(0., 0., -1.)), &forward.normalize()));
+
+        // Up stays in the plane of forward and up, on the same side as up
+        let new_up = q.rotate_vec3(&Vec3::y());
+        prop_assert!(new_up.dot(&up) > 0.);
+        prop_assert!(new_up.dot(&forward.cross(&up).normalize()).abs() < EPS);
+    }
+
+    #[test]
+    fn look_rotation_rejects_degenerate(v in direction(), s in -10f32..10.) {
+        prop_assert!(Quaternion::look_rotation(&Vec3::zeros(), &v).is_none());
+        prop_assert!(Quaternion::look_rotation(&v, &(v * s)).is_none());
+    }
+
+    #[test]
+    fn glm_round_trip(q in unit_quaternion()) {
+        let back = Quaternion::from(Quat::from(q));
+        prop_assert_eq!((back.i(), back.j(), back.k(), back.l()), (q.i(), q.j(), q.k(), q.l()));
+    }
+
+    #[test]
+    fn glm_rotates_the_same(q in unit_quaternion(), v in vector()) {
+        let rotated = glm::quat_rotate_vec3(&Quat::from(q), &v);
+        prop_assert!(close_vec(&rotated, &q.rotate_vec3(&v)));
+    }
+}
+
+#[test]
+fn inverse_of_zero_is_none() {
+    assert!(Quaternion::new(0., 0., 0., 0.).inverse().is_none());
+}
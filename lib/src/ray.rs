@@ -0,0 +1,232 @@
+#[cfg(test)]
+mod tests;
+
+use glm::{vec2, vec4, Mat4, Vec2, Vec3};
+
+use crate::object::{ObjectType, Shape};
+use crate::{Aabb, Object, ObjectHandle, ObjectManager};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    // Not required to be normalized, distances are measured in multiples of its length
+    pub dir: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub t: f32,
+    pub position: Vec3,
+    // World space geometric normal, unit length and facing out of the surface (not towards the ray)
+    pub normal: Vec3,
+    // Weights of the second and third triangle vertices for mesh hits. Analytic shapes store the
+    // texture coordinates of their tessellation instead
+    pub barycentrics: Vec2,
+    pub object: ObjectHandle,
+    // Triangle index into the object type's mesh, `None` for analytic shapes
+    pub triangle: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub t: f32,
+    // Weights of `v1` and `v2`, the weight of `v0` is `1 - u - v`
+    pub u: f32,
+    pub v: f32,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, dir: Vec3) -> Self {
+        Self { origin, dir }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.dir * t
+    }
+
+    pub fn transform(&self, mat: &Mat4) -> Self {
+        let o = self.origin;
+        let d = self.dir;
+
+        Self {
+            origin: (mat * vec4(o.x, o.y, o.z, 1.)).xyz(),
+            dir: (mat * vec4(d.x, d.y, d.z, 0.)).xyz(),
+        }
+    }
+
+    // Watertight ray/triangle intersection (Woop, Benthin and Wald 2013). Rays passing exactly
+    // through a shared edge or vertex always hit at least one of the triangles sharing it
+    pub fn intersect_triangle(
+        &self,
+        v0: &Vec3,
+        v1: &Vec3,
+        v2: &Vec3,
+        max_t: f32,
+    ) -> Option<TriangleHit> {
+        let dir = self.dir;
+
+        // Axis where the direction is largest becomes z, winding is kept by swapping x and y
+        let kz = dir.iamax();
+        let mut kx = (kz + 1) % 3;
+        let mut ky = (kx + 1) % 3;
+        if dir[kz] < 0. {
+            std::mem::swap(&mut kx, &mut ky);
+        }
+
+        if dir[kz] == 0. {
+            return None;
+        }
+
+        // Shear and scale so the ray runs along +z from the origin
+        let sx = dir[kx] / dir[kz];
+        let sy = dir[ky] / dir[kz];
+        let sz = 1. / dir[kz];
+
+        let a = v0 - self.origin;
+        let b = v1 - self.origin;
+        let c = v2 - self.origin;
+
+        let ax = a[kx] - sx * a[kz];
+        let ay = a[ky] - sy * a[kz];
+        let bx = b[kx] - sx * b[kz];
+        let by = b[ky] - sy * b[kz];
+        let cx = c[kx] - sx * c[kz];
+        let cy = c[ky] - sy * c[kz];
+
+        let mut u = cx * by - cy * bx;
+        let mut v = ax * cy - ay * cx;
+        let mut w = bx * ay - by * ax;
+
+        // Fall back to double precision on edges so neighbouring triangles agree on the result
+        if u == 0. || v == 0. || w == 0. {
+            u = (cx as f64 * by as f64 - cy as f64 * bx as f64) as f32;
+            v = (ax as f64 * cy as f64 - ay as f64 * cx as f64) as f32;
+            w = (bx as f64 * ay as f64 - by as f64 * ax as f64) as f32;
+        }
+
+        if (u < 0. || v < 0. || w < 0.) && (u > 0. || v > 0. || w > 0.) {
+            return None;
+        }
+
+        let det = u + v + w;
+        if det == 0. {
+            return None;
+        }
+
+        let az = sz * a[kz];
+        let bz = sz * b[kz];
+        let cz = sz * c[kz];
+        let t_scaled = u * az + v * bz + w * cz;
+
+        // Range check before the division, `t_scaled / det` must lie in (0, max_t)
+        if det < 0. && (t_scaled >= 0. || t_scaled < max_t * det) {
+            return None;
+        }
+        if det > 0. && (t_scaled <= 0. || t_scaled > max_t * det) {
+            return None;
+        }
+
+        let inv_det = 1. / det;
+
+        Some(TriangleHit {
+            t: t_scaled * inv_det,
+            u: v * inv_det,
+            v: w * inv_det,
+        })
+    }
+
+    // Returns the nearest distance in (0, max_t) where the ray enters or leaves the sphere
+    pub fn intersect_sphere(&self, center: &Vec3, radius: f32, max_t: f32) -> Option<f32> {
+        let oc = self.origin - center;
+        let a = self.dir.norm_squared();
+        let half_b = oc.dot(&self.dir);
+        let c = oc.norm_squared() - radius * radius;
+
+        let disc = half_b * half_b - a * c;
+        if disc < 0. || a == 0. {
+            return None;
+        }
+
+        // Numerically stable form, avoids cancellation when one root is close to zero
+        let q = -(half_b + disc.sqrt().copysign(half_b));
+        let (t0, t1) = if q == 0. {
+            (0., 0.)
+        } else {
+            let (r0, r1) = (q / a, c / q);
+            (r0.min(r1), r0.max(r1))
+        };
+
+        [t0, t1].into_iter().find(|t| *t > 0. && *t < max_t)
+    }
+}
+
+// An object with its transforms cached, ready to be intersected many times
+pub(crate) struct Instance<'a> {
+    pub(crate) object: &'a Object,
+    pub(crate) mesh: &'a ObjectType,
+    pub(crate) handle: ObjectHandle,
+    to_local: Mat4,
+}
+
+impl<'a> Instance<'a> {
+    // `to_local` comes from `placement`, worked out once per object placement
+    pub(crate) fn new(
+        object: &'a Object,
+        handle: ObjectHandle,
+        to_local: Mat4,
+        meshes: &'a ObjectManager,
+    ) -> Self {
+        Self {
+            object,
+            mesh: meshes.from_id(object.object_type),
+            handle,
+            to_local,
+        }
+    }
+
+    // World to object transform and world space bounds, what the top level BVH is built over, of
+    // an object of type `mesh` placed by `to_world`
+    pub(crate) fn placement(mesh: &ObjectType, to_world: &Mat4) -> (Mat4, Aabb) {
+        let to_local = to_world.try_inverse().unwrap_or_else(Mat4::zeros);
+        (to_local, mesh.local_bounds().transform(to_world))
+    }
+
+    pub(crate) fn intersect(&self, ray: &Ray, max_t: f32) -> Option<Hit> {
+        // The local ray keeps the world ray's parameterization, so distances carry over as is
+        let local = ray.transform(&self.to_local);
+
+        let (t, local_normal, barycentrics, triangle) = match self.mesh.shape {
+            Shape::Mesh => {
+                let mut closest = None;
+
+                self.mesh.blas().traverse(&local, max_t, |i, max_t| {
+                    let [v0, v1, v2] = self.mesh.triangle(i);
+                    let hit = local.intersect_triangle(&v0, &v1, &v2, max_t)?;
+
+                    let normal = (v1 - v0).cross(&(v2 - v0));
+                    closest = Some((hit.t, normal, vec2(hit.u, hit.v), Some(i)));
+                    Some(hit.t)
+                });
+
+                closest?
+            }
+            shape => {
+                let hit = shape.intersect(&local, max_t)?;
+                (hit.t, hit.normal, hit.uv, None)
+            }
+        };
+
+        // Normals transform with the inverse transpose of the model matrix
+        let n =
+            self.to_local.transpose() * vec4(local_normal.x, local_normal.y, local_normal.z, 0.);
+
+        Some(Hit {
+            t,
+            position: ray.at(t),
+            normal: n.xyz().normalize(),
+            barycentrics,
+            object: self.handle,
+            triangle,
+        })
+    }
+}
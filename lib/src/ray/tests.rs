@@ -0,0 +1,214 @@
+use std::f32::consts::TAU;
+
+use glm::{vec3, DVec3, Vec3};
+use proptest::prelude::*;
+
+use super::Ray;
+
+fn vector(range: f32) -> impl Strategy<Value = Vec3> {
+    prop::array::uniform3(-range..range).prop_map(Vec3::from)
+}
+
+fn direction() -> impl Strategy<Value = Vec3> {
+    vector(1.).prop_filter("too short to normalize", |v| v.norm() > 0.1)
+}
+
+// Triangles around `center` in its xy plane, their ring is irregular so no edge lines up with
+// an axis
+fn fan(center: Vec3, radii: &[f32]) -> Vec<[Vec3; 3]> {
+    let ring: Vec<Vec3> = radii
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let angle = (i as f32 + 0.3 * (i % 3) as f32) / radii.len() as f32 * TAU;
+            center + vec3(angle.cos(), angle.sin(), 0.) * *r
+        })
+        .collect();
+
+    (0..ring.len())
+        .map(|i| [center, ring[i], ring[(i + 1) % ring.len()]])
+        .collect()
+}
+
+fn hits_any(ray: &Ray, triangles: &[[Vec3; 3]]) -> bool {
+    triangles
+        .iter()
+        .any(|[a, b, c]| ray.intersect_triangle(a, b, c, f32::INFINITY).is_some())
+}
+
+// Roots of the sphere intersection worked out in double precision, nearest first
+fn reference_roots(ray: &Ray, center: &Vec3, radius: f32) -> Option<(f64, f64)> {
+    let origin: DVec3 = ray.origin.cast();
+    let dir: DVec3 = ray.dir.cast();
+    let oc = origin - center.cast();
+
+    let a = dir.norm_squared();
+    let half_b = oc.dot(&dir);
+    let c = oc.norm_squared() - radius as f64 * radius as f64;
+    let disc = half_b * half_b - a * c;
+
+    (disc >= 0.).then(|| ((-half_b - disc.sqrt()) / a, (-half_b + disc.sqrt()) / a))
+}
+
+proptest! {
+    #[test]
+    fn rays_through_a_fan_center_hit(
+        center in vector(2.),
+        radii in prop::collection::vec(0.5f32..2., 3..9),
+        from in vector(5.),
+        flip in any::<bool>(),
+    ) {
+        // From either side of the plane, seen from below every triangle is back facing
+        let height = if flip { -(1. + from.z.abs()) } else { 1. + from.z.abs() };
+        let origin = vec3(from.x, from.y, center.z + height);
+
+        let ray = Ray::new(origin, center - origin);
+        prop_assert!(hits_any(&ray, &fan(center, &radii)));
+    }
+
+    #[test]
+    fn rays_through_shared_edges_hit(
+        center in vector(2.),
+        radii in prop::collection::vec(0.5f32..2., 3..9),
+        edge in any::<prop::sample::Index>(),
+        along in 0f32..1.,
+        from in vector(5.),
+        flip in any::<bool>(),
+    ) {
+        let triangles = fan(center, &radii);
+        let height = if flip { -(1. + from.z.abs()) } else { 1. + from.z.abs() };
+        let origin = vec3(from.x, from.y, center.z + height);
+
+        // Edges from the center to the ring are shared by two triangles
+        let [center, spoke, _] = triangles[edge.index(triangles.len())];
+        let target = center + (spoke - center) * along;
+
+        let ray = Ray::new(origin, target - origin);
+        prop_assert!(hits_any(&ray, &triangles));
+    }
+
+    #[test]
+    fn rays_through_a_quad_diagonal_hit(
+        a in vector(3.),
+        c in vector(3.),
+        across in vector(1.),
+        corners in prop::array::uniform4(0.1f32..1.),
+        along in 0f32..1.,
+        origin in vector(10.),
+    ) {
+        // The other two corners lie on either side of the diagonal from `a` to `c`
+        let diagonal = c - a;
+        prop_assume!(diagonal.norm() > 0.1);
+        let across = across - diagonal * (diagonal.dot(&across) / diagonal.norm_squared());
+        prop_assume!(across.norm() > 0.1);
+
+        let [b_along, b_out, d_along, d_out] = corners;
+        let b = a + diagonal * b_along + across * b_out;
+        let d = a + diagonal * d_along - across * d_out;
+
+        let normal = diagonal.cross(&across).normalize();
+        prop_assume!(normal.dot(&(origin - a)).abs() > 0.1);
+
+        let target = a + diagonal * along;
+        let ray = Ray::new(origin, target - origin);
+        prop_assert!(hits_any(&ray, &[[a, b, c], [a, c, d]]));
+    }
+
+    #[test]
+    fn sphere_matches_double_precision(
+        center in vector(100.),
+        radius in 0.01f32..50.,
+        origin in vector(100.),
+        dir in direction(),
+    ) {
+        let ray = Ray::new(origin, dir);
+        let expected = reference_roots(&ray, &center, radius)
+            .and_then(|(t0, t1)| [t0, t1].into_iter().find(|t| *t > 0.));
+
+        // Roots this close to zero or to grazing may fall either way in single precision
+        let roots = reference_roots(&ray, &center, radius);
+        if let Some((t0, t1)) = roots {
+            prop_assume!(t0.abs() > 1e-2 && t1.abs() > 1e-2 && t1 - t0 > 1e-2);
+        }
+
+        let t = ray.intersect_sphere(&center, radius, f32::INFINITY);
+        match (t, expected) {
+            (Some(t), Some(expected)) => {
+                let error = (t as f64 - expected).abs();
+                prop_assert!(error <= 1e-3 * expected.max(1.), "{} != {}", t, expected);
+            }
+            (t, expected) => prop_assert_eq!(t.is_some(), expected.is_some()),
+        }
+    }
+
+    #[test]
+    fn rays_leaving_a_sphere_surface(
+        center in vector(10.),
+        radius in 0.1f32..10.,
+        normal in direction(),
+        dir in direction(),
+    ) {
+        let normal = normal.normalize();
+        let cos = dir.normalize().dot(&normal);
+        prop_assume!(cos.abs() > 0.05);
+
+        let origin = center + normal * radius;
+        let t = Ray::new(origin, dir).intersect_sphere(&center, radius, f32::INFINITY);
+
+        // Where the ray starts rounds to either side of the surface, so it may hit right there
+        let at_origin = |t: f32| t < 1e-4 * radius;
+        if cos > 0. {
+            prop_assert!(t.is_none_or(at_origin), "{:?}", t);
+        } else {
+            let chord = 2. * radius * -cos / dir.norm();
+            let t = t.unwrap();
+            prop_assert!(at_origin(t) || (t - chord).abs() < 1e-3 * chord, "{} != {}", t, chord);
+        }
+    }
+}
+
+#[test]
+fn tangent_rays_touch_the_sphere() {
+    let center = vec3(1., 2., 3.);
+
+    let ray = Ray::new(vec3(-4., 3., 3.), vec3(1., 0., 0.));
+    let t = ray.intersect_sphere(&center, 1., f32::INFINITY).unwrap();
+    assert!((t - 5.).abs() < 1e-3);
+
+    let ray = Ray::new(vec3(-4., 3.01, 3.), vec3(1., 0., 0.));
+    assert_eq!(ray.intersect_sphere(&center, 1., f32::INFINITY), None);
+}
+
+#[test]
+fn rays_from_the_surface_find_the_far_side() {
+    let center = Vec3::zeros();
+    let inward = Ray::new(vec3(2., 0., 0.), vec3(-1., 0., 0.));
+    let t = inward.intersect_sphere(&center, 2., f32::INFINITY);
+    assert_eq!(t, Some(4.));
+    assert_eq!(inward.intersect_sphere(&center, 2., 3.), None);
+
+    let outward = Ray::new(vec3(2., 0., 0.), vec3(1., 0., 0.));
+    assert_eq!(outward.intersect_sphere(&center, 2., f32::INFINITY), None);
+
+    // From the center only the way out is ahead
+    let inside = Ray::new(center, vec3(0., 0., 1.));
+    let t = inside.intersect_sphere(&center, 2., f32::INFINITY);
+    assert_eq!(t, Some(2.));
+}
+
+#[test]
+fn shared_vertex_of_an_axis_aligned_quad() {
+    let [a, b, c, d] = [
+        vec3(0., 0., 0.),
+        vec3(1., 0., 0.),
+        vec3(1., 1., 0.),
+        vec3(0., 1., 0.),
+    ];
+    let triangles = [[a, b, c], [a, c, d]];
+
+    // Straight down the diagonal's corners and its midpoint
+    for target in [a, c, (a + c) / 2.] {
+        let ray = Ray::new(target + vec3(0., 0., 1.), vec3(0., 0., -1.));
+        assert!(hits_any(&ray, &triangles), "missed {:?}", target);
+    }
+}
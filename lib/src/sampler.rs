@@ -0,0 +1,86 @@
+use std::f32::consts::PI;
+
+use glm::{vec2, vec3, Vec2, Vec3};
+
+// Small PCG32 generator, seeded per pixel so renders are reproducible regardless of threading
+pub(crate) struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(6364136223846793005).wrapping_add(self.inc);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Uniform float in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1 << 24) as f32)
+    }
+}
+
+// Orthonormal (tangent, bitangent) pair around a unit normal (Duff et al. 2017)
+pub(crate) fn orthonormal_basis(normal: &Vec3) -> (Vec3, Vec3) {
+    let sign = 1_f32.copysign(normal.z);
+    let a = -1. / (sign + normal.z);
+    let b = normal.x * normal.y * a;
+
+    let tangent = vec3(
+        1. + sign * normal.x * normal.x * a,
+        sign * b,
+        -sign * normal.x,
+    );
+    let bitangent = vec3(b, sign + normal.y * normal.y * a, -normal.y);
+
+    (tangent, bitangent)
+}
+
+// Uniform point on the unit disk, concentric mapping (Shirley and Chiu 1997)
+pub(crate) fn sample_disk(rng: &mut Rng) -> Vec2 {
+    let u = vec2(rng.next_f32(), rng.next_f32()) * 2. - vec2(1., 1.);
+
+    if u.x == 0. && u.y == 0. {
+        return Vec2::zeros();
+    }
+
+    let (r, theta) = if u.x.abs() > u.y.abs() {
+        (u.x, PI / 4. * (u.y / u.x))
+    } else {
+        (u.y, PI / 2. - PI / 4. * (u.x / u.y))
+    };
+
+    vec2(r * theta.cos(), r * theta.sin())
+}
+
+pub(crate) fn sample_cosine_hemisphere(normal: &Vec3, rng: &mut Rng) -> Vec3 {
+    let d = sample_disk(rng);
+    let z = (1. - d.norm_squared()).max(0.).sqrt();
+    let (tangent, bitangent) = orthonormal_basis(normal);
+
+    (tangent * d.x + bitangent * d.y + normal * z).normalize()
+}
+
+pub(crate) fn sample_unit_sphere(rng: &mut Rng) -> Vec3 {
+    let z = 1. - 2. * rng.next_f32();
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * rng.next_f32();
+
+    vec3(r * phi.cos(), r * phi.sin(), z)
+}
@@ -1,9 +1,24 @@
+mod acceleration;
+mod file;
+mod hierarchy;
+mod mitsuba;
+mod pbrt;
+
 use glfw::WindowEvent;
-use glm::{Vec3, Vec4};
+use glm::{vec3, Vec3};
 
-use crate::{Camera, Object, Quaternion};
+use crate::{
+    tracer, BvhStats, Camera, Film, Hit, Light, LightHandle, Material, MaterialHandle, Object,
+    ObjectHandle, ObjectManager, Projection, Quaternion, Ray, Transform,
+};
+use acceleration::TopLevel;
+pub(crate) use hierarchy::WorldTransforms;
 use std::{
-    sync::{Arc, Mutex},
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
@@ -16,26 +31,73 @@ macro_rules! function {
     };
 }
 
+// Shared by every scene, so versions of different scenes never compare equal and a window switched
+// to another scene doesn't take what it drew before as up to date
+static VERSION: AtomicU64 = AtomicU64::new(1);
+
+fn next_version() -> u64 {
+    VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+// The generation is bumped every time the slot is emptied, so handles to the removed object
+// stop resolving
+struct ObjectSlot {
+    generation: u32,
+    object: Option<Object>,
+}
+
+// A scene read from another renderer's file format, with the settings the tracer leaves to the
+// caller and what the file asks for that the tracer can't do
+pub struct ImportedScene {
+    pub scene: Scene,
+    // Film resolution
+    pub width: usize,
+    pub height: usize,
+    // Samples per pixel, if the file sets them
+    pub samples: Option<u32>,
+    // Skipped elements and parameters, as "file:line: message"
+    pub warnings: Vec<String>,
+}
+
 // Scenes are not marked as dirty because they need window specific information. If there are
 // multiple windows open on one scene, the scene will be updated twice
 pub struct Scene {
-    pub(crate) objects: Vec<Object>,
+    // Indexed by `ObjectHandle::index`, see `ObjectSlot`
+    objects: Vec<ObjectSlot>,
+    // Empty slots in `objects`, reused before the list grows
+    free_objects: Vec<u32>,
+    // Indexed by `MaterialHandle`, the first entry is the default material
+    pub(crate) materials: Vec<Material>,
+    // Changes whenever a material may have been edited
+    material_version: u64,
+    // Indexed by `LightHandle`, removed lights leave an empty slot so handles stay valid
+    pub(crate) lights: Vec<Option<Light>>,
     pub camera: Camera,
     pub on_update: function!(Duration),
     pub on_event: function!(WindowEvent),
     pub(crate) clear_color: (f32, f32, f32, f32),
     pub(crate) clear_color_dirty: bool,
+    // Top level BVH over the live objects, brought up to date whenever the scene is traced
+    acceleration: RefCell<TopLevel>,
+    // Cached world matrices of the objects, anything handing out `&mut Object` marks them stale
+    world: RefCell<WorldTransforms>,
 }
 
 impl Scene {
     pub fn new(aspect: f32) -> Scene {
         Scene {
             objects: Vec::new(),
+            free_objects: Vec::new(),
+            materials: vec![Material::default()],
+            material_version: 0,
+            lights: Vec::new(),
             camera: Camera::new(aspect),
             on_update: None,
             on_event: None,
             clear_color: (0., 0., 0., 0.),
             clear_color_dirty: false,
+            acceleration: RefCell::default(),
+            world: RefCell::default(),
         }
     }
 
@@ -45,21 +107,237 @@ impl Scene {
         position: Vec3,
         scale: Vec3,
         rotation: Quaternion,
-        color: Vec4,
-    ) -> usize {
-        let index = self.objects.len();
-        self.objects.push(Object {
+        material: MaterialHandle,
+    ) -> ObjectHandle {
+        let object = Object {
             object_type: id,
-            position,
-            scale,
-            orientation: rotation,
-            color,
+            transform: Transform::new(position, rotation, scale),
+            material,
+            parent: None,
+        };
+
+        if let Some(index) = self.free_objects.pop() {
+            let slot = &mut self.objects[index as usize];
+            slot.object = Some(object);
+            self.world.get_mut().mark(index as usize);
+
+            return ObjectHandle {
+                index,
+                generation: slot.generation,
+            };
+        }
+
+        self.objects.push(ObjectSlot {
+            generation: 0,
+            object: Some(object),
         });
-        index
+        self.world.get_mut().mark(self.objects.len() - 1);
+
+        ObjectHandle {
+            index: self.objects.len() as u32 - 1,
+            generation: 0,
+        }
+    }
+
+    // Children of the removed object are attached to its parent, keeping their place in the world
+    pub fn remove(&mut self, handle: ObjectHandle) -> Option<Object> {
+        let transform = self.get(handle)?.transform;
+        self.detach_children(handle, &transform);
+
+        let slot = &mut self.objects[handle.index as usize];
+        let object = slot.object.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_objects.push(handle.index);
+        self.world.get_mut().mark(handle.index as usize);
+
+        Some(object)
+    }
+
+    pub fn get(&self, handle: ObjectHandle) -> Option<&Object> {
+        let slot = self.objects.get(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        slot.object.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: ObjectHandle) -> Option<&mut Object> {
+        let slot = self.objects.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        self.world.get_mut().mark(handle.index as usize);
+        slot.object.as_mut()
+    }
+
+    pub fn contains(&self, handle: ObjectHandle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectHandle, &Object)> {
+        self.objects.iter().enumerate().filter_map(|(i, slot)| {
+            let handle = ObjectHandle {
+                index: i as u32,
+                generation: slot.generation,
+            };
+            Some((handle, slot.object.as_ref()?))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ObjectHandle, &mut Object)> {
+        self.world.get_mut().mark_all();
+        self.objects.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let handle = ObjectHandle {
+                index: i as u32,
+                generation: slot.generation,
+            };
+            Some((handle, slot.object.as_mut()?))
+        })
+    }
+
+    // Number of objects in the scene, removed objects are not counted
+    pub fn object_count(&self) -> usize {
+        self.objects.len() - self.free_objects.len()
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialHandle {
+        self.materials.push(material);
+        MaterialHandle(self.materials.len() - 1)
+    }
+
+    pub fn material(&self, handle: MaterialHandle) -> Option<&Material> {
+        self.materials.get(handle.0)
+    }
+
+    pub fn material_mut(&mut self, handle: MaterialHandle) -> Option<&mut Material> {
+        self.material_version = next_version();
+        self.materials.get_mut(handle.0)
+    }
+
+    pub(crate) fn material_version(&self) -> u64 {
+        self.material_version
+    }
+
+    pub fn add_light(&mut self, light: Light) -> LightHandle {
+        self.lights.push(Some(light));
+        LightHandle(self.lights.len() - 1)
+    }
+
+    pub fn remove_light(&mut self, handle: LightHandle) -> Option<Light> {
+        self.lights.get_mut(handle.0)?.take()
+    }
+
+    pub fn light(&self, handle: LightHandle) -> Option<&Light> {
+        self.lights.get(handle.0)?.as_ref()
+    }
+
+    pub fn light_mut(&mut self, handle: LightHandle) -> Option<&mut Light> {
+        self.lights.get_mut(handle.0)?.as_mut()
+    }
+
+    pub fn lights(&self) -> impl Iterator<Item = (LightHandle, &Light)> {
+        self.lights
+            .iter()
+            .enumerate()
+            .filter_map(|(i, light)| Some((LightHandle(i), light.as_ref()?)))
+    }
+
+    pub(crate) fn resolve_material(&self, handle: MaterialHandle) -> &Material {
+        handle.resolve(&self.materials)
     }
 
     pub fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
         self.clear_color = (red, green, blue, alpha);
         self.clear_color_dirty = true;
     }
+
+    // Path traces the scene on the CPU. `objects` must be the manager the scene's object types were
+    // registered with. Light comes from the scene's lights, emissive materials and the clear color
+    // surrounding the scene
+    pub fn render_to_image(
+        &self,
+        objects: &ObjectManager,
+        width: usize,
+        height: usize,
+        spp: u32,
+    ) -> Film {
+        tracer::render(self, objects, width, height, spp)
+    }
+
+    // Closest object hit by the ray within `max_t` (in multiples of `dir`'s length). `meshes` must
+    // be the manager the scene's object types were registered with, e.g. `Window::object_manager`
+    pub fn raycast(
+        &self,
+        meshes: &ObjectManager,
+        origin: Vec3,
+        dir: Vec3,
+        max_t: f32,
+    ) -> Option<Hit> {
+        let ray = Ray::new(origin, dir);
+        let top = self.acceleration(meshes);
+
+        let mut closest = None;
+        top.bvh.traverse(&ray, max_t, |i, max_t| {
+            let hit = top.instance(self, meshes, i).intersect(&ray, max_t)?;
+            closest = Some(hit);
+            Some(hit.t)
+        });
+
+        closest
+    }
+
+    // Every object hit by the ray within `max_t`, nearest first. Only the closest hit on each
+    // object is reported
+    pub fn raycast_all(
+        &self,
+        meshes: &ObjectManager,
+        origin: Vec3,
+        dir: Vec3,
+        max_t: f32,
+    ) -> Vec<Hit> {
+        let ray = Ray::new(origin, dir);
+        let top = self.acceleration(meshes);
+
+        let mut hits = Vec::new();
+        top.bvh.traverse(&ray, max_t, |i, max_t| {
+            hits.extend(top.instance(self, meshes, i).intersect(&ray, max_t));
+            // Never shrink the search distance, objects behind this one are wanted too
+            None
+        });
+
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        hits
+    }
+
+    // Focuses the camera on whatever is under the image center and returns the new focus
+    // distance. The camera is left as it is if nothing is there
+    pub fn autofocus(&mut self, meshes: &ObjectManager) -> Option<f32> {
+        let ray = self.camera.ray(0.5, 0.5);
+        let hit = self.raycast(meshes, ray.origin, ray.dir, f32::INFINITY)?;
+
+        let offset = hit.position - self.camera.position;
+        let distance = match self.camera.projection() {
+            // Measured along the view direction, the focus distance is to a plane
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                let forward = self.camera.orientation.rotate_vec3(&vec3(0., 0., -1.));
+                offset.dot(&forward)
+            }
+            Projection::Fisheye | Projection::Equirectangular => offset.norm(),
+        };
+
+        self.camera.set_focus_distance(distance);
+        Some(distance)
+    }
+
+    // Statistics of the top level BVH as of the last trace
+    pub fn acceleration_stats(&self) -> BvhStats {
+        self.acceleration.borrow().bvh.stats()
+    }
+
+    pub(crate) fn background(&self) -> Vec3 {
+        let c = self.clear_color;
+        vec3(c.0, c.1, c.2)
+    }
 }
@@ -0,0 +1,87 @@
+use std::cell::Ref;
+
+use glm::Mat4;
+
+use crate::ray::Instance;
+use crate::{Aabb, Bvh, ObjectHandle, ObjectManager, Scene};
+
+use super::WorldTransforms;
+
+// Top level BVH over the live objects and what intersecting them needs, kept between traces and
+// raycasts. Only objects whose world matrix changed are placed again, the tree is refitted when
+// anything moved and rebuilt when objects were added or removed
+#[derive(Default)]
+pub(crate) struct TopLevel {
+    // `WorldTransforms::version` this is up to date with, `None` before the first update
+    version: Option<u64>,
+    // Primitives of `bvh` in order
+    handles: Vec<ObjectHandle>,
+    to_local: Vec<Mat4>,
+    bounds: Vec<Aabb>,
+    pub(crate) bvh: Bvh,
+}
+
+impl Scene {
+    // Brings the top level BVH up to date with the current object placements. `meshes` must be
+    // the manager the scene's object types were registered with
+    pub(crate) fn acceleration(&self, meshes: &ObjectManager) -> Ref<'_, TopLevel> {
+        let world = self.world();
+        let mut top = self.acceleration.borrow_mut();
+        if top.version != Some(world.version()) {
+            top.update(self, &world, meshes);
+        }
+
+        drop(top);
+        self.acceleration.borrow()
+    }
+}
+
+impl TopLevel {
+    fn update(&mut self, scene: &Scene, world: &WorldTransforms, meshes: &ObjectManager) {
+        let handles: Vec<_> = scene.iter().map(|(handle, _)| handle).collect();
+        let rebuild = self.version.is_none() || handles != self.handles;
+
+        if rebuild {
+            self.to_local.resize(handles.len(), Mat4::identity());
+            self.bounds.resize(handles.len(), Aabb::empty());
+            self.handles = handles;
+        }
+
+        for (i, handle) in self.handles.iter().enumerate() {
+            let index = handle.index as usize;
+            let moved = self
+                .version
+                .is_some_and(|version| world.changed_since(index, version));
+
+            if rebuild || moved {
+                let object = scene.get(*handle).unwrap();
+                let mesh = meshes.from_id(object.object_type);
+                (self.to_local[i], self.bounds[i]) =
+                    Instance::placement(mesh, &world.matrices()[index]);
+            }
+        }
+
+        if rebuild {
+            self.bvh = Bvh::build(&self.bounds);
+        } else {
+            self.bvh.update(&self.bounds);
+        }
+        self.version = Some(world.version());
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    // Instance of primitive `i` of the BVH
+    pub(crate) fn instance<'a>(
+        &self,
+        scene: &'a Scene,
+        meshes: &'a ObjectManager,
+        i: usize,
+    ) -> Instance<'a> {
+        let handle = self.handles[i];
+        let object = scene.get(handle).unwrap();
+        Instance::new(object, handle, self.to_local[i], meshes)
+    }
+}
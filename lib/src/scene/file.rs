@@ -0,0 +1,516 @@
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glm::Vec3;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::object::{MeshSource, ObjectType};
+use crate::{
+    ApertureShape, Camera, Light, Material, MaterialHandle, Mesh, Model, ObjectHandle,
+    ObjectManager, Projection, Quaternion, Scene, TexCoord, TriangleIndecies, Vertex,
+};
+
+// Everything a scene file describes. Callbacks aren't stored, and handles are numbered again in
+// the order things are listed
+#[derive(Serialize, Deserialize)]
+struct SceneFile {
+    camera: CameraFile,
+    #[serde(default)]
+    clear_color: (f32, f32, f32, f32),
+    #[serde(default)]
+    meshes: Vec<MeshFile>,
+    // The first material is the scene's default material, the scene keeps its own if empty
+    #[serde(default)]
+    materials: Vec<Material>,
+    #[serde(default)]
+    lights: Vec<Light>,
+    #[serde(default)]
+    objects: Vec<ObjectFile>,
+}
+
+// The aspect ratio isn't stored, windows and renders set it from their size
+#[derive(Serialize, Deserialize)]
+struct CameraFile {
+    position: Vec3,
+    orientation: Quaternion,
+    // Vertical field of view in degrees
+    #[serde(default = "default_fov")]
+    fov: f32,
+    #[serde(default = "default_near")]
+    near: f32,
+    #[serde(default = "default_far")]
+    far: f32,
+    #[serde(default)]
+    projection: Projection,
+    #[serde(default)]
+    aperture_radius: f32,
+    #[serde(default = "default_focus_distance")]
+    focus_distance: f32,
+    #[serde(default)]
+    aperture_shape: ApertureShape,
+}
+
+// How to make an object type, see `MeshSource`. Parametric primitives are reused if the object
+// manager already has the same one
+#[derive(Serialize, Deserialize)]
+enum MeshFile {
+    Sphere,
+    Cube,
+    Plane {
+        subdivisions: usize,
+    },
+    Disk {
+        segments: usize,
+    },
+    Cylinder {
+        segments: usize,
+    },
+    Cone {
+        segments: usize,
+    },
+    Torus {
+        minor_radius: f32,
+        segments: usize,
+        sides: usize,
+    },
+    Capsule {
+        radius: f32,
+        segments: usize,
+        rings: usize,
+    },
+    Icosphere {
+        level: u32,
+    },
+    UvSphere {
+        segments: usize,
+        rings: usize,
+    },
+    // Mesh `mesh` of a model file (obj, gltf, glb or ply), relative to the scene file
+    File {
+        path: PathBuf,
+        #[serde(default)]
+        mesh: usize,
+    },
+    // Vertex data written out in full, for meshes registered from code
+    Data {
+        name: String,
+        verts: Vec<Vertex>,
+        #[serde(default)]
+        normals: Vec<Vertex>,
+        #[serde(default)]
+        uvs: Vec<TexCoord>,
+        #[serde(default)]
+        colors: Vec<Vertex>,
+        tris: Vec<TriangleIndecies>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectFile {
+    // Index into `SceneFile::meshes`
+    mesh: usize,
+    position: Vec3,
+    #[serde(default = "unit_scale")]
+    scale: Vec3,
+    #[serde(default = "Quaternion::zero")]
+    orientation: Quaternion,
+    // Index into `SceneFile::materials`
+    #[serde(default)]
+    material: usize,
+    // Index into `SceneFile::objects`, the transform is relative to that object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<usize>,
+}
+
+impl Scene {
+    // Reads a scene written by `save`, or by hand. Meshes are registered with `objects`, which
+    // has to be the manager of the window showing the scene. Model files are looked up relative
+    // to the scene file
+    pub fn load(path: impl AsRef<Path>, objects: &mut ObjectManager) -> Result<Scene, String> {
+        let path = path.as_ref();
+        let error = |e: String| format!("{}: {}", path.display(), e);
+
+        let source = fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let file: SceneFile = ron::from_str(&source).map_err(|e| error(e.to_string()))?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        file.into_scene(objects, dir).map_err(error)
+    }
+
+    // Writes the camera, clear color, materials, lights and objects with the meshes they use.
+    // `objects` must be the manager the scene's object types were registered with
+    pub fn save(&self, objects: &ObjectManager, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let file = SceneFile::new(self, objects, dir)?;
+        let config = PrettyConfig::new().depth_limit(3);
+        let text = ron::ser::to_string_pretty(&file, config)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::write(path, text + "\n")
+    }
+}
+
+impl SceneFile {
+    fn new(scene: &Scene, objects: &ObjectManager, dir: &Path) -> io::Result<Self> {
+        let camera = &scene.camera;
+
+        let mut file = SceneFile {
+            camera: CameraFile {
+                position: camera.position,
+                orientation: camera.orientation,
+                fov: camera.fov(),
+                near: camera.near(),
+                far: camera.far(),
+                projection: camera.projection(),
+                aperture_radius: camera.aperture_radius(),
+                focus_distance: camera.focus_distance(),
+                aperture_shape: camera.aperture_shape().clone(),
+            },
+            clear_color: scene.clear_color,
+            meshes: Vec::new(),
+            materials: scene.materials.clone(),
+            lights: scene.lights.iter().flatten().copied().collect(),
+            objects: Vec::new(),
+        };
+
+        // Only the object types the scene uses are written, in the order they're first used
+        let mut meshes: HashMap<usize, usize> = HashMap::new();
+        // Where each object ends up in the file, for the parent links
+        let indices: HashMap<ObjectHandle, usize> =
+            scene.iter().enumerate().map(|(i, (h, _))| (h, i)).collect();
+
+        for (_, object) in scene.iter() {
+            let id = object.object_type;
+            let ty = objects.get(id).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("No object type with id {}", id),
+                )
+            })?;
+
+            let mesh = *meshes.entry(id).or_insert_with(|| {
+                file.meshes.push(MeshFile::new(ty, dir));
+                file.meshes.len() - 1
+            });
+
+            file.objects.push(ObjectFile {
+                mesh,
+                position: object.transform.position,
+                scale: object.transform.scale,
+                orientation: object.transform.orientation,
+                material: object.material.index(&scene.materials),
+                parent: object.parent.map(|parent| indices[&parent]),
+            });
+        }
+
+        Ok(file)
+    }
+
+    fn into_scene(self, objects: &mut ObjectManager, dir: &Path) -> Result<Scene, String> {
+        let mut scene = Scene::new(1.);
+
+        if !Camera::valid_fov(self.camera.fov) {
+            return Err(format!(
+                "camera: field of view {} needs 0 < fov < 180",
+                self.camera.fov
+            ));
+        }
+        if !Camera::valid_clip_planes(self.camera.near, self.camera.far) {
+            return Err(format!(
+                "camera: clip planes {} to {} need 0 < near < far",
+                self.camera.near, self.camera.far
+            ));
+        }
+        if !Camera::valid_aperture_radius(self.camera.aperture_radius) {
+            return Err(format!(
+                "camera: aperture radius {} can't be negative",
+                self.camera.aperture_radius
+            ));
+        }
+        if !Camera::valid_focus_distance(self.camera.focus_distance) {
+            return Err(format!(
+                "camera: focus distance {} needs to be above zero",
+                self.camera.focus_distance
+            ));
+        }
+        scene.camera.position = self.camera.position;
+        scene.camera.orientation = self.camera.orientation;
+        scene
+            .camera
+            .set_fov(self.camera.fov)
+            .set_clip_planes(self.camera.near, self.camera.far)
+            .set_projection(self.camera.projection)
+            .set_aperture_radius(self.camera.aperture_radius)
+            .set_focus_distance(self.camera.focus_distance)
+            .set_aperture_shape(self.camera.aperture_shape);
+
+        let (r, g, b, a) = self.clear_color;
+        scene.set_clear_color(r, g, b, a);
+
+        if !self.materials.is_empty() {
+            scene.materials = self.materials;
+        }
+        for light in self.lights {
+            scene.add_light(light);
+        }
+
+        // Models are loaded once however many of their meshes are used
+        let mut models: HashMap<PathBuf, Model> = HashMap::new();
+        let mut meshes = Vec::with_capacity(self.meshes.len());
+
+        for (i, mesh) in self.meshes.into_iter().enumerate() {
+            let id = mesh
+                .register(objects, dir, &mut models)
+                .map_err(|e| format!("mesh {}: {}", i, e))?;
+            meshes.push(id);
+        }
+
+        let mut handles = Vec::with_capacity(self.objects.len());
+        let mut parents = Vec::new();
+
+        for (i, object) in self.objects.into_iter().enumerate() {
+            let mesh = *meshes.get(object.mesh).ok_or_else(|| {
+                format!(
+                    "object {}: mesh {} out of range, {} defined",
+                    i,
+                    object.mesh,
+                    meshes.len()
+                )
+            })?;
+
+            if object.material >= scene.materials.len() {
+                return Err(format!(
+                    "object {}: material {} out of range, {} defined",
+                    i,
+                    object.material,
+                    scene.materials.len()
+                ));
+            }
+
+            handles.push(scene.add_object(
+                mesh,
+                object.position,
+                object.scale,
+                object.orientation,
+                MaterialHandle(object.material),
+            ));
+            parents.extend(object.parent.map(|parent| (i, parent)));
+        }
+
+        // Parents may be listed after their children
+        for (i, parent) in parents {
+            let &handle = handles.get(parent).ok_or_else(|| {
+                format!(
+                    "object {}: parent {} out of range, {} defined",
+                    i,
+                    parent,
+                    handles.len()
+                )
+            })?;
+
+            if !scene.set_parent(handles[i], Some(handle)) {
+                return Err(format!(
+                    "object {}: parent {} would make a cycle",
+                    i, parent
+                ));
+            }
+        }
+
+        Ok(scene)
+    }
+}
+
+impl MeshFile {
+    fn new(ty: &ObjectType, dir: &Path) -> Self {
+        match ty.source.clone() {
+            MeshSource::Data => {
+                let mesh = ty.mesh();
+                MeshFile::Data {
+                    name: ty.name.clone(),
+                    verts: mesh.verts,
+                    normals: mesh.normals,
+                    uvs: mesh.uvs,
+                    colors: mesh.colors,
+                    tris: mesh.tris,
+                }
+            }
+            MeshSource::Sphere => MeshFile::Sphere,
+            MeshSource::Cube => MeshFile::Cube,
+            MeshSource::Plane { subdivisions } => MeshFile::Plane { subdivisions },
+            MeshSource::Disk { segments } => MeshFile::Disk { segments },
+            MeshSource::Cylinder { segments } => MeshFile::Cylinder { segments },
+            MeshSource::Cone { segments } => MeshFile::Cone { segments },
+            MeshSource::Torus {
+                minor_radius,
+                segments,
+                sides,
+            } => MeshFile::Torus {
+                minor_radius,
+                segments,
+                sides,
+            },
+            MeshSource::Capsule {
+                radius,
+                segments,
+                rings,
+            } => MeshFile::Capsule {
+                radius,
+                segments,
+                rings,
+            },
+            MeshSource::Icosphere { level } => MeshFile::Icosphere { level },
+            MeshSource::UvSphere { segments, rings } => MeshFile::UvSphere { segments, rings },
+            MeshSource::File { path, mesh } => MeshFile::File {
+                path: relative_to(&path, dir),
+                mesh,
+            },
+        }
+    }
+
+    fn register(
+        self,
+        objects: &mut ObjectManager,
+        dir: &Path,
+        models: &mut HashMap<PathBuf, Model>,
+    ) -> Result<usize, String> {
+        let source = match self {
+            MeshFile::Data {
+                name,
+                verts,
+                normals,
+                uvs,
+                colors,
+                tris,
+            } => {
+                let mesh = Mesh {
+                    verts,
+                    normals,
+                    uvs,
+                    colors,
+                    tris,
+                };
+                return objects
+                    .register_mesh(&name, mesh)
+                    .ok_or_else(|| "invalid mesh data".to_string());
+            }
+            MeshFile::File { path, mesh } => {
+                let path = dir.join(path);
+                let path =
+                    fs::canonicalize(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                MeshSource::File { path, mesh }
+            }
+            MeshFile::Sphere => MeshSource::Sphere,
+            MeshFile::Cube => MeshSource::Cube,
+            MeshFile::Plane { subdivisions } => MeshSource::Plane { subdivisions },
+            MeshFile::Disk { segments } => MeshSource::Disk { segments },
+            MeshFile::Cylinder { segments } => MeshSource::Cylinder { segments },
+            MeshFile::Cone { segments } => MeshSource::Cone { segments },
+            MeshFile::Torus {
+                minor_radius,
+                segments,
+                sides,
+            } => MeshSource::Torus {
+                minor_radius,
+                segments,
+                sides,
+            },
+            MeshFile::Capsule {
+                radius,
+                segments,
+                rings,
+            } => MeshSource::Capsule {
+                radius,
+                segments,
+                rings,
+            },
+            MeshFile::Icosphere { level } => MeshSource::Icosphere { level },
+            MeshFile::UvSphere { segments, rings } => MeshSource::UvSphere { segments, rings },
+        };
+
+        if let Some(id) = objects.find_source(&source) {
+            return Ok(id);
+        }
+
+        Ok(match source {
+            MeshSource::Plane { subdivisions } => objects.register_plane(subdivisions),
+            MeshSource::Disk { segments } => objects.register_disk(segments),
+            MeshSource::Cylinder { segments } => objects.register_cylinder(segments),
+            MeshSource::Cone { segments } => objects.register_cone(segments),
+            MeshSource::Torus {
+                minor_radius,
+                segments,
+                sides,
+            } => objects.register_torus(minor_radius, segments, sides),
+            MeshSource::Capsule {
+                radius,
+                segments,
+                rings,
+            } => objects.register_capsule(radius, segments, rings),
+            MeshSource::Icosphere { level } => objects.register_icosphere(level),
+            MeshSource::UvSphere { segments, rings } => objects.register_uv_sphere(segments, rings),
+            MeshSource::File { path, mesh } => {
+                if !models.contains_key(&path) {
+                    let model = objects.load_model(&path)?;
+                    models.insert(path.clone(), model);
+                }
+
+                let model = &models[&path];
+                let model_mesh = model.meshes.get(mesh).ok_or_else(|| {
+                    format!(
+                        "{}: mesh {} out of range, {} defined",
+                        path.display(),
+                        mesh,
+                        model.meshes.len()
+                    )
+                })?;
+                model_mesh.object_type
+            }
+            // Every manager starts with these, so `find_source` always finds them
+            MeshSource::Sphere | MeshSource::Cube | MeshSource::Data => {
+                unreachable!("built in object type missing")
+            }
+        })
+    }
+}
+
+// Model paths are kept absolute, files next to or below the scene are written relative to it so
+// the directory can be moved
+fn relative_to(path: &Path, dir: &Path) -> PathBuf {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+
+    path.strip_prefix(&dir)
+        .map_or_else(|_| path.to_path_buf(), Path::to_path_buf)
+}
+
+fn unit_scale() -> Vec3 {
+    Vec3::repeat(1.)
+}
+
+fn default_fov() -> f32 {
+    Camera::new(1.).fov()
+}
+
+fn default_near() -> f32 {
+    Camera::new(1.).near()
+}
+
+fn default_far() -> f32 {
+    Camera::new(1.).far()
+}
+
+fn default_focus_distance() -> f32 {
+    Camera::new(1.).focus_distance()
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glm::vec3;
+
+use crate::{
+    ApertureShape, Light, Material, MaterialHandle, Mesh, ObjectHandle, ObjectManager, PlyFormat,
+    Primitive, Projection, Quaternion, Scene,
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kobold-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn triangle() -> Mesh {
+    Mesh {
+        verts: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+        normals: vec![[0., 0., 1.]; 3],
+        uvs: vec![[0., 0.], [1., 0.], [0.1, 0.9]],
+        colors: Vec::new(),
+        tris: vec![[0, 1, 2]],
+    }
+}
+
+// One object type of every kind a scene file can describe
+fn object_types(objects: &mut ObjectManager, dir: &Path) -> Vec<usize> {
+    let model = dir.join("model.ply");
+    let data = objects.register_mesh("triangle", triangle()).unwrap();
+    objects
+        .save_ply(data, &model, PlyFormat::BinaryLittleEndian)
+        .unwrap();
+    let file = objects.load_ply(&model).unwrap().meshes[0].object_type;
+
+    vec![
+        Primitive::SPHERE,
+        Primitive::CUBE,
+        objects.register_plane(3),
+        objects.register_disk(7),
+        objects.register_cylinder(9),
+        objects.register_cone(5),
+        objects.register_torus(0.3, 12, 6),
+        objects.register_capsule(0.4, 10, 3),
+        objects.register_icosphere(2),
+        objects.register_uv_sphere(10, 5),
+        data,
+        file,
+    ]
+}
+
+fn scene(types: &[usize]) -> Scene {
+    let mut scene = Scene::new(1.);
+    scene.camera.position = vec3(1., 2., 3.);
+    scene.camera.orientation = *Quaternion::new(0.1, 0.2, 0.3, 0.9).normalize();
+    scene
+        .camera
+        .set_fov(60.)
+        .set_clip_planes(0.5, 500.)
+        .set_projection(Projection::Fisheye)
+        .set_aperture_radius(0.2)
+        .set_focus_distance(4.)
+        .set_aperture_shape(ApertureShape::Polygon {
+            blades: 6,
+            rotation: 15.,
+        });
+    scene.set_clear_color(0.1, 0.2, 0.3, 1.);
+
+    let metal = scene.add_material(Material::Metal {
+        albedo: vec3(0.9, 0.6, 0.3),
+        roughness: 0.25,
+    });
+    scene.add_light(Light::Spot {
+        position: vec3(0., 5., 0.),
+        direction: vec3(0., -1., 0.),
+        color: vec3(1., 0.9, 0.8),
+        intensity: 20.,
+        radius: 0.1,
+        inner_angle: 0.3,
+        outer_angle: 0.5,
+    });
+    scene.add_light(Light::Directional {
+        direction: vec3(1., -1., 0.),
+        color: vec3(1., 1., 1.),
+        intensity: 2.,
+    });
+
+    for (i, ty) in types.iter().enumerate() {
+        let position = vec3(i as f32, 0., -(i as f32));
+        let rotation = Quaternion::from_two(i as f32 / 4., vec3(0., 1., 0.));
+        let material = if i % 2 == 0 { metal } else { MaterialHandle(0) };
+        scene.add_object(*ty, position, vec3(1., 2., 0.5), rotation, material);
+    }
+
+    let handles: Vec<_> = scene.iter().map(|(handle, _)| handle).collect();
+    // Listed before its parent
+    scene.set_parent(handles[0], Some(handles[5]));
+    scene.set_parent(handles[5], Some(handles[1]));
+    scene
+}
+
+#[test]
+fn save_load_round_trip() {
+    let dir = temp_dir("scene");
+    let path = dir.join("scene.ron");
+
+    let mut objects = ObjectManager::new();
+    let types = object_types(&mut objects, &dir);
+    let original = scene(&types);
+    original.save(&objects, &path).unwrap();
+
+    // A fresh manager has to make every object type again
+    let mut loaded_objects = ObjectManager::new();
+    let loaded = Scene::load(&path, &mut loaded_objects);
+    fs::remove_dir_all(&dir).unwrap();
+    let loaded = loaded.unwrap();
+
+    let (a, b) = (&original.camera, &loaded.camera);
+    assert_eq!(a.position, b.position);
+    assert_eq!(a.orientation.as_matrix(), b.orientation.as_matrix());
+    assert_eq!(a.fov(), b.fov());
+    assert_eq!((a.near(), a.far()), (b.near(), b.far()));
+    assert_eq!(a.projection(), b.projection());
+    assert_eq!(a.aperture_radius(), b.aperture_radius());
+    assert_eq!(a.focus_distance(), b.focus_distance());
+    assert_eq!(a.aperture_shape(), b.aperture_shape());
+
+    assert_eq!(original.clear_color, loaded.clear_color);
+    assert_eq!(original.materials, loaded.materials);
+    let lights = |scene: &Scene| scene.lights().map(|(_, l)| *l).collect::<Vec<_>>();
+    assert_eq!(lights(&original), lights(&loaded));
+
+    let handles = |scene: &Scene| scene.iter().map(|(h, _)| h).collect::<Vec<_>>();
+    let (original_handles, loaded_handles) = (handles(&original), handles(&loaded));
+    assert_eq!(original_handles.len(), loaded_handles.len());
+
+    for (a, b) in original_handles.iter().zip(&loaded_handles) {
+        let (a, b) = (original.get(*a).unwrap(), loaded.get(*b).unwrap());
+        assert_eq!(a.transform.matrix(), b.transform.matrix());
+        assert_eq!(a.material, b.material);
+
+        let (a_type, b_type) = (
+            objects.from_id(a.object_type),
+            loaded_objects.from_id(b.object_type),
+        );
+        assert_eq!(a_type.source, b_type.source);
+        assert_eq!(a_type.mesh(), b_type.mesh());
+
+        // Parents are compared by where they're listed
+        let position = |handles: &[ObjectHandle], parent: Option<ObjectHandle>| {
+            parent.map(|p| handles.iter().position(|h| *h == p).unwrap())
+        };
+        assert_eq!(
+            position(&original_handles, a.parent),
+            position(&loaded_handles, b.parent)
+        );
+    }
+}
+
+#[test]
+fn foreign_materials_save_as_the_default() {
+    let dir = temp_dir("foreign-material");
+    let path = dir.join("scene.ron");
+
+    let mut objects = ObjectManager::new();
+    let mut scene = Scene::new(1.);
+    let foreign = MaterialHandle(7);
+    scene.add_object(
+        Primitive::CUBE,
+        vec3(0., 0., 0.),
+        vec3(1., 1., 1.),
+        Quaternion::IDENTITY,
+        foreign,
+    );
+    assert_eq!(scene.resolve_material(foreign), &scene.materials[0]);
+    scene.save(&objects, &path).unwrap();
+
+    let loaded = Scene::load(&path, &mut objects);
+    fs::remove_dir_all(&dir).unwrap();
+    let loaded = loaded.unwrap();
+
+    let (_, object) = loaded.iter().next().unwrap();
+    assert_eq!(object.material, MaterialHandle(0));
+}
@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests;
+
+use std::cell::Ref;
+
+use glm::Mat4;
+
+use crate::{ObjectHandle, Scene, Transform};
+
+use super::{next_version, ObjectSlot};
+
+// Object to world matrices indexed like `Scene::objects`. Only slots whose transform changed, or
+// whose parent's world matrix changed, are worked out again
+#[derive(Default)]
+pub(crate) struct WorldTransforms {
+    matrices: Vec<Mat4>,
+    dirty: Vec<bool>,
+    any_dirty: bool,
+    // Changes with every update that changed anything. Each slot keeps the version of the last
+    // update that changed its matrix, added or removed its object, so caches can tell what to redo
+    version: u64,
+    versions: Vec<u64>,
+}
+
+impl Scene {
+    // Places `child` relative to `parent`, or back in world space for `None`. The child keeps its
+    // transform, so it moves along with the new parent's placement. Fails if either handle is
+    // stale or the parent is the child itself or one of its descendants
+    pub fn set_parent(&mut self, child: ObjectHandle, parent: Option<ObjectHandle>) -> bool {
+        if !self.contains(child) {
+            return false;
+        }
+
+        if let Some(parent) = parent {
+            if !self.contains(parent) || self.ancestors(parent).any(|a| a == child) {
+                return false;
+            }
+        }
+
+        if let Some(object) = self.get_mut(child) {
+            object.parent = parent;
+        }
+        true
+    }
+
+    // Objects whose parent is `handle`
+    pub fn children(&self, handle: ObjectHandle) -> impl Iterator<Item = ObjectHandle> + '_ {
+        self.iter()
+            .filter(move |(_, object)| object.parent == Some(handle))
+            .map(|(child, _)| child)
+    }
+
+    // `handle` followed by its parent, its parent's parent and so on
+    fn ancestors(&self, handle: ObjectHandle) -> impl Iterator<Item = ObjectHandle> + '_ {
+        std::iter::successors(Some(handle), |h| self.get(*h)?.parent)
+    }
+
+    // Object to world transform including every parent's placement
+    pub fn world_matrix(&self, handle: ObjectHandle) -> Option<Mat4> {
+        self.get(handle)?;
+        Some(self.world_matrices()[handle.index as usize])
+    }
+
+    // Indexed by `ObjectHandle::index`, entries of empty slots are meaningless
+    pub(crate) fn world_matrices(&self) -> Ref<'_, [Mat4]> {
+        Ref::map(self.world(), |world| world.matrices.as_slice())
+    }
+
+    pub(crate) fn world(&self) -> Ref<'_, WorldTransforms> {
+        self.world.borrow_mut().update(&self.objects);
+        self.world.borrow()
+    }
+
+    // Children of an object that is going away stay where they are, attached to its parent
+    pub(super) fn detach_children(&mut self, handle: ObjectHandle, removed: &Transform) {
+        let parent = self.get(handle).and_then(|object| object.parent);
+        let children: Vec<_> = self.children(handle).collect();
+
+        for child in children {
+            if let Some(object) = self.get_mut(child) {
+                // Shear from non-uniform scale further up can't be kept
+                let matrix = removed.matrix() * object.transform.matrix();
+                object.transform = Transform::from_matrix(&matrix);
+                object.parent = parent;
+            }
+        }
+    }
+}
+
+impl WorldTransforms {
+    pub(crate) fn matrices(&self) -> &[Mat4] {
+        &self.matrices
+    }
+
+    pub(crate) fn version(&self) -> u64 {
+        self.version
+    }
+
+    // Whether the slot changed in an update after `version`
+    pub(crate) fn changed_since(&self, index: usize, version: u64) -> bool {
+        self.versions[index] > version
+    }
+
+    pub(super) fn mark(&mut self, index: usize) {
+        if index >= self.dirty.len() {
+            self.matrices.resize(index + 1, Mat4::identity());
+            self.dirty.resize(index + 1, true);
+            self.versions.resize(index + 1, 0);
+        }
+
+        self.dirty[index] = true;
+        self.any_dirty = true;
+    }
+
+    pub(super) fn mark_all(&mut self) {
+        self.dirty.fill(true);
+        self.any_dirty = true;
+    }
+
+    fn update(&mut self, objects: &[ObjectSlot]) {
+        if !self.any_dirty {
+            return;
+        }
+
+        self.version = next_version();
+
+        // Whether each slot's matrix changed in this pass, `None` until it has been visited
+        let mut changed = vec![None; objects.len()];
+        for index in 0..objects.len() {
+            self.resolve(objects, index, &mut changed);
+        }
+
+        self.any_dirty = false;
+    }
+
+    // Parents are brought up to date before their children, a child changes with its parent
+    fn resolve(
+        &mut self,
+        objects: &[ObjectSlot],
+        index: usize,
+        changed: &mut [Option<bool>],
+    ) -> bool {
+        if let Some(changed) = changed[index] {
+            return changed;
+        }
+
+        let Some(object) = &objects[index].object else {
+            // Marked when its object was removed
+            if self.dirty[index] {
+                self.dirty[index] = false;
+                self.versions[index] = self.version;
+            }
+            changed[index] = Some(false);
+            return false;
+        };
+
+        let parent = object.parent.map(|p| p.index as usize);
+        let parent_changed = parent.is_some_and(|p| self.resolve(objects, p, changed));
+
+        let dirty = self.dirty[index] || parent_changed;
+        if dirty {
+            let parent_matrix = parent.map_or_else(Mat4::identity, |p| self.matrices[p]);
+            self.matrices[index] = parent_matrix * object.transform.matrix();
+            self.dirty[index] = false;
+            self.versions[index] = self.version;
+        }
+
+        changed[index] = Some(dirty);
+        dirty
+    }
+}
@@ -0,0 +1,145 @@
+use std::f32::consts::FRAC_PI_2;
+
+use glm::{vec3, Mat4, Vec3};
+
+use crate::{MaterialHandle, ObjectHandle, Primitive, Quaternion, Scene};
+
+fn add(scene: &mut Scene, position: Vec3, scale: Vec3, rotation: Quaternion) -> ObjectHandle {
+    scene.add_object(
+        Primitive::CUBE,
+        position,
+        scale,
+        rotation,
+        MaterialHandle::default(),
+    )
+}
+
+fn add_at(scene: &mut Scene, position: Vec3) -> ObjectHandle {
+    add(scene, position, Vec3::repeat(1.), Quaternion::IDENTITY)
+}
+
+fn origin(scene: &Scene, handle: ObjectHandle) -> Vec3 {
+    (scene.world_matrix(handle).unwrap() * vec3(0., 0., 0.).push(1.)).xyz()
+}
+
+fn close_mat(a: &Mat4, b: &Mat4) -> bool {
+    (a - b).abs().max() < 1e-4
+}
+
+// Root, its child and the child's child
+fn chain(scene: &mut Scene) -> [ObjectHandle; 3] {
+    let quarter = Quaternion::from_two(FRAC_PI_2, Vec3::y());
+    let root = add(scene, vec3(1., 2., 3.), vec3(2., 1., 1.), quarter);
+    let half = Vec3::repeat(0.5);
+    let child = add(scene, vec3(1., 0., 0.), half, Quaternion::IDENTITY);
+    let grandchild = add_at(scene, vec3(0., 1., 0.));
+
+    assert!(scene.set_parent(child, Some(root)));
+    assert!(scene.set_parent(grandchild, Some(child)));
+    [root, child, grandchild]
+}
+
+#[test]
+fn rejects_cycles() {
+    let mut scene = Scene::new(1.);
+    let [root, child, grandchild] = chain(&mut scene);
+
+    assert!(!scene.set_parent(root, Some(root)));
+    assert!(!scene.set_parent(root, Some(child)));
+    assert!(!scene.set_parent(root, Some(grandchild)));
+    assert_eq!(scene.get(root).unwrap().parent(), None);
+
+    // Moving a child under its sibling or back to the world is fine
+    assert!(scene.set_parent(grandchild, Some(root)));
+    assert!(scene.set_parent(child, None));
+}
+
+#[test]
+fn rejects_stale_handles() {
+    let mut scene = Scene::new(1.);
+    let a = add_at(&mut scene, Vec3::zeros());
+    let b = add_at(&mut scene, Vec3::zeros());
+    scene.remove(b);
+
+    assert!(!scene.set_parent(a, Some(b)));
+    assert!(!scene.set_parent(b, Some(a)));
+    assert_eq!(scene.get(a).unwrap().parent(), None);
+}
+
+#[test]
+fn world_matrix_composes_every_level() {
+    let mut scene = Scene::new(1.);
+    let [root, child, grandchild] = chain(&mut scene);
+
+    let local = |h| scene.get(h).unwrap().transform.matrix();
+    let expected = local(root) * local(child) * local(grandchild);
+    let world = scene.world_matrix(grandchild).unwrap();
+    assert!(close_mat(&world, &expected));
+    assert!(close_mat(&scene.world_matrix(root).unwrap(), &local(root)));
+
+    let children: Vec<_> = scene.children(root).collect();
+    assert_eq!(children, vec![child]);
+}
+
+#[test]
+fn moving_a_parent_moves_its_descendants() {
+    let mut scene = Scene::new(1.);
+    let [root, child, grandchild] = chain(&mut scene);
+
+    // Worked out once, so the next query has to notice the change
+    let before = [origin(&scene, child), origin(&scene, grandchild)];
+
+    scene.get_mut(root).unwrap().transform.position += vec3(0., 0., 5.);
+    let after = [origin(&scene, child), origin(&scene, grandchild)];
+
+    for (before, after) in before.iter().zip(&after) {
+        assert!((after - before - vec3(0., 0., 5.)).norm() < 1e-4);
+    }
+
+    // Moving the child leaves its parent where it is
+    let root_before = origin(&scene, root);
+    scene.get_mut(child).unwrap().transform.position.y += 1.;
+    assert_eq!(origin(&scene, root), root_before);
+    assert!((origin(&scene, grandchild) - after[1]).norm() > 0.1);
+}
+
+#[test]
+fn reparenting_moves_with_the_new_parent() {
+    let mut scene = Scene::new(1.);
+    let parent = add_at(&mut scene, vec3(10., 0., 0.));
+    let child = add_at(&mut scene, vec3(1., 0., 0.));
+
+    assert!((origin(&scene, child) - vec3(1., 0., 0.)).norm() < 1e-4);
+    scene.set_parent(child, Some(parent));
+    assert!((origin(&scene, child) - vec3(11., 0., 0.)).norm() < 1e-4);
+    scene.set_parent(child, None);
+    assert!((origin(&scene, child) - vec3(1., 0., 0.)).norm() < 1e-4);
+}
+
+#[test]
+fn removing_a_parent_keeps_children_in_place() {
+    let mut scene = Scene::new(1.);
+    let [root, child, grandchild] = chain(&mut scene);
+    let before = scene.world_matrix(grandchild).unwrap();
+
+    scene.remove(child);
+    assert_eq!(scene.get(grandchild).unwrap().parent(), Some(root));
+    assert!(close_mat(&scene.world_matrix(grandchild).unwrap(), &before));
+
+    scene.remove(root);
+    assert_eq!(scene.get(grandchild).unwrap().parent(), None);
+    assert!(close_mat(&scene.world_matrix(grandchild).unwrap(), &before));
+}
+
+#[test]
+fn reused_slots_start_without_a_parent() {
+    let mut scene = Scene::new(1.);
+    let [root, child, _] = chain(&mut scene);
+    scene.remove(child);
+
+    let added = add_at(&mut scene, vec3(0., 0., 1.));
+    assert_eq!(added.index, child.index);
+    assert_eq!(scene.get(added).unwrap().parent(), None);
+    assert!((origin(&scene, added) - vec3(0., 0., 1.)).norm() < 1e-4);
+    assert_eq!(scene.children(root).count(), 1);
+}
@@ -0,0 +1,887 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use glm::{vec3, vec4, Mat3, Mat4, Vec3};
+use roxmltree::{Document, Node};
+
+use crate::material::{conductor_albedo, metal_albedo};
+use crate::{
+    Camera, ImportedScene, Light, Material, MaterialHandle, Model, ObjectManager, Primitive, Scene,
+    Transform,
+};
+
+// Elements that describe a property of their parent rather than an object of their own
+const PROPERTY_TAGS: [&str; 10] = [
+    "float",
+    "integer",
+    "boolean",
+    "string",
+    "rgb",
+    "spectrum",
+    "point",
+    "vector",
+    "transform",
+    "texture",
+];
+
+// Named properties of one element. Lookups mark properties as used, whatever is left unused
+// afterwards is reported
+struct Props<'a, 'input> {
+    properties: Vec<(Node<'a, 'input>, Cell<bool>)>,
+    variables: Rc<HashMap<String, String>>,
+    file: Rc<str>,
+}
+
+// A shape with its transform, before the world is mirrored
+struct Shape {
+    object_type: usize,
+    transform: Mat4,
+    material: Material,
+}
+
+struct Loader<'a> {
+    objects: &'a mut ObjectManager,
+    scene: Scene,
+    warnings: Vec<String>,
+    file: Rc<str>,
+    // Mesh files are looked up relative to the scene file
+    dir: PathBuf,
+    variables: Rc<HashMap<String, String>>,
+    width: usize,
+    height: usize,
+    samples: Option<u32>,
+    // Sensors whose transform mirrors the image are kept rotations by mirroring the world too
+    mirror: Mat4,
+    // BSDFs defined at the top level, by id
+    bsdfs: HashMap<String, Material>,
+    // Scene materials already added, shared by every shape using the same material
+    materials: Vec<(Material, MaterialHandle)>,
+    models: HashMap<PathBuf, Model>,
+}
+
+impl Scene {
+    // Reads the subset of Mitsuba 3 XML needed to compare renders against Mitsuba: perspective
+    // sensors, obj, ply, sphere, rectangle, disk and cube shapes, diffuse, conductor and
+    // dielectric BSDFs, point, spot, directional, constant and area emitters, and transforms.
+    // `variables` override the `<default>` values of `$name` references. Anything else is skipped
+    // with a warning. Meshes are registered with `objects`
+    pub fn load_mitsuba(
+        path: impl AsRef<Path>,
+        objects: &mut ObjectManager,
+        variables: &[(&str, &str)],
+    ) -> Result<ImportedScene, String> {
+        let path = path.as_ref();
+        let file = path.display().to_string();
+
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", file, e))?;
+        let document = Document::parse(&source).map_err(|e| format!("{}: {}", file, e))?;
+
+        let root = document.root_element();
+        if !root.has_tag_name("scene") {
+            return Err(format!("{}: root element is not <scene>", file));
+        }
+
+        // Explicit values take precedence over the file's defaults
+        let mut defined: HashMap<String, String> = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        for node in root.children().filter(|n| n.has_tag_name("default")) {
+            let (Some(name), Some(value)) = (node.attribute("name"), node.attribute("value"))
+            else {
+                let row = document.text_pos_at(node.range().start).row;
+                return Err(format!(
+                    "{}:{}: <default> needs a name and value",
+                    file, row
+                ));
+            };
+            defined
+                .entry(name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+
+        let mut loader = Loader {
+            objects,
+            scene: Scene::new(768. / 576.),
+            warnings: Vec::new(),
+            file: file.into(),
+            dir: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            variables: Rc::new(defined),
+            width: 768,
+            height: 576,
+            samples: None,
+            mirror: Mat4::identity(),
+            bsdfs: HashMap::new(),
+            materials: Vec::new(),
+            models: HashMap::new(),
+        };
+
+        // The sensor decides whether the world is mirrored, so it goes first
+        let sensor = root.children().find(|n| n.has_tag_name("sensor"));
+        match sensor {
+            Some(sensor) => loader.sensor(sensor)?,
+            None => loader.set_camera(&Mat4::identity(), 39.6, "x", 0.01, 10000.),
+        }
+
+        for node in root.children().filter(Node::is_element) {
+            match node.tag_name().name() {
+                "default" | "sensor" => {}
+                "bsdf" => {
+                    let material = loader.bsdf(node)?;
+                    match node.attribute("id") {
+                        Some(id) => {
+                            loader.bsdfs.insert(id.to_string(), material);
+                        }
+                        None => loader.warn(node, "<bsdf> without an id is never used".into()),
+                    }
+                }
+                "shape" => loader.shape(node)?,
+                "emitter" => loader.emitter(node)?,
+                "integrator" => {
+                    let props = loader.props(node);
+                    if node.attribute("type") != Some("path") {
+                        loader.warn(node, "unsupported integrator, using path tracing".into());
+                    }
+                    loader.warn_unused(node, &props);
+                }
+                tag => loader.warn(node, format!("unsupported element <{}>", tag)),
+            }
+        }
+
+        Ok(ImportedScene {
+            scene: loader.scene,
+            width: loader.width,
+            height: loader.height,
+            samples: loader.samples,
+            warnings: loader.warnings,
+        })
+    }
+}
+
+impl<'a> Loader<'a> {
+    fn location(&self, node: Node) -> String {
+        let position = node.document().text_pos_at(node.range().start);
+        format!("{}:{}", self.file, position.row)
+    }
+
+    fn error(&self, node: Node, message: &str) -> String {
+        format!("{}: {}", self.location(node), message)
+    }
+
+    fn warn(&mut self, node: Node, message: String) {
+        let warning = format!("{}: {}", self.location(node), message);
+        self.warnings.push(warning);
+    }
+
+    fn props<'b, 'input>(&self, node: Node<'b, 'input>) -> Props<'b, 'input> {
+        Props::new(node, self.variables.clone(), self.file.clone())
+    }
+
+    fn warn_unused(&mut self, node: Node, props: &Props) {
+        let ty = node.attribute("type").unwrap_or_default();
+        let unused: Vec<_> = props.unused().collect();
+
+        for property in unused {
+            let message = format!(
+                "<{} type=\"{}\">: ignoring <{} name=\"{}\">",
+                node.tag_name().name(),
+                ty,
+                property.tag_name().name(),
+                property.attribute("name").unwrap_or_default()
+            );
+            self.warn(property, message);
+        }
+    }
+
+    // Child elements that are objects of their own, like a shape's BSDF
+    fn objects<'b, 'input>(node: Node<'b, 'input>) -> impl Iterator<Item = Node<'b, 'input>> {
+        node.children()
+            .filter(|n| n.is_element() && !PROPERTY_TAGS.contains(&n.tag_name().name()))
+    }
+
+    fn sensor(&mut self, node: Node) -> Result<(), String> {
+        let props = self.props(node);
+        let ty = node.attribute("type").unwrap_or_default();
+
+        // Focal lengths are for 35mm film, which is 36mm wide
+        let lens_fov = |focal: f32| 2. * (18. / focal).atan().to_degrees();
+        let mut fov = match props.float_opt("fov")? {
+            Some(fov) => fov,
+            None => {
+                let focal = props.string("focal_length")?.unwrap_or("50mm".into());
+                let focal: f32 = focal
+                    .trim_end_matches("mm")
+                    .parse()
+                    .map_err(|_| self.error(node, "invalid focal_length"))?;
+                lens_fov(focal)
+            }
+        };
+        if !Camera::valid_fov(fov) {
+            self.warn(node, format!("invalid fov {}, using a 50mm lens", fov));
+            fov = lens_fov(50.);
+        }
+        let axis = props.string("fov_axis")?.unwrap_or("x".into());
+        let mut near = props.float("near_clip", 0.01)?;
+        let mut far = props.float("far_clip", 10000.)?;
+        if !Camera::valid_clip_planes(near, far) {
+            self.warn(
+                node,
+                format!(
+                    "invalid near_clip {} and far_clip {}, using the defaults",
+                    near, far
+                ),
+            );
+            (near, far) = (0.01, 10000.);
+        }
+        let to_world = props.transform("to_world")?.unwrap_or_else(Mat4::identity);
+
+        for child in Self::objects(node) {
+            let child_props = self.props(child);
+
+            match child.tag_name().name() {
+                "film" => {
+                    self.width = child_props.int("width", 768)?.max(1) as usize;
+                    self.height = child_props.int("height", 576)?.max(1) as usize;
+                }
+                "sampler" => {
+                    self.samples = Some(child_props.int("sample_count", 4)?.max(1) as u32);
+                }
+                tag => self.warn(child, format!("unsupported element <{}>", tag)),
+            }
+
+            self.warn_unused(child, &child_props);
+        }
+
+        if ty != "perspective" && ty != "thinlens" {
+            self.warn(
+                node,
+                format!("unsupported sensor \"{}\", using perspective", ty),
+            );
+        }
+
+        self.set_camera(&to_world, fov, &axis, near, far);
+        if ty == "thinlens" {
+            let radius = props.float("aperture_radius", 0.)?;
+            let default_distance = self.scene.camera.focus_distance();
+            let distance = props.float("focus_distance", default_distance)?;
+
+            // The camera keeps its pinhole and default focus for values it can't use
+            if !Camera::valid_aperture_radius(radius) {
+                self.warn(node, format!("invalid aperture_radius {}, using 0", radius));
+            }
+            if !Camera::valid_focus_distance(distance) {
+                let message = format!(
+                    "invalid focus_distance {}, using {}",
+                    distance, default_distance
+                );
+                self.warn(node, message);
+            }
+            self.scene
+                .camera
+                .set_aperture_radius(radius)
+                .set_focus_distance(distance);
+        }
+        self.warn_unused(node, &props);
+
+        Ok(())
+    }
+
+    fn set_camera(&mut self, to_world: &Mat4, fov: f32, axis: &str, near: f32, far: f32) {
+        let aspect = self.width as f32 / self.height as f32;
+
+        // Field of view along the y axis, from the one along `axis`
+        let tan = (fov.to_radians() / 2.).tan();
+        let tan_y = match axis {
+            "y" => tan,
+            "diagonal" => tan / (aspect * aspect + 1.).sqrt(),
+            "smaller" if aspect >= 1. => tan,
+            "larger" if aspect < 1. => tan,
+            _ => tan / aspect,
+        };
+
+        let linear: Mat3 = to_world.fixed_view::<3, 3>(0, 0).into();
+        if linear.determinant() < 0. {
+            self.mirror = glm::scale(&Mat4::identity(), &vec3(-1., 1., 1.));
+        }
+
+        // Mitsuba's sensors look down +z with +x to the left, the tracer looks down -z with +x to
+        // the right, which is half a turn around y
+        let turn = glm::scale(&Mat4::identity(), &vec3(-1., 1., -1.));
+        let world_from_camera = self.mirror * to_world;
+        let view = turn
+            * world_from_camera
+                .try_inverse()
+                .unwrap_or_else(Mat4::identity);
+
+        self.scene.camera = Camera::from_view(&view, aspect);
+        self.scene
+            .camera
+            .set_fov(2. * tan_y.atan().to_degrees())
+            .set_clip_planes(near, far);
+    }
+
+    fn bsdf(&mut self, node: Node) -> Result<Material, String> {
+        let props = self.props(node);
+        let ty = node.attribute("type").unwrap_or_default();
+
+        let material = match ty {
+            "twosided" | "mask" | "bumpmap" | "normalmap" => {
+                if ty != "twosided" {
+                    self.warn(node, format!("ignoring <bsdf type=\"{}\">", ty));
+                }
+
+                match self.nested_bsdf(node)? {
+                    Some(material) => material,
+                    None => return Err(self.error(node, "missing nested <bsdf>")),
+                }
+            }
+            "diffuse" => diffuse(props.color("reflectance")?.unwrap_or(Vec3::repeat(0.5))),
+            "conductor" | "roughconductor" => {
+                let base = match (props.color("eta")?, props.color("k")?) {
+                    (Some(eta), Some(k)) => conductor_albedo(&eta, &k),
+                    _ => {
+                        let name = props.string("material")?.unwrap_or("none".into());
+                        match metal_albedo(&name) {
+                            Some(albedo) => albedo,
+                            None if name == "none" => Vec3::repeat(1.),
+                            None => {
+                                self.warn(node, format!("unknown material \"{}\"", name));
+                                Vec3::repeat(1.)
+                            }
+                        }
+                    }
+                };
+                let tint = props
+                    .color("specular_reflectance")?
+                    .unwrap_or(Vec3::repeat(1.));
+
+                let default_alpha = if ty == "conductor" { 0. } else { 0.1 };
+                let roughness = match props.float_opt("alpha")? {
+                    Some(alpha) => alpha,
+                    None => {
+                        let u = props.float("alpha_u", default_alpha)?;
+                        let v = props.float("alpha_v", default_alpha)?;
+                        (u + v) / 2.
+                    }
+                };
+
+                Material::Metal {
+                    albedo: base.component_mul(&tint),
+                    roughness,
+                }
+            }
+            "dielectric" | "roughdielectric" | "thindielectric" => {
+                let inside = props.ior("int_ior", 1.5046)?;
+                let outside = props.ior("ext_ior", 1.000277)?;
+
+                Material::Dielectric {
+                    ior: inside / outside,
+                    tint: props
+                        .color("specular_transmittance")?
+                        .unwrap_or(Vec3::repeat(1.)),
+                }
+            }
+            _ => {
+                let message = format!("unsupported <bsdf type=\"{}\">, using diffuse", ty);
+                self.warn(node, message);
+
+                let color = props.color("diffuse_reflectance")?;
+                let color = color.or(props.color("base_color")?);
+                diffuse(color.unwrap_or(Vec3::repeat(0.5)))
+            }
+        };
+
+        self.warn_unused(node, &props);
+        Ok(material)
+    }
+
+    // BSDF given by a `<bsdf>` or `<ref>` child
+    fn nested_bsdf(&mut self, node: Node) -> Result<Option<Material>, String> {
+        for child in Self::objects(node) {
+            match child.tag_name().name() {
+                "bsdf" => return self.bsdf(child).map(Some),
+                "ref" => {
+                    let id = child.attribute("id").unwrap_or_default();
+                    match self.bsdfs.get(id) {
+                        Some(material) => return Ok(Some(*material)),
+                        None => self.warn(child, format!("unknown bsdf \"{}\"", id)),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn shape(&mut self, node: Node) -> Result<(), String> {
+        let props = self.props(node);
+        let ty = node.attribute("type").unwrap_or_default();
+        let to_world = props.transform("to_world")?.unwrap_or_else(Mat4::identity);
+
+        // The built in plane and disk face +y, Mitsuba's +z
+        let facing_z = glm::rotate_x(&Mat4::identity(), std::f32::consts::FRAC_PI_2);
+
+        let shapes: Vec<(usize, Mat4)> = match ty {
+            "obj" | "ply" => {
+                let file = props
+                    .string("filename")?
+                    .ok_or_else(|| self.error(node, "shape without filename"))?;
+                let model = self.model(&file).map_err(|e| self.error(node, &e))?;
+
+                model
+                    .instances
+                    .iter()
+                    .map(|i| (model.meshes[i.mesh].object_type, to_world * i.transform))
+                    .collect()
+            }
+            "sphere" => {
+                let center = props.vector("center")?.unwrap_or(Vec3::zeros());
+                let radius = props.float("radius", 1.)?;
+                let local = glm::translate(&Mat4::identity(), &center)
+                    * glm::scale(&Mat4::identity(), &Vec3::repeat(radius));
+
+                vec![(Primitive::UV_SPHERE, to_world * local)]
+            }
+            "rectangle" => vec![(Primitive::PLANE, to_world * facing_z)],
+            "disk" => vec![(Primitive::DISK, to_world * facing_z)],
+            "cube" => vec![(Primitive::CUBE, to_world)],
+            _ => {
+                self.warn(node, format!("unsupported <shape type=\"{}\">", ty));
+                return Ok(());
+            }
+        };
+
+        let mut material = self
+            .nested_bsdf(node)?
+            .unwrap_or(diffuse(Vec3::repeat(0.5)));
+
+        for child in Self::objects(node) {
+            match child.tag_name().name() {
+                "bsdf" | "ref" => {}
+                "emitter" if child.attribute("type") == Some("area") => {
+                    let emitter = self.props(child);
+                    material = Material::Emissive {
+                        color: emitter.color("radiance")?.unwrap_or(Vec3::repeat(1.)),
+                        strength: 1.,
+                    };
+                    self.warn_unused(child, &emitter);
+                }
+                tag => self.warn(child, format!("unsupported element <{}>", tag)),
+            }
+        }
+
+        for (object_type, transform) in shapes {
+            self.place(Shape {
+                object_type,
+                transform,
+                material,
+            });
+        }
+
+        self.warn_unused(node, &props);
+        Ok(())
+    }
+
+    // Loads each mesh file once, however many shapes use it
+    fn model(&mut self, file: &str) -> Result<Model, String> {
+        let path = self.dir.join(file);
+
+        if let Some(model) = self.models.get(&path) {
+            return Ok(model.clone());
+        }
+
+        let model = self.objects.load_model(&path)?;
+        self.models.insert(path, model.clone());
+        Ok(model)
+    }
+
+    fn place(&mut self, shape: Shape) {
+        let placement = Transform::from_matrix(&(self.mirror * shape.transform));
+
+        let material = match self.materials.iter().find(|(m, _)| *m == shape.material) {
+            Some((_, handle)) => *handle,
+            None => {
+                let handle = self.scene.add_material(shape.material);
+                self.materials.push((shape.material, handle));
+                handle
+            }
+        };
+
+        self.scene.add_object(
+            shape.object_type,
+            placement.position,
+            placement.scale,
+            placement.orientation,
+            material,
+        );
+    }
+
+    fn emitter(&mut self, node: Node) -> Result<(), String> {
+        let props = self.props(node);
+        let ty = node.attribute("type").unwrap_or_default();
+
+        let to_world = self.mirror * props.transform("to_world")?.unwrap_or_else(Mat4::identity);
+        let point = |p: Vec3| (to_world * vec4(p.x, p.y, p.z, 1.)).xyz();
+        let vector = |v: Vec3| (to_world * vec4(v.x, v.y, v.z, 0.)).xyz();
+
+        let light = match ty {
+            "point" => Light::Point {
+                position: point(props.vector("position")?.unwrap_or(Vec3::zeros())),
+                color: props.color("intensity")?.unwrap_or(Vec3::repeat(1.)),
+                intensity: 1.,
+                radius: 0.,
+            },
+            // Shines down +z of its transform
+            "spot" => {
+                let cutoff = props.float("cutoff_angle", 20.)?;
+                let beam = props.float("beam_width", cutoff * 0.75)?;
+
+                Light::Spot {
+                    position: point(Vec3::zeros()),
+                    direction: vector(Vec3::z()),
+                    color: props.color("intensity")?.unwrap_or(Vec3::repeat(1.)),
+                    intensity: 1.,
+                    radius: 0.,
+                    inner_angle: beam.to_radians(),
+                    outer_angle: cutoff.to_radians(),
+                }
+            }
+            "directional" => Light::Directional {
+                direction: vector(props.vector("direction")?.unwrap_or(Vec3::z())),
+                color: props.color("irradiance")?.unwrap_or(Vec3::repeat(1.)),
+                intensity: 1.,
+            },
+            // The clear color lights the scene from every direction
+            "constant" => {
+                let color = props.color("radiance")?.unwrap_or(Vec3::repeat(1.));
+                self.scene.set_clear_color(color.x, color.y, color.z, 1.);
+                self.warn_unused(node, &props);
+                return Ok(());
+            }
+            _ => {
+                self.warn(node, format!("unsupported <emitter type=\"{}\">", ty));
+                return Ok(());
+            }
+        };
+
+        self.scene.add_light(light);
+        self.warn_unused(node, &props);
+        Ok(())
+    }
+}
+
+fn diffuse(albedo: Vec3) -> Material {
+    Material::Diffuse { albedo }
+}
+
+// Mitsuba's table of named indices of refraction
+fn named_ior(name: &str) -> Option<f32> {
+    Some(match name {
+        "vacuum" => 1.,
+        "helium" => 1.000036,
+        "hydrogen" => 1.000132,
+        "air" => 1.000277,
+        "carbon dioxide" => 1.00045,
+        "water" => 1.333,
+        "acetone" => 1.36,
+        "ethanol" => 1.361,
+        "carbon tetrachloride" => 1.461,
+        "glycerol" => 1.4729,
+        "benzene" => 1.501,
+        "silicone oil" => 1.52045,
+        "bromine" => 1.661,
+        "water ice" => 1.31,
+        "fused quartz" => 1.458,
+        "pyrex" => 1.47,
+        "acrylic glass" => 1.49,
+        "polypropylene" => 1.49,
+        "bk7" => 1.5046,
+        "sodium chloride" => 1.544,
+        "amber" => 1.55,
+        "pet" => 1.575,
+        "diamond" => 2.419,
+        _ => return None,
+    })
+}
+
+// Numbers separated by commas and/or whitespace
+fn parse_numbers(text: &str) -> Option<Vec<f32>> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok())
+        .collect()
+}
+
+impl<'a, 'input> Props<'a, 'input> {
+    fn new(node: Node<'a, 'input>, variables: Rc<HashMap<String, String>>, file: Rc<str>) -> Self {
+        let properties = node
+            .children()
+            .filter(|n| PROPERTY_TAGS.contains(&n.tag_name().name()) && n.has_attribute("name"))
+            .map(|n| (n, Cell::new(false)))
+            .collect();
+
+        Self {
+            properties,
+            variables,
+            file,
+        }
+    }
+
+    fn unused(&self) -> impl Iterator<Item = Node<'a, 'input>> + '_ {
+        self.properties
+            .iter()
+            .filter(|(_, used)| !used.get())
+            .map(|(node, _)| *node)
+    }
+
+    fn error(&self, node: Node, message: &str) -> String {
+        let position = node.document().text_pos_at(node.range().start);
+        format!("{}:{}: {}", self.file, position.row, message)
+    }
+
+    // Property `name` if it's one of the tags, marked as used
+    fn find(&self, name: &str, tags: &[&str]) -> Option<Node<'a, 'input>> {
+        let (node, used) = self.properties.iter().find(|(n, _)| {
+            n.attribute("name") == Some(name) && tags.contains(&n.tag_name().name())
+        })?;
+
+        used.set(true);
+        Some(*node)
+    }
+
+    // Attribute with `$name` references replaced by the variable's value
+    fn attribute(&self, node: Node, attribute: &str) -> Result<Option<String>, String> {
+        let Some(text) = node.attribute(attribute) else {
+            return Ok(None);
+        };
+
+        let mut value = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find('$') {
+            value.push_str(&rest[..start]);
+            rest = &rest[start + 1..];
+
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let name = &rest[..end];
+            let variable = self
+                .variables
+                .get(name)
+                .ok_or_else(|| self.error(node, &format!("undefined variable ${}", name)))?;
+
+            value.push_str(variable);
+            rest = &rest[end..];
+        }
+        value.push_str(rest);
+
+        Ok(Some(value))
+    }
+
+    fn value(&self, node: Node) -> Result<String, String> {
+        self.attribute(node, "value")?
+            .ok_or_else(|| self.error(node, "missing value"))
+    }
+
+    fn numbers(&self, node: Node) -> Result<Vec<f32>, String> {
+        let value = self.value(node)?;
+        parse_numbers(&value)
+            .ok_or_else(|| self.error(node, &format!("invalid numbers \"{}\"", value)))
+    }
+
+    fn float_opt(&self, name: &str) -> Result<Option<f32>, String> {
+        let Some(node) = self.find(name, &["float"]) else {
+            return Ok(None);
+        };
+
+        match self.numbers(node)?[..] {
+            [value] => Ok(Some(value)),
+            _ => Err(self.error(node, "expected a single number")),
+        }
+    }
+
+    fn float(&self, name: &str, default: f32) -> Result<f32, String> {
+        Ok(self.float_opt(name)?.unwrap_or(default))
+    }
+
+    fn int(&self, name: &str, default: i32) -> Result<i32, String> {
+        let Some(node) = self.find(name, &["integer"]) else {
+            return Ok(default);
+        };
+
+        let value = self.value(node)?;
+        value
+            .trim()
+            .parse()
+            .map_err(|_| self.error(node, &format!("invalid integer \"{}\"", value)))
+    }
+
+    fn string(&self, name: &str) -> Result<Option<String>, String> {
+        match self.find(name, &["string"]) {
+            Some(node) => self.value(node).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // RGB triplets, single values for gray and sampled spectra, which are averaged
+    fn color(&self, name: &str) -> Result<Option<Vec3>, String> {
+        let Some(node) = self.find(name, &["rgb", "spectrum", "float"]) else {
+            return Ok(None);
+        };
+
+        let value = self.value(node)?;
+        let invalid = || self.error(node, &format!("invalid color \"{}\"", value));
+
+        if value.contains(':') {
+            let samples: Option<Vec<f32>> = value
+                .split(',')
+                .map(|pair| pair.split_once(':')?.1.trim().parse().ok())
+                .collect();
+            let samples = samples.filter(|s| !s.is_empty()).ok_or_else(invalid)?;
+
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            return Ok(Some(Vec3::repeat(mean)));
+        }
+
+        match parse_numbers(&value).ok_or_else(invalid)?[..] {
+            [gray] => Ok(Some(Vec3::repeat(gray))),
+            [r, g, b] => Ok(Some(vec3(r, g, b))),
+            _ => Err(invalid()),
+        }
+    }
+
+    // `x`, `y` and `z` attributes or a `value` with all three
+    fn vector(&self, name: &str) -> Result<Option<Vec3>, String> {
+        let Some(node) = self.find(name, &["point", "vector"]) else {
+            return Ok(None);
+        };
+
+        xyz(self, node, 0.).map(Some)
+    }
+
+    // Index of refraction given as a number or a name
+    fn ior(&self, name: &str, default: f32) -> Result<f32, String> {
+        if let Some(ior) = self.float_opt(name)? {
+            return Ok(ior);
+        }
+
+        match self.string(name)? {
+            Some(named) => named_ior(&named.to_lowercase()).ok_or_else(|| {
+                let node = self.find(name, &["string"]).unwrap();
+                self.error(node, &format!("unknown index of refraction \"{}\"", named))
+            }),
+            None => Ok(default),
+        }
+    }
+
+    // The operations are applied in order, each one after the ones before it
+    fn transform(&self, name: &str) -> Result<Option<Mat4>, String> {
+        let Some(node) = self.find(name, &["transform"]) else {
+            return Ok(None);
+        };
+
+        let mut transform = Mat4::identity();
+
+        for op in node.children().filter(Node::is_element) {
+            let step = match op.tag_name().name() {
+                "translate" => glm::translate(&Mat4::identity(), &xyz(self, op, 0.)?),
+                "scale" => {
+                    let scale = match self.attribute(op, "value")? {
+                        Some(value) => match parse_numbers(&value).as_deref() {
+                            Some([s]) => Vec3::repeat(*s),
+                            Some([x, y, z]) => vec3(*x, *y, *z),
+                            _ => return Err(self.error(op, "invalid scale")),
+                        },
+                        None => xyz(self, op, 1.)?,
+                    };
+                    glm::scale(&Mat4::identity(), &scale)
+                }
+                "rotate" => {
+                    let axis = xyz(self, op, 0.)?;
+                    let angle = self
+                        .attribute(op, "angle")?
+                        .and_then(|a| a.trim().parse::<f32>().ok())
+                        .ok_or_else(|| self.error(op, "rotate needs an angle"))?;
+                    glm::rotate(&Mat4::identity(), angle.to_radians(), &axis)
+                }
+                // Row by row
+                "matrix" => match self.numbers(op)?[..] {
+                    ref m if m.len() == 16 => Mat4::from_row_slice(m),
+                    ref m if m.len() == 9 => Mat3::from_row_slice(m).to_homogeneous(),
+                    _ => return Err(self.error(op, "matrix needs 9 or 16 numbers")),
+                },
+                "lookat" => {
+                    let vector = |attribute: &str| -> Result<Option<Vec3>, String> {
+                        match self.attribute(op, attribute)? {
+                            Some(value) => match parse_numbers(&value).as_deref() {
+                                Some([x, y, z]) => Ok(Some(vec3(*x, *y, *z))),
+                                _ => Err(self.error(op, &format!("invalid {}", attribute))),
+                            },
+                            None => Ok(None),
+                        }
+                    };
+
+                    let origin = vector("origin")?.unwrap_or(Vec3::zeros());
+                    let target = vector("target")?.unwrap_or(Vec3::z());
+                    let up = vector("up")?.unwrap_or(Vec3::y());
+                    look_at(&origin, &target, &up)
+                        .ok_or_else(|| self.error(op, "lookat direction is parallel to up"))?
+                }
+                tag => return Err(self.error(op, &format!("unknown transform <{}>", tag))),
+            };
+
+            transform = step * transform;
+        }
+
+        Ok(Some(transform))
+    }
+}
+
+// Vector given as `value="x, y, z"` or as separate attributes, missing ones are `default`
+fn xyz(props: &Props, node: Node, default: f32) -> Result<Vec3, String> {
+    if let Some(value) = props.attribute(node, "value")? {
+        return match parse_numbers(&value).as_deref() {
+            Some([x, y, z]) => Ok(vec3(*x, *y, *z)),
+            _ => Err(props.error(node, &format!("invalid vector \"{}\"", value))),
+        };
+    }
+
+    let mut v = Vec3::repeat(default);
+    for (i, attribute) in ["x", "y", "z"].into_iter().enumerate() {
+        if let Some(value) = props.attribute(node, attribute)? {
+            v[i] = value
+                .trim()
+                .parse()
+                .map_err(|_| props.error(node, &format!("invalid {} \"{}\"", attribute, value)))?;
+        }
+    }
+
+    Ok(v)
+}
+
+// World from camera transform of a sensor at `origin` looking at `target`, with +x to the left
+fn look_at(origin: &Vec3, target: &Vec3, up: &Vec3) -> Option<Mat4> {
+    let dir = (target - origin).normalize();
+    let left = up.normalize().cross(&dir);
+    if left.norm() == 0. {
+        return None;
+    }
+
+    let left = left.normalize();
+    let up = dir.cross(&left);
+
+    #[rustfmt::skip]
+    let world_from_camera = Mat4::new(
+        left.x, up.x, dir.x, origin.x,
+        left.y, up.y, dir.y, origin.y,
+        left.z, up.z, dir.z, origin.z,
+        0., 0., 0., 1.,
+    );
+
+    Some(world_from_camera)
+}
@@ -0,0 +1,996 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glm::{vec3, vec4, Mat3, Mat4, Vec3};
+
+use crate::material::{conductor_albedo, metal_albedo};
+use crate::{
+    Camera, ImportedScene, Light, Material, MaterialHandle, Mesh, ObjectManager, Primitive, Scene,
+    Transform,
+};
+
+// Value inside or outside of brackets. PBRT writes bools both quoted and bare
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f32),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug)]
+enum Arg {
+    Value(Value),
+    List(Vec<Value>),
+}
+
+struct Directive {
+    name: String,
+    args: Vec<Arg>,
+    // Where the directive starts, for messages
+    location: String,
+}
+
+// `"type name" value` pairs following a directive. Lookups mark parameters as used, whatever is
+// left unused afterwards is reported
+struct Param {
+    ty: String,
+    name: String,
+    values: Vec<Value>,
+    used: Cell<bool>,
+}
+
+struct Params(Vec<Param>);
+
+// What AttributeBegin saves and AttributeEnd restores
+#[derive(Clone)]
+struct GraphicsState {
+    transform: Mat4,
+    material: Material,
+    // Emission given by AreaLightSource, replaces the material of the shapes that follow
+    area_light: Option<Material>,
+}
+
+// Camera directive with the camera from world transform at the time
+#[derive(Clone, Copy)]
+struct CameraDirective {
+    camera_from_world: Mat4,
+    fov: f32,
+    aspect: Option<f32>,
+    lens_radius: f32,
+    focal_distance: f32,
+}
+
+// A shape placed relative to the world, or to the instance while inside ObjectBegin
+struct Shape {
+    object_type: usize,
+    transform: Mat4,
+    material: Material,
+}
+
+struct Builder<'a> {
+    objects: &'a mut ObjectManager,
+    scene: Scene,
+    warnings: Vec<String>,
+    // Files are looked up relative to the main file, as PBRT does
+    dir: PathBuf,
+    width: usize,
+    height: usize,
+    samples: Option<u32>,
+    camera: Option<CameraDirective>,
+    // PBRT's camera space is left handed, see `WorldBegin`
+    mirror: Mat4,
+    state: GraphicsState,
+    stack: Vec<GraphicsState>,
+    named_materials: HashMap<String, Material>,
+    coordinate_systems: HashMap<String, Mat4>,
+    // Definitions of ObjectBegin blocks, and the one being defined
+    instances: HashMap<String, Vec<Shape>>,
+    instance: Option<(String, Vec<Shape>)>,
+    // Scene materials already added, shared by every shape using the same material
+    materials: Vec<(Material, MaterialHandle)>,
+    ply_meshes: HashMap<PathBuf, usize>,
+}
+
+impl Scene {
+    // Reads the subset of PBRT-v4 needed to compare renders against PBRT: perspective cameras,
+    // triangle, PLY, sphere and disk shapes, diffuse, conductor and dielectric materials, point,
+    // spot, distant, infinite and area lights, transforms, attributes, named materials and object
+    // instancing. Anything else is skipped with a warning. Meshes are registered with `objects`
+    pub fn load_pbrt(
+        path: impl AsRef<Path>,
+        objects: &mut ObjectManager,
+    ) -> Result<ImportedScene, String> {
+        let path = path.as_ref();
+
+        let mut builder = Builder {
+            objects,
+            scene: Scene::new(1280. / 720.),
+            warnings: Vec::new(),
+            dir: path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            width: 1280,
+            height: 720,
+            samples: None,
+            camera: None,
+            mirror: glm::scale(&Mat4::identity(), &vec3(-1., 1., 1.)),
+            state: GraphicsState {
+                transform: Mat4::identity(),
+                material: diffuse(Vec3::repeat(0.5)),
+                area_light: None,
+            },
+            stack: Vec::new(),
+            named_materials: HashMap::new(),
+            coordinate_systems: HashMap::new(),
+            instances: HashMap::new(),
+            instance: None,
+            materials: Vec::new(),
+            ply_meshes: HashMap::new(),
+        };
+
+        builder.parse_file(path, 0)?;
+        Ok(builder.finish())
+    }
+}
+
+impl Builder<'_> {
+    fn parse_file(&mut self, path: &Path, depth: usize) -> Result<(), String> {
+        // Guards against files including themselves
+        if depth > 32 {
+            return Err(format!("{}: includes nested too deeply", path.display()));
+        }
+
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let directives = parse_directives(&source, &path.display().to_string())?;
+
+        for directive in directives {
+            self.directive(&directive, depth)
+                .map_err(|e| format!("{}: {}", directive.location, e))?;
+        }
+
+        Ok(())
+    }
+
+    fn warn(&mut self, directive: &Directive, message: String) {
+        self.warnings
+            .push(format!("{}: {}", directive.location, message));
+    }
+
+    fn warn_unused(&mut self, directive: &Directive, ty: &str, params: &Params) {
+        for param in params.0.iter().filter(|p| !p.used.get()) {
+            let message = format!(
+                "{} \"{}\": ignoring parameter \"{} {}\"",
+                directive.name, ty, param.ty, param.name
+            );
+            self.warn(directive, message);
+        }
+    }
+
+    fn directive(&mut self, directive: &Directive, depth: usize) -> Result<(), String> {
+        let args = &directive.args;
+
+        match directive.name.as_str() {
+            "Identity" => self.state.transform = Mat4::identity(),
+            "Translate" => {
+                let [x, y, z] = numbers(args)?;
+                self.concat(glm::translate(&Mat4::identity(), &vec3(x, y, z)));
+            }
+            "Scale" => {
+                let [x, y, z] = numbers(args)?;
+                self.concat(glm::scale(&Mat4::identity(), &vec3(x, y, z)));
+            }
+            "Rotate" => {
+                let [angle, x, y, z] = numbers(args)?;
+                let rotation = glm::rotate(&Mat4::identity(), angle.to_radians(), &vec3(x, y, z));
+                self.concat(rotation);
+            }
+            "LookAt" => {
+                let [ex, ey, ez, lx, ly, lz, ux, uy, uz] = numbers(args)?;
+                let eye = vec3(ex, ey, ez);
+
+                match look_at(&eye, &vec3(lx, ly, lz), &vec3(ux, uy, uz)) {
+                    Some(transform) => self.concat(transform),
+                    None => self.warn(directive, "LookAt direction is parallel to up".into()),
+                }
+            }
+            // Matrices are given column by column
+            "Transform" => self.state.transform = Mat4::from_column_slice(&numbers::<16>(args)?),
+            "ConcatTransform" => self.concat(Mat4::from_column_slice(&numbers::<16>(args)?)),
+            "CoordinateSystem" => {
+                let ([name], _) = strings(args)?;
+                self.coordinate_systems.insert(name, self.state.transform);
+            }
+            "CoordSysTransform" => {
+                let ([name], _) = strings(args)?;
+                match self.coordinate_systems.get(&name) {
+                    Some(transform) => self.state.transform = *transform,
+                    None => self.warn(directive, format!("unknown coordinate system \"{}\"", name)),
+                }
+            }
+            "AttributeBegin" | "TransformBegin" => self.stack.push(self.state.clone()),
+            "AttributeEnd" | "TransformEnd" => {
+                let Some(state) = self.stack.pop() else {
+                    return Err(format!("{} without a matching begin", directive.name));
+                };
+
+                // TransformEnd only restores the transform
+                if directive.name == "TransformEnd" {
+                    self.state.transform = state.transform;
+                } else {
+                    self.state = state;
+                }
+            }
+            "Camera" => self.camera(directive)?,
+            "Film" => {
+                let ([ty], params) = strings(args)?;
+                let params = Params::parse(params)?;
+
+                self.width = params.int("xresolution", 1280).max(1) as usize;
+                self.height = params.int("yresolution", 720).max(1) as usize;
+                self.warn_unused(directive, &ty, &params);
+            }
+            "Sampler" => {
+                let ([ty], params) = strings(args)?;
+                let params = Params::parse(params)?;
+
+                self.samples = Some(params.int("pixelsamples", 16).max(1) as u32);
+                self.warn_unused(directive, &ty, &params);
+            }
+            "WorldBegin" => {
+                // PBRT looks down +z with +x to the right, which is a mirror image of the view
+                // space the tracer uses. Mirroring the world as well keeps the camera a rotation
+                // and the picture the same. Files that already mirror their camera need nothing
+                let camera = self
+                    .camera
+                    .map_or_else(Mat4::identity, |c| c.camera_from_world);
+                let linear: Mat3 = camera.fixed_view::<3, 3>(0, 0).into();
+                self.mirror = if linear.determinant() < 0. {
+                    Mat4::identity()
+                } else {
+                    glm::scale(&Mat4::identity(), &vec3(-1., 1., 1.))
+                };
+
+                self.state.transform = Mat4::identity();
+                self.coordinate_systems
+                    .insert("world".into(), Mat4::identity());
+            }
+            "Material" => {
+                let ([ty], params) = strings(args)?;
+                let params = Params::parse(params)?;
+
+                self.state.material = self.material(directive, &ty, &params);
+                self.warn_unused(directive, &ty, &params);
+            }
+            "MakeNamedMaterial" => {
+                let ([name], params) = strings(args)?;
+                let params = Params::parse(params)?;
+                let ty = params.string("type").unwrap_or("diffuse").to_string();
+
+                let material = self.material(directive, &ty, &params);
+                self.named_materials.insert(name, material);
+                self.warn_unused(directive, &ty, &params);
+            }
+            "NamedMaterial" => {
+                let ([name], _) = strings(args)?;
+                match self.named_materials.get(&name) {
+                    Some(material) => self.state.material = *material,
+                    None => self.warn(directive, format!("unknown material \"{}\"", name)),
+                }
+            }
+            "AreaLightSource" => {
+                let ([ty], params) = strings(args)?;
+                let params = Params::parse(params)?;
+
+                if ty == "diffuse" {
+                    // Emissive materials always emit from both sides
+                    params.bool("twosided", false);
+                    self.state.area_light = Some(Material::Emissive {
+                        color: params.rgb("L").unwrap_or(Vec3::repeat(1.)),
+                        strength: params.float("scale", 1.),
+                    });
+                } else {
+                    self.warn(directive, format!("unsupported area light \"{}\"", ty));
+                }
+                self.warn_unused(directive, &ty, &params);
+            }
+            "LightSource" => {
+                let ([ty], params) = strings(args)?;
+                let params = Params::parse(params)?;
+
+                self.light(directive, &ty, &params);
+                self.warn_unused(directive, &ty, &params);
+            }
+            "Shape" => {
+                let ([ty], params) = strings(args)?;
+                let params = Params::parse(params)?;
+
+                self.shape(directive, &ty, &params)?;
+                self.warn_unused(directive, &ty, &params);
+            }
+            "ObjectBegin" => {
+                let ([name], _) = strings(args)?;
+                if self.instance.is_some() {
+                    return Err("ObjectBegin inside another ObjectBegin".into());
+                }
+
+                self.stack.push(self.state.clone());
+                self.instance = Some((name, Vec::new()));
+            }
+            "ObjectEnd" => {
+                let Some((name, shapes)) = self.instance.take() else {
+                    return Err("ObjectEnd without ObjectBegin".into());
+                };
+
+                self.instances.insert(name, shapes);
+                if let Some(state) = self.stack.pop() {
+                    self.state = state;
+                }
+            }
+            "ObjectInstance" => {
+                let ([name], _) = strings(args)?;
+                let Some(shapes) = self.instances.get(&name) else {
+                    self.warn(directive, format!("unknown object \"{}\"", name));
+                    return Ok(());
+                };
+
+                let placed: Vec<_> = shapes
+                    .iter()
+                    .map(|shape| Shape {
+                        transform: self.state.transform * shape.transform,
+                        ..*shape
+                    })
+                    .collect();
+                for shape in placed {
+                    self.place(shape);
+                }
+            }
+            "Include" | "Import" => {
+                let ([file], _) = strings(args)?;
+                let path = self.dir.join(file);
+                self.parse_file(&path, depth + 1)?;
+            }
+            // Rendering settings, media, textures and motion blur
+            name => self.warn(directive, format!("unsupported directive {}", name)),
+        }
+
+        Ok(())
+    }
+
+    fn concat(&mut self, transform: Mat4) {
+        self.state.transform *= transform;
+    }
+
+    fn camera(&mut self, directive: &Directive) -> Result<(), String> {
+        let ([ty], params) = strings(&directive.args)?;
+        let params = Params::parse(params)?;
+
+        if ty != "perspective" {
+            self.warn(
+                directive,
+                format!("unsupported camera \"{}\", using perspective", ty),
+            );
+        }
+
+        let mut fov = params.float("fov", 90.);
+        if !Camera::valid_fov(fov) {
+            self.warn(directive, format!("invalid fov {}, using 90", fov));
+            fov = 90.;
+        }
+        let aspect = params
+            .floats("frameaspectratio")
+            .and_then(|v| v.first().copied());
+        let mut lens_radius = params.float("lensradius", 0.);
+        if !Camera::valid_aperture_radius(lens_radius) {
+            let message = format!("invalid lensradius {}, using 0", lens_radius);
+            self.warn(directive, message);
+            lens_radius = 0.;
+        }
+        let mut focal_distance = params.float("focaldistance", 1e6);
+        if !Camera::valid_focus_distance(focal_distance) {
+            let message = format!("invalid focaldistance {}, using 1e6", focal_distance);
+            self.warn(directive, message);
+            focal_distance = 1e6;
+        }
+        self.camera = Some(CameraDirective {
+            camera_from_world: self.state.transform,
+            fov,
+            aspect,
+            lens_radius,
+            focal_distance,
+        });
+
+        let camera = self
+            .state
+            .transform
+            .try_inverse()
+            .unwrap_or_else(Mat4::identity);
+        self.coordinate_systems.insert("camera".into(), camera);
+        self.warn_unused(directive, &ty, &params);
+
+        Ok(())
+    }
+
+    fn material(&mut self, directive: &Directive, ty: &str, params: &Params) -> Material {
+        match ty {
+            "diffuse" => diffuse(params.rgb("reflectance").unwrap_or(Vec3::repeat(0.5))),
+            "conductor" => {
+                let albedo = params
+                    .rgb("reflectance")
+                    .unwrap_or_else(|| conductor_color(params));
+                let roughness = params.roughness();
+
+                Material::Metal { albedo, roughness }
+            }
+            "dielectric" | "thindielectric" => {
+                // Named glass spectra all have an index close to 1.5
+                let ior = match params.string("eta") {
+                    Some(_) => 1.5,
+                    None => params.float("eta", 1.5),
+                };
+
+                Material::Dielectric {
+                    ior,
+                    tint: Vec3::repeat(1.),
+                }
+            }
+            "" | "none" | "interface" => Material::Dielectric {
+                ior: 1.,
+                tint: Vec3::repeat(1.),
+            },
+            _ => {
+                let message = format!("unsupported material \"{}\", using diffuse", ty);
+                self.warn(directive, message);
+
+                diffuse(params.rgb("reflectance").unwrap_or(Vec3::repeat(0.5)))
+            }
+        }
+    }
+
+    fn light(&mut self, directive: &Directive, ty: &str, params: &Params) {
+        let transform = self.mirror * self.state.transform;
+        let point = |p: Vec3| (transform * vec4(p.x, p.y, p.z, 1.)).xyz();
+        let vector = |v: Vec3| (transform * vec4(v.x, v.y, v.z, 0.)).xyz();
+
+        let scale = params.float("scale", 1.);
+        let from = params.point("from").unwrap_or(Vec3::zeros());
+
+        let light = match ty {
+            "point" => Light::Point {
+                position: point(from),
+                color: params.rgb("I").unwrap_or(Vec3::repeat(1.)),
+                intensity: scale,
+                radius: 0.,
+            },
+            "spot" => {
+                let to = params.point("to").unwrap_or(vec3(0., 0., 1.));
+                let cone = params.float("coneangle", 30.);
+                let delta = params.float("conedelta", 5.);
+
+                Light::Spot {
+                    position: point(from),
+                    direction: vector(to - from),
+                    color: params.rgb("I").unwrap_or(Vec3::repeat(1.)),
+                    intensity: scale,
+                    radius: 0.,
+                    inner_angle: (cone - delta).max(0.).to_radians(),
+                    outer_angle: cone.to_radians(),
+                }
+            }
+            "distant" => {
+                let to = params.point("to").unwrap_or(vec3(0., 0., 1.));
+
+                Light::Directional {
+                    direction: vector(to - from),
+                    color: params.rgb("L").unwrap_or(Vec3::repeat(1.)),
+                    intensity: scale,
+                }
+            }
+            // The clear color lights the scene from every direction
+            "infinite" => {
+                let color = params.rgb("L").unwrap_or(Vec3::repeat(1.)) * scale;
+                self.scene.set_clear_color(color.x, color.y, color.z, 1.);
+                return;
+            }
+            _ => {
+                self.warn(directive, format!("unsupported light \"{}\"", ty));
+                return;
+            }
+        };
+
+        self.scene.add_light(light);
+    }
+
+    fn shape(&mut self, directive: &Directive, ty: &str, params: &Params) -> Result<(), String> {
+        let (object_type, local) = match ty {
+            "trianglemesh" => {
+                let mesh = triangle_mesh(params)?;
+                let id = self
+                    .objects
+                    .register_mesh("trianglemesh", mesh)
+                    .ok_or("triangle mesh index out of range")?;
+
+                (id, Mat4::identity())
+            }
+            "plymesh" => {
+                let file = params
+                    .string("filename")
+                    .ok_or("plymesh without filename")?;
+                let path = self.dir.join(file);
+
+                let id = match self.ply_meshes.get(&path) {
+                    Some(id) => *id,
+                    None => {
+                        let model = self.objects.load_ply(&path)?;
+                        let id = model.meshes[0].object_type;
+                        self.ply_meshes.insert(path, id);
+                        id
+                    }
+                };
+
+                (id, Mat4::identity())
+            }
+            "sphere" => {
+                let radius = params.float("radius", 1.);
+                let scale = glm::scale(&Mat4::identity(), &Vec3::repeat(radius));
+
+                (Primitive::UV_SPHERE, scale)
+            }
+            // PBRT's disk faces +z at `height`, the built in one faces +y
+            "disk" => {
+                let radius = params.float("radius", 1.);
+                let height = params.float("height", 0.);
+                let local = glm::translate(&Mat4::identity(), &vec3(0., 0., height))
+                    * glm::rotate_x(&Mat4::identity(), std::f32::consts::FRAC_PI_2)
+                    * glm::scale(&Mat4::identity(), &Vec3::repeat(radius));
+
+                (Primitive::DISK, local)
+            }
+            _ => {
+                self.warn(directive, format!("unsupported shape \"{}\"", ty));
+                return Ok(());
+            }
+        };
+
+        let shape = Shape {
+            object_type,
+            transform: self.state.transform * local,
+            material: self.state.area_light.unwrap_or(self.state.material),
+        };
+
+        match &mut self.instance {
+            Some((_, shapes)) => shapes.push(shape),
+            None => self.place(shape),
+        }
+
+        Ok(())
+    }
+
+    fn place(&mut self, shape: Shape) {
+        let placement = Transform::from_matrix(&(self.mirror * shape.transform));
+
+        let material = match self.materials.iter().find(|(m, _)| *m == shape.material) {
+            Some((_, handle)) => *handle,
+            None => {
+                let handle = self.scene.add_material(shape.material);
+                self.materials.push((shape.material, handle));
+                handle
+            }
+        };
+
+        self.scene.add_object(
+            shape.object_type,
+            placement.position,
+            placement.scale,
+            placement.orientation,
+            material,
+        );
+    }
+
+    fn finish(mut self) -> ImportedScene {
+        let camera = self.camera.unwrap_or(CameraDirective {
+            camera_from_world: Mat4::identity(),
+            fov: 90.,
+            aspect: None,
+            lens_radius: 0.,
+            focal_distance: 1e6,
+        });
+        let aspect = camera
+            .aspect
+            .unwrap_or(self.width as f32 / self.height as f32);
+
+        // The field of view spans the shorter image axis
+        let fov = camera.fov.to_radians();
+        let fovy = if aspect >= 1. {
+            fov
+        } else {
+            2. * ((fov / 2.).tan() / aspect).atan()
+        };
+
+        // Into the tracer's view space, which looks down -z
+        let flip = glm::scale(&Mat4::identity(), &vec3(1., 1., -1.));
+        let view = flip * camera.camera_from_world * self.mirror;
+
+        self.scene.camera = Camera::from_view(&view, aspect);
+        self.scene
+            .camera
+            .set_fov(fovy.to_degrees())
+            .set_clip_planes(0.1, 1000.)
+            .set_aperture_radius(camera.lens_radius)
+            .set_focus_distance(camera.focal_distance);
+
+        ImportedScene {
+            scene: self.scene,
+            width: self.width,
+            height: self.height,
+            samples: self.samples,
+            warnings: self.warnings,
+        }
+    }
+}
+
+fn diffuse(albedo: Vec3) -> Material {
+    Material::Diffuse { albedo }
+}
+
+// Reflectance at normal incidence of the named metal spectra PBRT ships, or of a complex index of
+// refraction given as RGB
+fn conductor_color(params: &Params) -> Vec3 {
+    if let (Some(eta), Some(k)) = (params.rgb("eta"), params.rgb("k")) {
+        return conductor_albedo(&eta, &k);
+    }
+
+    // e.g. "metal-Au-eta", the default is copper
+    let name = params.string("eta").unwrap_or("metal-Cu-eta");
+    params.string("k");
+
+    match name.split('-').nth(1) {
+        // PBRT's named spectra include these oxides, which aren't metals
+        Some("MgO") | Some("TiO2") => vec3(0.10, 0.10, 0.10),
+        element => element
+            .and_then(metal_albedo)
+            .unwrap_or(vec3(0.96, 0.64, 0.54)),
+    }
+}
+
+fn triangle_mesh(params: &Params) -> Result<Mesh, String> {
+    let positions = params.floats("P").ok_or("trianglemesh without P")?;
+    let verts: Vec<_> = positions
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+
+    let indices = match params.ints("indices") {
+        Some(indices) => indices,
+        // Indices may only be left out for a single triangle
+        None if verts.len() == 3 => vec![0, 1, 2],
+        None => return Err("trianglemesh without indices".into()),
+    };
+
+    let normals = params.floats("N").unwrap_or_default();
+    let uvs = params.floats("uv").unwrap_or_default();
+
+    Ok(Mesh {
+        verts,
+        normals: normals
+            .chunks_exact(3)
+            .map(|n| [n[0], n[1], n[2]])
+            .collect(),
+        uvs: uvs.chunks_exact(2).map(|t| [t[0], t[1]]).collect(),
+        colors: Vec::new(),
+        tris: indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect(),
+    })
+}
+
+// Camera from world transform, PBRT's camera looks down +z with `up` along +y
+fn look_at(eye: &Vec3, target: &Vec3, up: &Vec3) -> Option<Mat4> {
+    let dir = (target - eye).normalize();
+    let right = up.normalize().cross(&dir);
+    if right.norm() == 0. {
+        return None;
+    }
+
+    let right = right.normalize();
+    let up = dir.cross(&right);
+
+    #[rustfmt::skip]
+    let world_from_camera = Mat4::new(
+        right.x, up.x, dir.x, eye.x,
+        right.y, up.y, dir.y, eye.y,
+        right.z, up.z, dir.z, eye.z,
+        0., 0., 0., 1.,
+    );
+
+    world_from_camera.try_inverse()
+}
+
+// The directive's leading strings, e.g. the type of a shape, and the rest of the arguments
+fn strings<const N: usize>(args: &[Arg]) -> Result<([String; N], &[Arg]), String> {
+    if args.len() < N {
+        return Err(format!("expected {} string arguments", N));
+    }
+
+    let mut strings = Vec::with_capacity(N);
+    for arg in &args[..N] {
+        match arg {
+            Arg::Value(Value::Str(s)) => strings.push(s.clone()),
+            _ => return Err(format!("expected {} string arguments", N)),
+        }
+    }
+
+    Ok((strings.try_into().unwrap(), &args[N..]))
+}
+
+// Numbers of a transform directive, which may be bracketed or not
+fn numbers<const N: usize>(args: &[Arg]) -> Result<[f32; N], String> {
+    let values = args.iter().flat_map(|arg| match arg {
+        Arg::Value(value) => std::slice::from_ref(value),
+        Arg::List(values) => values.as_slice(),
+    });
+
+    let numbers: Vec<f32> = values
+        .map(|value| match value {
+            Value::Number(n) => Ok(*n),
+            _ => Err(format!("expected {} numbers", N)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    numbers
+        .try_into()
+        .map_err(|v: Vec<f32>| format!("expected {} numbers, got {}", N, v.len()))
+}
+
+impl Params {
+    fn parse(args: &[Arg]) -> Result<Self, String> {
+        let mut params = Vec::new();
+
+        for pair in args.chunks(2) {
+            let [Arg::Value(Value::Str(declaration)), value] = pair else {
+                return Err("expected a parameter declaration like \"float radius\"".into());
+            };
+            let Some((ty, name)) = declaration.split_once(char::is_whitespace) else {
+                return Err(format!("invalid parameter declaration \"{}\"", declaration));
+            };
+
+            let values = match value {
+                Arg::Value(value) => vec![value.clone()],
+                Arg::List(values) => values.clone(),
+            };
+
+            params.push(Param {
+                ty: ty.to_string(),
+                name: name.trim().to_string(),
+                values,
+                used: Cell::new(false),
+            });
+        }
+
+        Ok(Self(params))
+    }
+
+    // Parameter `name` if it has one of the types, marked as used
+    fn get(&self, name: &str, types: &[&str]) -> Option<&[Value]> {
+        let param = self
+            .0
+            .iter()
+            .find(|p| p.name == name && types.contains(&p.ty.as_str()))?;
+
+        param.used.set(true);
+        Some(&param.values)
+    }
+
+    fn floats(&self, name: &str) -> Option<Vec<f32>> {
+        let types = [
+            "float", "point2", "vector2", "point3", "vector3", "normal3", "normal", "point",
+            "vector",
+        ];
+
+        let values = self.get(name, &types)?;
+        Some(values.iter().filter_map(Value::number).collect())
+    }
+
+    fn float(&self, name: &str, default: f32) -> f32 {
+        self.get(name, &["float"])
+            .and_then(|v| v.first()?.number())
+            .unwrap_or(default)
+    }
+
+    fn ints(&self, name: &str) -> Option<Vec<i32>> {
+        let values = self.get(name, &["integer"])?;
+        Some(
+            values
+                .iter()
+                .filter_map(|v| Some(v.number()? as i32))
+                .collect(),
+        )
+    }
+
+    fn int(&self, name: &str, default: i32) -> i32 {
+        self.ints(name)
+            .and_then(|v| v.first().copied())
+            .unwrap_or(default)
+    }
+
+    fn bool(&self, name: &str, default: bool) -> bool {
+        let value = self.get(name, &["bool"]).and_then(|v| v.first());
+
+        match value {
+            Some(Value::Bool(b)) => *b,
+            Some(Value::Str(s)) => s == "true",
+            _ => default,
+        }
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name, &["string", "spectrum"])?.first()? {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // Only RGB colors, spectra and blackbody emitters are left unused
+    fn rgb(&self, name: &str) -> Option<Vec3> {
+        let values = self.get(name, &["rgb", "color"])?;
+        match values {
+            [Value::Number(r), Value::Number(g), Value::Number(b)] => Some(vec3(*r, *g, *b)),
+            _ => None,
+        }
+    }
+
+    fn point(&self, name: &str) -> Option<Vec3> {
+        match self.floats(name)?[..] {
+            [x, y, z] => Some(vec3(x, y, z)),
+            _ => None,
+        }
+    }
+
+    // The tracer's roughness is closest to the microfacet alpha, which PBRT takes as the square
+    // root of the roughness unless remapping is turned off
+    fn roughness(&self) -> f32 {
+        let roughness = match self.get("roughness", &["float"]) {
+            Some(_) => self.float("roughness", 0.),
+            None => (self.float("uroughness", 0.) + self.float("vroughness", 0.)) / 2.,
+        };
+
+        if self.bool("remaproughness", true) {
+            roughness.sqrt()
+        } else {
+            roughness
+        }
+    }
+}
+
+impl Value {
+    fn number(&self) -> Option<f32> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+// Splits the file into directives with their arguments
+fn parse_directives(source: &str, file: &str) -> Result<Vec<Directive>, String> {
+    let mut directives: Vec<Directive> = Vec::new();
+    let mut list: Option<Vec<Value>> = None;
+
+    for token in tokenize(source, file)? {
+        let error = |message: &str| format!("{}:{}: {}", file, token.line, message);
+
+        let value = if token.quoted {
+            Value::Str(token.text)
+        } else {
+            match token.text.as_str() {
+                "[" if list.is_none() => {
+                    list = Some(Vec::new());
+                    continue;
+                }
+                "]" => {
+                    let values = list.take().ok_or_else(|| error("unexpected ]"))?;
+                    let directive = directives.last_mut().ok_or_else(|| error("unexpected ]"))?;
+                    directive.args.push(Arg::List(values));
+                    continue;
+                }
+                "[" => return Err(error("nested [")),
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                text => match text.parse() {
+                    Ok(number) => Value::Number(number),
+                    Err(_) if list.is_some() => return Err(error("missing ]")),
+                    Err(_) => {
+                        directives.push(Directive {
+                            name: text.to_string(),
+                            args: Vec::new(),
+                            location: format!("{}:{}", file, token.line),
+                        });
+                        continue;
+                    }
+                },
+            }
+        };
+
+        match &mut list {
+            Some(values) => values.push(value),
+            None => directives
+                .last_mut()
+                .ok_or_else(|| error("expected a directive"))?
+                .args
+                .push(Arg::Value(value)),
+        }
+    }
+
+    if list.is_some() {
+        return Err(format!("{}: missing ]", file));
+    }
+
+    Ok(directives)
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+    line: usize,
+}
+
+fn tokenize(source: &str, file: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|c| *c != '\n').is_some() {},
+            '"' => {
+                let start = line;
+                let mut text = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some(c) => text.push(c),
+                            None => break,
+                        },
+                        Some('\n') => {
+                            line += 1;
+                            text.push('\n');
+                        }
+                        Some(c) => text.push(c),
+                        None => return Err(format!("{}:{}: unterminated string", file, start)),
+                    }
+                }
+
+                tokens.push(Token {
+                    text,
+                    quoted: true,
+                    line: start,
+                });
+            }
+            '[' | ']' => tokens.push(Token {
+                text: c.to_string(),
+                quoted: false,
+                line,
+            }),
+            c => {
+                let mut text = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"\"[]#".contains(*c)) {
+                    text.push(c);
+                }
+
+                tokens.push(Token {
+                    text,
+                    quoted: false,
+                    line,
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
@@ -0,0 +1,185 @@
+use std::thread;
+
+use glm::{vec3, Vec3};
+
+use crate::ray::{Hit, Instance, Ray};
+use crate::sampler::Rng;
+use crate::{Bvh, Film, Light, Material, ObjectManager, Scene};
+
+// Bounces before a path is terminated, russian roulette starts after `MIN_DEPTH`
+const MAX_DEPTH: u32 = 8;
+const MIN_DEPTH: u32 = 3;
+// Offset applied to secondary ray origins so they don't hit the surface they left
+const EPSILON: f32 = 1e-4;
+
+// Snapshot of a scene that can be shared across render threads. `Scene` itself holds the
+// event listeners, which are not `Sync`
+struct TraceScene<'a> {
+    instances: Vec<Instance<'a>>,
+    tlas: &'a Bvh,
+    materials: &'a [Material],
+    lights: Vec<Light>,
+    background: Vec3,
+}
+
+pub(crate) fn render(
+    scene: &Scene,
+    meshes: &ObjectManager,
+    width: usize,
+    height: usize,
+    spp: u32,
+) -> Film {
+    let mut film = Film::new(width, height);
+
+    if width == 0 || height == 0 {
+        return film;
+    }
+
+    let top = scene.acceleration(meshes);
+    let trace_scene = TraceScene {
+        instances: (0..top.len())
+            .map(|i| top.instance(scene, meshes, i))
+            .collect(),
+        tlas: &top.bvh,
+        materials: &scene.materials,
+        lights: scene.lights.iter().flatten().copied().collect(),
+        background: scene.background(),
+    };
+    // The image decides the aspect ratio, not the window the scene was last shown in
+    let mut camera = scene.camera.clone();
+    camera.set_aspect(width as f32 / height as f32);
+    let rays = camera.rays();
+    let spp = spp.max(1);
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let rows_per_thread = height.div_ceil(threads);
+
+    thread::scope(|s| {
+        for (chunk, pixels) in film
+            .pixels_mut()
+            .chunks_mut(rows_per_thread * width)
+            .enumerate()
+        {
+            let trace_scene = &trace_scene;
+            let rays = &rays;
+
+            s.spawn(move || {
+                for (i, pixel) in pixels.iter_mut().enumerate() {
+                    let index = chunk * rows_per_thread * width + i;
+                    let (x, y) = (index % width, index / width);
+                    let mut rng = Rng::new(index as u64, 0);
+
+                    let mut sum = Vec3::zeros();
+                    for _ in 0..spp {
+                        let u = (x as f32 + rng.next_f32()) / width as f32;
+                        let v = (y as f32 + rng.next_f32()) / height as f32;
+
+                        let ray = rays.lens_ray(u, v, &mut rng);
+                        sum += trace_scene.radiance(ray, &mut rng);
+                    }
+
+                    *pixel = sum / spp as f32;
+                }
+            });
+        }
+    });
+
+    film
+}
+
+impl TraceScene<'_> {
+    fn radiance(&self, mut ray: Ray, rng: &mut Rng) -> Vec3 {
+        let mut color = Vec3::zeros();
+        let mut throughput = vec3(1., 1., 1.);
+
+        for depth in 0..MAX_DEPTH {
+            let Some((hit, instance)) = self.intersect(&ray, f32::INFINITY) else {
+                return color + throughput.component_mul(&self.background);
+            };
+
+            let instance = &self.instances[instance];
+            let material = instance.object.material.resolve(self.materials);
+            color += throughput.component_mul(&material.emission());
+
+            // Vertex colors tint whatever the material reflects
+            let tint = instance.mesh.color_at(hit.triangle, &hit.barycentrics);
+
+            // Next event estimation for diffuse and rough metal surfaces. Lights have no geometry,
+            // so paths can't reach them by bouncing and nothing is counted twice
+            let direct = self.sample_lights(&hit, &ray.dir, material, rng);
+            color += throughput.component_mul(&tint.component_mul(&direct));
+
+            let Some(scatter) = material.scatter(&ray.dir, &hit.normal, rng) else {
+                break;
+            };
+            throughput = throughput.component_mul(&scatter.attenuation.component_mul(&tint));
+
+            if depth >= MIN_DEPTH {
+                let survive = throughput.max().clamp(0.05, 1.0);
+                if rng.next_f32() >= survive {
+                    break;
+                }
+                throughput /= survive;
+            }
+
+            // Offset to the side of the surface the path continues on, refracted paths go through
+            let side = hit.normal.dot(&scatter.dir).signum();
+            ray = Ray::new(ray.at(hit.t) + hit.normal * EPSILON * side, scatter.dir);
+        }
+
+        color
+    }
+
+    // Light from every light that the material at the hit reflects back along `incoming`
+    fn sample_lights(
+        &self,
+        hit: &Hit,
+        incoming: &Vec3,
+        material: &Material,
+        rng: &mut Rng,
+    ) -> Vec3 {
+        let mut total = Vec3::zeros();
+        let facing = if incoming.dot(&hit.normal) < 0. {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+
+        for light in &self.lights {
+            let Some(sample) = light.sample(&hit.position, rng) else {
+                continue;
+            };
+
+            let reflectance = material.reflectance(incoming, &hit.normal, &sample.dir);
+            if reflectance == Vec3::zeros() {
+                continue;
+            }
+
+            let shadow = Ray::new(hit.position + facing * EPSILON, sample.dir);
+            if !self.occluded(&shadow, sample.distance - EPSILON) {
+                total += sample.radiance.component_mul(&reflectance);
+            }
+        }
+
+        total
+    }
+
+    fn occluded(&self, ray: &Ray, max_t: f32) -> bool {
+        self.tlas.any_hit(ray, max_t, |i, max_t| {
+            self.instances[i].intersect(ray, max_t).is_some()
+        })
+    }
+
+    // Closest hit and the index of the instance it belongs to
+    fn intersect(&self, ray: &Ray, max_t: f32) -> Option<(Hit, usize)> {
+        let mut closest = None;
+
+        self.tlas.traverse(ray, max_t, |i, max_t| {
+            let hit = self.instances[i].intersect(ray, max_t)?;
+            closest = Some((hit, i));
+            Some(hit.t)
+        });
+
+        closest
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests;
+
+use glm::{Mat3, Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::Quaternion;
+
+// Placement of an object relative to its parent, or to the world for objects without one. Applied
+// as scale, then rotation, then translation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Transform {
+    pub position: Vec3,
+    pub orientation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            position: Vec3::zeros(),
+            orientation: Quaternion::IDENTITY,
+            scale: Vec3::repeat(1.),
+        }
+    }
+}
+
+impl Transform {
+    pub fn new(position: Vec3, orientation: Quaternion, scale: Vec3) -> Self {
+        Self {
+            position,
+            orientation,
+            scale,
+        }
+    }
+
+    // Splits an affine transform into translation, rotation and scale. Shear can't be represented
+    // and is lost
+    pub fn from_matrix(mat: &Mat4) -> Self {
+        let linear: Mat3 = mat.fixed_view::<3, 3>(0, 0).into();
+        let mut scale = Vec3::from_fn(|i, _| linear.column(i).norm());
+
+        // Mirroring transforms keep the rotation proper by flipping one axis
+        if linear.determinant() < 0. {
+            scale.x = -scale.x;
+        }
+
+        let mut rotation = linear;
+        for i in 0..3 {
+            if scale[i] != 0. {
+                rotation.column_mut(i).unscale_mut(scale[i]);
+            }
+        }
+
+        Self {
+            position: mat.column(3).xyz(),
+            // `Quaternion::as_matrix` is the transpose of the rotation it describes
+            orientation: Quaternion::from_matrix3(&rotation.transpose()),
+            scale,
+        }
+    }
+
+    // Same composition the vertex shader uses. Multiply a parent's matrix from the left to place
+    // the transform relative to it
+    pub fn matrix(&self) -> Mat4 {
+        glm::translate(&Mat4::identity(), &self.position)
+            * self.orientation.as_matrix()
+            * glm::scale(&Mat4::identity(), &self.scale)
+    }
+
+    // Worked out from the parts instead of inverting `matrix`. `None` if any scale is zero
+    pub fn inverse_matrix(&self) -> Option<Mat4> {
+        if self.scale.iter().any(|s| *s == 0.) {
+            return None;
+        }
+
+        Some(
+            glm::scale(&Mat4::identity(), &self.scale.map(|s| 1. / s))
+                * self.orientation.as_matrix().transpose()
+                * glm::translate(&Mat4::identity(), &-self.position),
+        )
+    }
+
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        (self.matrix() * point.push(1.)).xyz()
+    }
+
+    // Scales and rotates without translating, for offsets and directions
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        (self.orientation.as_matrix() * vector.component_mul(&self.scale).push(0.)).xyz()
+    }
+
+    // Normals stay perpendicular to the surface under non-uniform scale, the result is unit length
+    pub fn transform_normal(&self, normal: &Vec3) -> Vec3 {
+        let scaled = normal.component_div(&self.scale);
+        (self.orientation.as_matrix() * scaled.push(0.))
+            .xyz()
+            .normalize()
+    }
+}
@@ -0,0 +1,100 @@
+use glm::{Mat4, Vec3};
+use proptest::prelude::*;
+
+use super::Transform;
+use crate::Quaternion;
+
+// Matrices go through a decomposition or an inverse, which loses a few more bits than one product
+const EPS: f32 = 1e-3;
+
+fn unit_quaternion() -> impl Strategy<Value = Quaternion> {
+    prop::array::uniform4(-1f32..1.)
+        .prop_filter("too close to zero to normalize", |c| {
+            c.iter().map(|c| c * c).sum::<f32>() > 0.01
+        })
+        .prop_map(|[i, j, k, l]| *Quaternion::new(i, j, k, l).normalize())
+}
+
+fn vector() -> impl Strategy<Value = Vec3> {
+    prop::array::uniform3(-10f32..10.).prop_map(Vec3::from)
+}
+
+// Scales far enough from zero for the decomposition to be well conditioned
+fn scale() -> impl Strategy<Value = Vec3> {
+    prop::array::uniform3(0.1f32..5.).prop_map(Vec3::from)
+}
+
+fn transform() -> impl Strategy<Value = Transform> {
+    (vector(), unit_quaternion(), scale()).prop_map(|(p, o, s)| Transform::new(p, o, s))
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() <= EPS * a.abs().max(b.abs()).max(1.)
+}
+
+fn close_vec(a: &Vec3, b: &Vec3) -> bool {
+    (0..3).all(|i| close(a[i], b[i]))
+}
+
+fn close_mat(a: &Mat4, b: &Mat4) -> bool {
+    (0..16).all(|i| close(a[i], b[i]))
+}
+
+proptest! {
+    #[test]
+    fn matrix_round_trip(t in transform()) {
+        let back = Transform::from_matrix(&t.matrix());
+
+        prop_assert!(close_mat(&back.matrix(), &t.matrix()));
+        prop_assert!(close_vec(&back.position, &t.position));
+        prop_assert!(close_vec(&back.scale, &t.scale));
+    }
+
+    #[test]
+    fn mirrored_matrix_round_trip(t in transform(), axis in 0usize..3) {
+        // One negative scale turns the basis left handed, which no rotation can do
+        let mut mirrored = t;
+        mirrored.scale[axis] = -mirrored.scale[axis];
+
+        let back = Transform::from_matrix(&mirrored.matrix());
+        prop_assert!(close_mat(&back.matrix(), &mirrored.matrix()));
+    }
+
+    #[test]
+    fn inverse_undoes_matrix(t in transform()) {
+        let product = t.inverse_matrix().unwrap() * t.matrix();
+        prop_assert!(close_mat(&product, &Mat4::identity()));
+    }
+
+    #[test]
+    fn transforms_match_matrix(t in transform(), v in vector()) {
+        let m = t.matrix();
+        prop_assert!(close_vec(&t.transform_point(&v), &(m * v.push(1.)).xyz()));
+        prop_assert!(close_vec(&t.transform_vector(&v), &(m * v.push(0.)).xyz()));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular(t in transform(), a in vector(), b in vector()) {
+        let normal = a.cross(&b);
+        prop_assume!(normal.norm() > 0.1);
+
+        // Both tangents span the surface, its transformed normal is perpendicular to them
+        let n = t.transform_normal(&normal);
+        prop_assert!(close(n.norm(), 1.));
+        for tangent in [a, b] {
+            let tangent = t.transform_vector(&tangent).normalize();
+            prop_assert!(close(n.dot(&tangent), 0.));
+        }
+    }
+}
+
+#[test]
+fn default_is_identity() {
+    assert_eq!(Transform::default().matrix(), Mat4::identity());
+}
+
+#[test]
+fn zero_scale_has_no_inverse() {
+    let t = Transform::new(Vec3::zeros(), Quaternion::IDENTITY, Vec3::new(1., 0., 1.));
+    assert!(t.inverse_matrix().is_none());
+}
@@ -2,8 +2,8 @@ use std::{f32::consts::PI, time::Duration};
 
 use lib::{
     glfw,
-    glm::{vec3, Vec3, Vec4},
-    App, Primitive, Quaternion, Scene, Window, WindowOptions,
+    glm::{vec3, Vec3},
+    App, Material, ObjectHandle, Primitive, Quaternion, Ray, Scene, Window, WindowOptions,
 };
 
 use std::sync::{Arc, Mutex};
@@ -16,6 +16,7 @@ fn main() {
         height: 600,
         scene: 0,
         title: String::from("test #1"),
+        shader_dir: None,
     });
 
     generate_scenes(&mut app);
@@ -25,6 +26,7 @@ fn main() {
     //     height: 700,
     //     scene: 1,
     //     title: String::from("Test #2"),
+    //     shader_dir: None,
     // });
 
     app.run()
@@ -33,12 +35,23 @@ fn main() {
 fn generate_scenes(app: &mut App) {
     let mut scene = Scene::new(600. / 800.);
 
-    scene.add_object(
+    let white = scene.add_material(Material::Diffuse {
+        albedo: vec3(1., 1., 1.),
+    });
+    let red = scene.add_material(Material::Metal {
+        albedo: vec3(1., 0., 0.),
+        roughness: 0.2,
+    });
+    let magenta = scene.add_material(Material::Diffuse {
+        albedo: vec3(1., 0., 1.),
+    });
+
+    let bobbing = scene.add_object(
         Primitive::SPHERE,
         Vec3::new(0., 5., 0.),
         Vec3::new(1., 1., 1.),
         Quaternion::from_euler(0., 0., 0.),
-        Vec4::new(1., 1., 1., 1.),
+        white,
     );
 
     scene.add_object(
@@ -46,7 +59,7 @@ fn generate_scenes(app: &mut App) {
         Vec3::new(0., -5., 0.),
         Vec3::new(1., 1., 1.),
         Quaternion::from_euler(0., 0., 0.),
-        Vec4::new(1., 0., 0., 1.),
+        red,
     );
 
     for i in 0..100 {
@@ -56,7 +69,7 @@ fn generate_scenes(app: &mut App) {
                 Vec3::new(i as f32 + 5., 0., j as f32),
                 Vec3::new(0.5, 0.5, 0.5),
                 Quaternion::zero(),
-                Vec4::new(1.0, 0.0, 1.0, 1.0),
+                magenta,
             );
         }
     }
@@ -65,17 +78,19 @@ fn generate_scenes(app: &mut App) {
     scene.camera.rotate(Quaternion::from_euler(0., 0.1, 0.));
 
     scene.set_clear_color(0.2, 0.3, 0.3, 1.);
-    set_listeners(&mut scene);
+    set_listeners(&mut scene, bobbing);
 
     app.register_scene(scene);
 }
 
-fn set_listeners(scene: &mut Scene) {
+fn set_listeners(scene: &mut Scene, bobbing: ObjectHandle) {
     use glfw::{Key, WindowEvent};
 
     let mut mouse_locked = false;
     let mut time = Duration::default();
+    let mut elapsed = Duration::default();
     let mut cursor_pos = (0_f32, 0_f32);
+    let mut hover_ray: Option<Ray> = None;
     let mut frames = 0_u32;
 
     let on_event = move |window: &mut Window, scene: &mut Scene, event: WindowEvent| match event {
@@ -121,7 +136,17 @@ fn set_listeners(scene: &mut Scene) {
 
             mouse_locked = true;
         }
+        WindowEvent::MouseButton(glfw::MouseButtonRight, glfw::Action::Release, _) => {
+            if let Some(ray) = hover_ray {
+                match scene.raycast(&window.object_manager, ray.origin, ray.dir, f32::INFINITY) {
+                    Some(hit) => println!("Picked object {:?} at distance {}", hit.object, hit.t),
+                    None => println!("Nothing under the cursor"),
+                }
+            }
+        }
         WindowEvent::CursorPos(x, y) => {
+            hover_ray = window.cursor_ray(&scene.camera, &event);
+
             let delta = (cursor_pos.0 - x as f32, cursor_pos.1 - y as f32);
             cursor_pos = (x as f32, y as f32);
 
@@ -138,8 +163,13 @@ fn set_listeners(scene: &mut Scene) {
         _ => {}
     };
 
-    let on_update = move |_: &mut Window, _: &mut Scene, delta: Duration| {
+    let on_update = move |_: &mut Window, scene: &mut Scene, delta: Duration| {
         time += delta;
+        elapsed += delta;
+
+        if let Some(object) = scene.get_mut(bobbing) {
+            object.transform.position.y = 5. + elapsed.as_secs_f32().sin();
+        }
 
         if time.as_secs() >= 1 {
             println!("FPS: {}", frames);